@@ -1,11 +1,61 @@
-// use shell::commands::builtins;
-use shell::parser::parser::Parser;
+use shell::runtime::exec::run_line;
+use shell::runtime::histfile;
+use shell::runtime::script::{run_file, run_script};
+use shell::runtime::shell::Shell;
+use shell::runtime::startup::{self, Options};
 use shell::terminal::action::Action;
 use shell::terminal::reader::Reader;
+use std::env;
+use std::io::{self, Read};
+use std::process;
 fn main() {
+  let args: Vec<String> = env::args().collect();
+  let options = match Options::parse(&args) {
+    Ok(options) => options,
+    Err(e) => {
+      eprintln!("rustsh: {}", e);
+      process::exit(2);
+    }
+  };
+  let interactive = options.script.is_none() && nix::unistd::isatty(libc::STDIN_FILENO).unwrap_or(false);
+  let mut shell = Shell::new(interactive);
+  shell.args = options.args.clone();
+  startup::load(&mut shell, &options);
+  if let Some(status) = shell.exiting() {
+    process::exit(status);
+  }
+
+  if let Some(script) = &options.script {
+    shell.name = script.display().to_string();
+    match run_file(&mut shell, script) {
+      Ok(status) => process::exit(shell.exiting().unwrap_or(status)),
+      Err(e) => {
+        eprintln!("rustsh: {}: {}", script.display(), e);
+        process::exit(127);
+      }
+    }
+  }
+  if !interactive {
+    let mut content = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut content) {
+      eprintln!("rustsh: {}", e);
+      process::exit(1);
+    }
+    let status = run_script(&mut shell, "stdin", &content);
+    process::exit(shell.exiting().unwrap_or(status));
+  }
+
   let mut reader = Reader::new();
+  reader.set_history(shell.history.clone());
+  histfile::load(&mut shell);
   loop {
-    match reader.read_line() {
+    histfile::sync(&mut shell);
+    set_window_size(&mut shell, &mut reader);
+    reader.set_prompt(&shell.prompt());
+    reader.set_word_chars(shell.get_var("WORDCHARS").unwrap_or(""));
+    reader.set_vi_mode(shell.settings.vi);
+    reader.set_highlight(shell.get_var("NO_COLOR").is_none() && shell.get_var("TERM") != Some("dumb"));
+    match reader.read_line(&mut shell) {
       Ok(Action::Cancel) => {
         continue;
       }
@@ -14,13 +64,28 @@ fn main() {
         break;
       }
       Ok(Action::Line(input)) => {
-        let mut parser = Parser::new(input);
-        parser.build();
-        let _commands = parser.commands;
+        set_window_size(&mut shell, &mut reader);
+        let started = histfile::start(&mut shell);
+        run_line(&mut shell, &input);
+        histfile::finish(&mut shell, started.elapsed());
+        if shell.exiting().is_some() {
+          break;
+        }
       }
       Err(_e) => {
         break;
       }
     }
   }
+  drop(reader);
+  histfile::save(&mut shell);
+  process::exit(shell.exiting().unwrap_or(shell.status));
+}
+
+// Keeps $COLUMNS and $LINES in step with the terminal, which may have
+// been resized while a command ran or a line was being edited.
+fn set_window_size(shell: &mut Shell, reader: &mut Reader) {
+  let (columns, lines) = reader.window_size();
+  shell.set_var("COLUMNS", &columns.to_string());
+  shell.set_var("LINES", &lines.to_string());
 }
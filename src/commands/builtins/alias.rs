@@ -0,0 +1,43 @@
+use crate::runtime::expand::single_quote;
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut out = command.take_output();
+  let args: Vec<&String> = command.args.iter().filter(|arg| *arg != "-p").collect();
+  if args.is_empty() {
+    for (name, value) in &shell.aliases {
+      writeln!(out, "alias {}={}", name, single_quote(value)).map_err(|e| e.to_string())?;
+    }
+    return Ok(0);
+  }
+  let mut status = 0;
+  for arg in args {
+    match arg.find('=') {
+      Some(idx) => {
+        let name = &arg[..idx];
+        if !valid_name(name) {
+          shell.report(&format!("alias: `{}': invalid alias name", name));
+          status = 1;
+          continue;
+        }
+        shell.aliases.insert(name.to_string(), arg[idx + 1..].to_string());
+      }
+      None => match shell.aliases.get(arg.as_str()) {
+        Some(value) => {
+          writeln!(out, "alias {}={}", arg, single_quote(value)).map_err(|e| e.to_string())?;
+        }
+        None => {
+          shell.report(&format!("alias: {}: not found", arg));
+          status = 1;
+        }
+      },
+    }
+  }
+  Ok(status)
+}
+
+fn valid_name(name: &str) -> bool {
+  !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || "/$`'\"\\=;|&<>(){}".contains(c))
+}
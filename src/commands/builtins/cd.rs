@@ -1,18 +1,196 @@
+use crate::runtime::shell::Shell;
 use crate::tokens::command::Command;
 use std::env;
-use std::error::Error;
-use std::io::{self, Read, Write};
-#[allow(deprecated)]
-pub fn run(command: Command) -> Result<(), String> {
-  if command.args.is_empty() {
-    env::set_current_dir(env::home_dir().unwrap()).unwrap();
-    return Ok(());
-  }
-
-  let mut current_path = env::current_dir().unwrap();
-  current_path.push(&command.args[0]);
-  if env::set_current_dir(current_path.as_path()).is_err() {
-    return Err(format!("{} not found", command.args[0]));
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let (physical, operand) = match parse(&command.args) {
+    Ok(parsed) => parsed,
+    Err(Usage::TooMany) => return Err("too many arguments".to_string()),
+    Err(Usage::Invalid(flag)) => {
+      shell.report(&format!("cd: -{}: invalid option", flag));
+      shell.report("cd: usage: cd [-L|-P] [dir]");
+      return Ok(2);
+    }
+  };
+  let (target, print) = match operand.map(|arg| arg.as_str()) {
+    None => match shell.home() {
+      Some(home) => (home, false),
+      None => return Err("HOME not set".to_string()),
+    },
+    Some("-") => match shell.get_var("OLDPWD") {
+      Some(old) if !old.is_empty() => (PathBuf::from(old), true),
+      _ => return Err("OLDPWD not set".to_string()),
+    },
+    Some(operand) => search_cdpath(shell.get_var("CDPATH"), operand),
+  };
+  let dir = change_dir(shell, &target, physical)
+    .map_err(|e| format!("{}: {}", operand.map(|arg| arg.as_str()).unwrap_or_else(|| "~"), e))?;
+  if print {
+    let mut out = command.take_output();
+    writeln!(out, "{}", dir.display()).map_err(|e| e.to_string())?;
+  }
+  Ok(0)
+}
+
+#[derive(Debug, PartialEq)]
+enum Usage {
+  Invalid(char),
+  TooMany,
+}
+
+// Splits the arguments into whether -P is in effect, the later of -L and
+// -P winning, and the operand. A lone `-` is the operand, not an option.
+fn parse(args: &[String]) -> Result<(bool, Option<&String>), Usage> {
+  let mut physical = false;
+  let mut args = args.iter();
+  let mut operand = None;
+  while let Some(arg) = args.next() {
+    if arg == "--" {
+      operand = args.next();
+      break;
+    }
+    if !arg.starts_with('-') || arg == "-" {
+      operand = Some(arg);
+      break;
+    }
+    for flag in arg[1..].chars() {
+      match flag {
+        'L' => physical = false,
+        'P' => physical = true,
+        _ => return Err(Usage::Invalid(flag)),
+      }
+    }
+  }
+  match args.next() {
+    Some(_) => Err(Usage::TooMany),
+    None => Ok((physical, operand)),
+  }
+}
+
+// Returns the directory to change to and whether it should be printed,
+// which POSIX requires when a non-empty CDPATH entry was used.
+pub fn search_cdpath(cdpath: Option<&str>, operand: &str) -> (PathBuf, bool) {
+  let path = Path::new(operand);
+  let dotted = matches!(path.components().next(), Some(Component::CurDir) | Some(Component::ParentDir));
+  if path.is_absolute() || dotted {
+    return (path.to_path_buf(), false);
+  }
+  for entry in cdpath.unwrap_or("").split(':') {
+    let candidate = match entry {
+      "" => Path::new(".").join(path),
+      _ => Path::new(entry).join(path),
+    };
+    if candidate.is_dir() {
+      return (candidate, !entry.is_empty());
+    }
+  }
+  (path.to_path_buf(), false)
+}
+
+// Shared by every builtin that moves the shell: changes the process
+// directory and keeps $PWD/$OLDPWD in sync. Logical mode resolves `..`
+// against $PWD instead of following the symlinks that led there.
+pub fn change_dir(shell: &mut Shell, target: &Path, physical: bool) -> Result<PathBuf, String> {
+  let old = match shell.get_var("PWD") {
+    Some(pwd) if Path::new(pwd).is_absolute() => Some(PathBuf::from(pwd)),
+    _ => env::current_dir().ok(),
+  };
+  let logical = match (target.is_absolute(), &old) {
+    (true, _) => normalize(target),
+    (false, Some(old)) => normalize(&old.join(target)),
+    (false, None) => target.to_path_buf(),
   };
-  Ok(())
+  let path = if physical { target.to_path_buf() } else { logical.clone() };
+  env::set_current_dir(&path).map_err(|e| describe(&e))?;
+  let dir = match physical {
+    true => env::current_dir().map_err(|e| describe(&e))?,
+    false => logical,
+  };
+  if let Some(old) = old {
+    shell.set_var("OLDPWD", &old.display().to_string());
+  }
+  shell.set_var("PWD", &dir.display().to_string());
+  Ok(dir)
+}
+
+pub fn describe(error: &io::Error) -> String {
+  match error.raw_os_error() {
+    Some(libc::ENOENT) => "No such file or directory".to_string(),
+    Some(libc::ENOTDIR) => "Not a directory".to_string(),
+    Some(libc::EACCES) => "Permission denied".to_string(),
+    _ => error.to_string(),
+  }
+}
+
+// Removes `.` and folds `..` into its parent without touching the
+// filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+  let mut result = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir => match result.components().next_back() {
+        Some(Component::Normal(_)) => {
+          result.pop();
+        }
+        Some(Component::RootDir) => {}
+        _ => result.push(".."),
+      },
+      _ => result.push(component.as_os_str()),
+    }
+  }
+  if result.as_os_str().is_empty() {
+    result.push(".");
+  }
+  result
+}
+
+#[cfg(test)]
+pub mod cd_test {
+  use super::*;
+  use crate::testing::TempDir;
+  use std::fs;
+  fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|arg| arg.to_string()).collect()
+  }
+  #[test]
+  fn parse_test() {
+    let dir = "dir".to_string();
+    let dash = "-".to_string();
+    assert_eq!(parse(&args(&["-P", "dir"])), Ok((true, Some(&dir))));
+    assert_eq!(parse(&args(&["-LP", "dir"])), Ok((true, Some(&dir))));
+    assert_eq!(parse(&args(&["-PL"])), Ok((false, None)));
+    assert_eq!(parse(&args(&["-P", "--", "-"])), Ok((true, Some(&dash))));
+    assert_eq!(parse(&args(&["-"])), Ok((false, Some(&dash))));
+    assert_eq!(parse(&args(&["-x"])), Err(Usage::Invalid('x')));
+    assert_eq!(parse(&args(&["-Lx", "dir"])), Err(Usage::Invalid('x')));
+    assert_eq!(parse(&args(&["a", "b"])), Err(Usage::TooMany));
+  }
+  #[test]
+  fn invalid_option_test() {
+    use crate::runtime::exec::run_line;
+    use crate::testing::collect_reports;
+    let mut shell = Shell::new(false);
+    let reported = collect_reports(&mut shell);
+    assert_eq!(run_line(&mut shell, "cd -x"), 2);
+    assert_eq!(reported.borrow()[0], "rustsh: cd: -x: invalid option");
+  }
+  #[test]
+  fn normalize_test() {
+    assert_eq!(normalize(Path::new("/a/./b/../c")), PathBuf::from("/a/c"));
+    assert_eq!(normalize(Path::new("/..")), PathBuf::from("/"));
+    assert_eq!(normalize(Path::new("a/../..")), PathBuf::from(".."));
+  }
+  #[test]
+  fn cdpath_test() {
+    let dir = TempDir::new("cdpath");
+    fs::create_dir_all(dir.join("project")).unwrap();
+    let cdpath = format!("/nonexistent:{}", dir.path().display());
+    assert_eq!(search_cdpath(Some(&cdpath), "project"), (dir.join("project"), true));
+    assert_eq!(search_cdpath(Some(&cdpath), "./project"), (PathBuf::from("./project"), false));
+    assert_eq!(search_cdpath(Some(&cdpath), "/tmp"), (PathBuf::from("/tmp"), false));
+    assert_eq!(search_cdpath(None, "missing"), (PathBuf::from("missing"), false));
+  }
 }
@@ -0,0 +1,59 @@
+use super::type_::describe;
+use crate::runtime::exec::run_command;
+use crate::runtime::expand::single_quote;
+use crate::runtime::lookup::{kind_of, Kind};
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut args = command.args.clone().into_iter().peekable();
+  let mut query = None;
+  while let Some(arg) = args.peek() {
+    match arg.as_str() {
+      "-v" | "-V" => query = Some(arg.clone()),
+      "--" => {
+        args.next();
+        break;
+      }
+      _ if arg.starts_with('-') => return Err(format!("{}: invalid option", arg)),
+      _ => break,
+    }
+    args.next();
+  }
+  let mut args: Vec<String> = args.collect();
+  if args.is_empty() {
+    return Ok(0);
+  }
+  match query {
+    Some(flag) => {
+      let mut out = command.take_output();
+      let mut status = 0;
+      for name in &args {
+        let kind = match kind_of(shell, name) {
+          Some(kind) => kind,
+          None => {
+            if flag == "-V" {
+              shell.report(&format!("command: {}: not found", name));
+            }
+            status = 1;
+            continue;
+          }
+        };
+        let line = match (flag.as_str(), &kind) {
+          ("-V", _) => describe(name, &kind),
+          (_, Kind::Alias(value)) => format!("alias {}={}", name, single_quote(value)),
+          (_, Kind::File(path, _)) => path.display().to_string(),
+          _ => name.clone(),
+        };
+        writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+      }
+      Ok(status)
+    }
+    None => {
+      command.command = args.remove(0);
+      command.args = args;
+      Ok(run_command(shell, command, false))
+    }
+  }
+}
@@ -0,0 +1,48 @@
+use super::complete::parse;
+use crate::runtime::complete::generate;
+use crate::runtime::shell::Shell;
+use crate::terminal::complete::{Context, Request};
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let options = parse(&command.args, true)?;
+  if options.operands.len() > 1 {
+    return Err("too many arguments".to_string());
+  }
+  let word = options.operands.first().cloned().unwrap_or_default();
+  let request = Request {
+    line: word.clone(),
+    pos: word.chars().count(),
+    start: 0,
+    raw: word.clone(),
+    word: word.clone(),
+    words: vec![word],
+    context: Context::Argument,
+  };
+  let mut results: Vec<String> = Vec::new();
+  for candidate in generate(shell, &options.spec, &request) {
+    // directories are listed without the slash completion adds
+    let value = match candidate.suffix {
+      None if candidate.value.len() > 1 => candidate.value.trim_end_matches('/').to_string(),
+      _ => candidate.value,
+    };
+    if !results.contains(&value) {
+      results.push(value);
+    }
+  }
+  let status = if results.is_empty() { 1 } else { 0 };
+  match options.array {
+    Some(array) => {
+      shell.unset_var(&array);
+      shell.arrays.insert(array, results);
+    }
+    None => {
+      let mut out = command.take_output();
+      for result in results {
+        writeln!(out, "{}", result).map_err(|e| e.to_string())?;
+      }
+    }
+  }
+  Ok(status)
+}
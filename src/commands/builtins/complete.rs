@@ -0,0 +1,131 @@
+use crate::runtime::complete::Spec;
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+  pub spec: Spec,
+  pub print: bool,
+  pub remove: bool,
+  // compgen -V: store the results in an array
+  pub array: Option<String>,
+  pub operands: Vec<String>,
+}
+
+// Parses the options `complete` and `compgen` share; -p/-r belong to
+// `complete` and -V to `compgen`.
+pub fn parse(args: &[String], compgen: bool) -> Result<Options, String> {
+  let mut options = Options::default();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    if arg == "--" {
+      options.operands.extend(args.cloned());
+      break;
+    }
+    if !arg.starts_with('-') || arg.len() < 2 {
+      options.operands.push(arg.clone());
+      options.operands.extend(args.cloned());
+      break;
+    }
+    let flags: Vec<char> = arg[1..].chars().collect();
+    for (i, &flag) in flags.iter().enumerate() {
+      match flag {
+        'c' => options.spec.commands = true,
+        'd' => options.spec.directories = true,
+        'f' => options.spec.files = true,
+        'p' if !compgen => options.print = true,
+        'r' if !compgen => options.remove = true,
+        'W' | 'F' | 'V' if flag != 'V' || compgen => {
+          let attached: String = flags[i + 1..].iter().collect();
+          let value = match attached.is_empty() {
+            true => args.next().cloned().ok_or(format!("-{}: option requires an argument", flag))?,
+            false => attached,
+          };
+          match flag {
+            'W' => options.spec.words = Some(value),
+            'F' => options.spec.function = Some(value),
+            _ => options.array = Some(value),
+          }
+          break;
+        }
+        _ => return Err(format!("-{}: invalid option", flag)),
+      }
+    }
+  }
+  Ok(options)
+}
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let options = parse(&command.args, false)?;
+  let mut out = command.take_output();
+  let mut status = 0;
+  if options.remove {
+    if options.operands.is_empty() {
+      shell.completions.clear();
+    }
+    for name in &options.operands {
+      if shell.completions.remove(name).is_none() {
+        shell.report(&format!("complete: {}: no completion specification", name));
+        status = 1;
+      }
+    }
+    return Ok(status);
+  }
+  if options.print || command.args.is_empty() {
+    if options.operands.is_empty() {
+      for (name, spec) in shell.completions.specs() {
+        writeln!(out, "{}", spec.command(name)).map_err(|e| e.to_string())?;
+      }
+    }
+    for name in &options.operands {
+      match shell.completions.get(name) {
+        Some(spec) => writeln!(out, "{}", spec.command(name)).map_err(|e| e.to_string())?,
+        None => {
+          shell.report(&format!("complete: {}: no completion specification", name));
+          status = 1;
+        }
+      }
+    }
+    return Ok(status);
+  }
+  if options.operands.is_empty() {
+    shell.report("complete: usage: complete [-cdfpr] [-W wordlist] [-F function] [name ...]");
+    return Ok(2);
+  }
+  for name in &options.operands {
+    shell.completions.insert(name, options.spec.clone());
+  }
+  Ok(0)
+}
+
+#[cfg(test)]
+pub mod complete_test {
+  use super::*;
+  use crate::runtime::exec::run_line;
+  fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+  }
+  #[test]
+  fn parse_test() {
+    let options = parse(&strings(&["-cf", "-Wa b", "-F", "_f", "git", "-x"]), false).unwrap();
+    assert!(options.spec.commands && options.spec.files && !options.spec.directories);
+    assert_eq!(options.spec.words, Some("a b".to_string()));
+    assert_eq!(options.spec.function, Some("_f".to_string()));
+    assert_eq!(options.operands, strings(&["git", "-x"]));
+    assert!(parse(&strings(&["-V", "x"]), false).is_err());
+    assert_eq!(parse(&strings(&["-V", "x"]), true).unwrap().array, Some("x".to_string()));
+    assert!(parse(&strings(&["-W"]), false).is_err());
+  }
+  #[test]
+  fn complete_test() {
+    let mut shell = Shell::new(false);
+    run_line(&mut shell, "complete -d -W 'a b' cd2 cd3");
+    assert_eq!(shell.completions.get("cd2").unwrap().command("cd2"), "complete -d -W 'a b' cd2");
+    run_line(&mut shell, "complete -r cd2");
+    assert!(shell.completions.get("cd2").is_none());
+    assert_eq!(run_line(&mut shell, "complete -r cd2"), 1);
+    run_line(&mut shell, "complete -r");
+    assert!(shell.completions.get("cd3").is_none());
+  }
+}
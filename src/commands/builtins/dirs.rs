@@ -0,0 +1,117 @@
+use super::cd::change_dir;
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut long = false;
+  let mut per_line = false;
+  let mut verbose = false;
+  let mut index = None;
+  let len = shell.dir_entries().len();
+  for arg in &command.args {
+    match arg.as_str() {
+      "-c" => shell.dir_stack.clear(),
+      "-l" => long = true,
+      "-p" => per_line = true,
+      "-v" => verbose = true,
+      _ => match parse_index(arg, len) {
+        Some(i) => index = Some(i?),
+        None => return Err(format!("{}: invalid argument", arg)),
+      },
+    }
+  }
+  let mut out = command.take_output();
+  let result = match index {
+    Some(i) => format!("{}\n", display(shell, &shell.dir_entries()[i], long)),
+    None => listing(shell, long, per_line, verbose),
+  };
+  out.write_all(result.as_bytes()).map_err(|e| e.to_string())?;
+  Ok(0)
+}
+
+// `+N` counts from the left of the `dirs` listing and `-N` from the right.
+// Returns None when the argument is not of that form.
+pub fn parse_index(arg: &str, len: usize) -> Option<Result<usize, String>> {
+  let (from_right, digits) = match arg.chars().next() {
+    Some('+') => (false, &arg[1..]),
+    Some('-') => (true, &arg[1..]),
+    _ => return None,
+  };
+  let n = digits.parse::<usize>().ok()?;
+  let index = match from_right {
+    true => len.checked_sub(n + 1),
+    false => Some(n).filter(|&n| n < len),
+  };
+  Some(index.ok_or_else(|| format!("{}: directory stack index out of range", arg)))
+}
+
+pub fn display(shell: &Shell, dir: &Path, long: bool) -> String {
+  if let (false, Some(home)) = (long, shell.home()) {
+    if let Ok(rest) = dir.strip_prefix(&home) {
+      return match rest.as_os_str().is_empty() {
+        true => "~".to_string(),
+        false => format!("~/{}", rest.display()),
+      };
+    }
+  }
+  dir.display().to_string()
+}
+
+pub fn listing(shell: &Shell, long: bool, per_line: bool, verbose: bool) -> String {
+  let entries = shell.dir_entries();
+  let mut result = String::new();
+  for (i, dir) in entries.iter().enumerate() {
+    let dir = display(shell, dir, long);
+    if verbose {
+      result.push_str(&format!("{:2}  {}\n", i, dir));
+    } else if per_line {
+      result.push_str(&format!("{}\n", dir));
+    } else {
+      if i > 0 {
+        result.push(' ');
+      }
+      result.push_str(&dir);
+    }
+  }
+  if !verbose && !per_line {
+    result.push('\n');
+  }
+  result
+}
+
+// Installs a new listing; the first entry becomes the working directory
+// unless `change` is false.
+pub fn replace(shell: &mut Shell, mut entries: Vec<PathBuf>, change: bool) -> Result<(), String> {
+  if change {
+    let target = entries[0].clone();
+    entries[0] = change_dir(shell, &target, false).map_err(|e| format!("{}: {}", target.display(), e))?;
+  }
+  shell.dir_stack = entries.split_off(1);
+  Ok(())
+}
+
+#[cfg(test)]
+pub mod dirs_test {
+  use super::*;
+  #[test]
+  fn parse_index_test() {
+    assert_eq!(parse_index("+0", 3), Some(Ok(0)));
+    assert_eq!(parse_index("-0", 3), Some(Ok(2)));
+    assert_eq!(parse_index("+2", 3), Some(Ok(2)));
+    assert!(matches!(parse_index("+3", 3), Some(Err(_))));
+    assert_eq!(parse_index("dir", 3), None);
+  }
+  #[test]
+  fn listing_test() {
+    let mut shell = Shell::new(false);
+    shell.vars.remove("HOME");
+    shell.vars.remove("PWD");
+    shell.set_var("HOME", "/home/user");
+    shell.set_var("PWD", "/home/user/src");
+    shell.dir_stack = vec![PathBuf::from("/tmp"), PathBuf::from("/home/user")];
+    assert_eq!(listing(&shell, false, false, false), "~/src /tmp ~\n");
+    assert_eq!(listing(&shell, true, false, true), " 0  /home/user/src\n 1  /tmp\n 2  /home/user\n");
+  }
+}
@@ -0,0 +1,106 @@
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(_shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut out = command.take_output();
+  let mut newline = true;
+  let mut escapes = false;
+  let mut args = command.args.iter().peekable();
+  while let Some(arg) = args.peek() {
+    let flags = match arg.strip_prefix('-') {
+      Some(flags) if !flags.is_empty() && flags.chars().all(|c| "neE".contains(c)) => flags,
+      _ => break,
+    };
+    for flag in flags.chars() {
+      match flag {
+        'n' => newline = false,
+        'e' => escapes = true,
+        _ => escapes = false,
+      }
+    }
+    args.next();
+  }
+  let mut result = String::new();
+  for (i, arg) in args.enumerate() {
+    if i > 0 {
+      result.push(' ');
+    }
+    if !escapes {
+      result.push_str(arg);
+      continue;
+    }
+    let (text, stop) = unescape(arg);
+    result.push_str(&text);
+    if stop {
+      newline = false;
+      break;
+    }
+  }
+  if newline {
+    result.push('\n');
+  }
+  out.write_all(result.as_bytes()).map_err(|e| e.to_string())?;
+  out.flush().map_err(|e| e.to_string())?;
+  Ok(0)
+}
+
+// Interprets backslash escapes as `echo -e` and `printf %b` do. The flag is
+// set when `\c` asked for all further output to be suppressed.
+pub fn unescape(s: &str) -> (String, bool) {
+  let mut result = String::new();
+  let mut chars = s.chars().peekable();
+  while let Some(ch) = chars.next() {
+    if ch != '\\' {
+      result.push(ch);
+      continue;
+    }
+    match chars.next() {
+      Some('a') => result.push('\x07'),
+      Some('b') => result.push('\x08'),
+      Some('c') => return (result, true),
+      Some('e') | Some('E') => result.push('\x1b'),
+      Some('f') => result.push('\x0c'),
+      Some('n') => result.push('\n'),
+      Some('r') => result.push('\r'),
+      Some('t') => result.push('\t'),
+      Some('v') => result.push('\x0b'),
+      Some('\\') => result.push('\\'),
+      Some('0') => result.push(numeric(&mut chars, 8, 3)),
+      Some('x') => match chars.peek() {
+        Some(c) if c.is_ascii_hexdigit() => result.push(numeric(&mut chars, 16, 2)),
+        _ => result.push_str("\\x"),
+      },
+      Some(c) => {
+        result.push('\\');
+        result.push(c);
+      }
+      None => result.push('\\'),
+    }
+  }
+  (result, false)
+}
+
+pub fn numeric<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>, radix: u32, max: usize) -> char {
+  let mut value = 0;
+  for _ in 0..max {
+    match chars.peek().and_then(|c| c.to_digit(radix)) {
+      Some(digit) => {
+        value = value * radix + digit;
+        chars.next();
+      }
+      None => break,
+    }
+  }
+  std::char::from_u32(value).unwrap_or('\0')
+}
+
+#[cfg(test)]
+pub mod echo_test {
+  use super::unescape;
+  #[test]
+  fn unescape_test() {
+    assert_eq!(unescape("a\\tb\\n\\x41\\0101\\q"), ("a\tb\nAA\\q".to_string(), false));
+    assert_eq!(unescape("a\\cb"), ("a".to_string(), true));
+  }
+}
@@ -0,0 +1,26 @@
+use crate::runtime::shell::{Flow, Shell};
+use crate::tokens::command::Command;
+
+// There is no warning about running jobs yet: without job control every
+// child is waited for before the prompt returns, so none can be left
+// running. It belongs here once `&` and a job table exist.
+pub fn run(shell: &mut Shell, command: Command) -> Result<i32, String> {
+  if shell.interactive && shell.call_depth == 0 {
+    eprintln!("exit");
+  }
+  let status = match command.args.first() {
+    Some(arg) => match arg.parse::<i32>() {
+      Ok(n) => n & 0xff,
+      Err(_) => {
+        shell.report(&format!("exit: {}: numeric argument required", arg));
+        2
+      }
+    },
+    None => shell.status,
+  };
+  if command.args.len() > 1 {
+    return Err("too many arguments".to_string());
+  }
+  shell.flow = Some(Flow::Exit(status));
+  Ok(status)
+}
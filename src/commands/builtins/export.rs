@@ -0,0 +1,32 @@
+use crate::runtime::expand::{is_name, single_quote};
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut out = command.take_output();
+  let names: Vec<&String> = command.args.iter().filter(|arg| *arg != "-p").collect();
+  if names.is_empty() {
+    for (name, var) in shell.vars.iter().filter(|(_, var)| var.exported) {
+      writeln!(out, "export {}={}", name, single_quote(&var.value)).map_err(|e| e.to_string())?;
+    }
+    return Ok(0);
+  }
+  let mut status = 0;
+  for arg in names {
+    let (name, value) = match arg.find('=') {
+      Some(idx) => (&arg[..idx], Some(&arg[idx + 1..])),
+      None => (arg.as_str(), None),
+    };
+    if !is_name(name) {
+      shell.report(&format!("export: `{}': not a valid identifier", arg));
+      status = 1;
+      continue;
+    }
+    if let Some(value) = value {
+      shell.set_var(name, value);
+    }
+    shell.export_var(name);
+  }
+  Ok(status)
+}
@@ -0,0 +1,202 @@
+use crate::runtime::exec::run_line;
+use crate::runtime::expand::single_quote;
+use crate::runtime::script::run_script;
+use crate::runtime::shell::Shell;
+use crate::runtime::tempfile::TempFile;
+use crate::terminal::history::Entry;
+use crate::tokens::command::Command;
+use std::env;
+use std::io::Write;
+use std::process;
+
+#[derive(Debug, Default)]
+struct Options {
+  list: bool,
+  numbers: bool,
+  reverse: bool,
+  substitute: bool,
+  editor: Option<String>,
+  operands: Vec<String>,
+}
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let options = parse(&command.args)?;
+  let entries = completed(shell);
+  let mut out = command.take_output();
+  if options.substitute || options.editor.as_deref() == Some("-") {
+    let mut operands = options.operands.iter().peekable();
+    let mut pairs = Vec::new();
+    while let Some(pair) = operands.peek().and_then(|operand| split_pair(operand)) {
+      pairs.push(pair);
+      operands.next();
+    }
+    let index = find(&entries, operands.next().map(|s| s.as_str()).unwrap_or("-1"))?;
+    let mut line = entries[index].command.clone();
+    for (old, new) in pairs {
+      line = line.replacen(old, new, 1);
+    }
+    writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+    replace_running(shell, &line);
+    return Ok(run_line(shell, &line));
+  }
+  let default = if options.list { "-16" } else { "-1" };
+  let first = options.operands.first().map(|s| s.as_str()).unwrap_or(default);
+  let mut first = find(&entries, first)?;
+  let last = match options.operands.get(1) {
+    Some(last) => find(&entries, last)?,
+    None if options.list => entries.len() - 1,
+    None => first,
+  };
+  let mut last = last;
+  let mut reverse = options.reverse;
+  if first > last {
+    std::mem::swap(&mut first, &mut last);
+    reverse = !reverse;
+  }
+  let mut range: Vec<(usize, &Entry)> = (first..=last).map(|i| (i, &entries[i])).collect();
+  if reverse {
+    range.reverse();
+  }
+  if options.list {
+    for (i, entry) in range {
+      let line = match options.numbers {
+        true => format!("{}\t{}\n", i + 1, entry.command),
+        false => format!("\t{}\n", entry.command),
+      };
+      out.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    return Ok(0);
+  }
+  let editor = match options.editor {
+    Some(editor) => editor,
+    None => ["FCEDIT", "EDITOR"]
+      .iter()
+      .filter_map(|name| shell.get_var(name))
+      .find(|value| !value.is_empty())
+      .unwrap_or("vi")
+      .to_string(),
+  };
+  let content: String = range.iter().map(|(_, entry)| format!("{}\n", entry.command)).collect();
+  let file = TempFile::create("fc", "", &content).map_err(|e| format!("{}: {}", env::temp_dir().display(), e))?;
+  let path = file.path().display().to_string();
+  let status = run_line(shell, &format!("{} {}", editor, single_quote(&path)));
+  if status != 0 {
+    return Ok(status);
+  }
+  let edited = file.read().map_err(|e| format!("{}: {}", path, e))?;
+  drop(file);
+  out.write_all(edited.as_bytes()).map_err(|e| e.to_string())?;
+  out.flush().map_err(|e| e.to_string())?;
+  replace_running(shell, edited.trim_end());
+  Ok(run_script(shell, "fc", &edited))
+}
+
+fn parse(args: &[String]) -> Result<Options, String> {
+  let mut options = Options {
+    numbers: true,
+    ..Options::default()
+  };
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let negative_number = arg.len() > 1 && arg[1..].chars().all(|c| c.is_ascii_digit());
+    if !arg.starts_with('-') || arg == "-" || negative_number {
+      options.operands.push(arg.clone());
+      options.operands.extend(args.cloned());
+      break;
+    }
+    if arg == "--" {
+      options.operands.extend(args.cloned());
+      break;
+    }
+    for flag in arg[1..].chars() {
+      match flag {
+        'l' => options.list = true,
+        'n' => options.numbers = false,
+        'r' => options.reverse = true,
+        's' => options.substitute = true,
+        'e' => match args.next() {
+          Some(editor) => options.editor = Some(editor.clone()),
+          None => return Err("-e: option requires an argument".to_string()),
+        },
+        _ => return Err(format!("-{}: invalid option", flag)),
+      }
+    }
+  }
+  Ok(options)
+}
+
+// The line that invoked fc is already in history; it is not a candidate.
+fn completed(shell: &Shell) -> Vec<Entry> {
+  let history = shell.history.borrow();
+  let mut entries: Vec<Entry> = history.cmd_list.iter().cloned().collect();
+  if let Some(last) = entries.last() {
+    if last.status.is_none() && last.session == Some(process::id()) {
+      entries.pop();
+    }
+  }
+  entries
+}
+
+// The re-executed command takes the place of the fc line, as in bash.
+fn replace_running(shell: &mut Shell, line: &str) {
+  if let Some(entry) = shell.history.borrow_mut().pending_mut() {
+    if entry.status.is_none() {
+      entry.command = line.to_string();
+    }
+  }
+}
+
+fn split_pair(operand: &str) -> Option<(&str, &str)> {
+  let idx = operand.find('=')?;
+  Some((&operand[..idx], &operand[idx + 1..]))
+}
+
+// Numbers are history positions, negative numbers count back from the most
+// recent command and anything else selects the latest command with that
+// prefix.
+fn find(entries: &[Entry], spec: &str) -> Result<usize, String> {
+  if entries.is_empty() {
+    return Err("no command found".to_string());
+  }
+  let len = entries.len() as i64;
+  let index = match spec.parse::<i64>() {
+    Ok(n) if n > 0 => (n - 1).min(len - 1),
+    Ok(n) => (len + n).max(0),
+    Err(_) => match entries.iter().rposition(|entry| entry.command.starts_with(spec)) {
+      Some(index) => index as i64,
+      None => return Err(format!("{}: no command found", spec)),
+    },
+  };
+  Ok(index as usize)
+}
+
+#[cfg(test)]
+pub mod fc_test {
+  use super::*;
+  fn entries(commands: &[&str]) -> Vec<Entry> {
+    commands
+      .iter()
+      .map(|command| Entry {
+        command: command.to_string(),
+        ..Entry::default()
+      })
+      .collect()
+  }
+  #[test]
+  fn find_test() {
+    let entries = entries(&["ls", "make", "make test", "git status"]);
+    assert_eq!(find(&entries, "-1"), Ok(3));
+    assert_eq!(find(&entries, "-2"), Ok(2));
+    assert_eq!(find(&entries, "2"), Ok(1));
+    assert_eq!(find(&entries, "make"), Ok(2));
+    assert_eq!(find(&entries, "-16"), Ok(0));
+    assert!(find(&entries, "cargo").is_err());
+  }
+  #[test]
+  fn parse_test() {
+    let args: Vec<String> = ["-lnr", "-5", "-1"].iter().map(|s| s.to_string()).collect();
+    let options = parse(&args).unwrap();
+    assert!(options.list && options.reverse && !options.numbers);
+    assert_eq!(options.operands, vec!["-5", "-1"]);
+  }
+}
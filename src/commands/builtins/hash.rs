@@ -0,0 +1,39 @@
+use crate::runtime::lookup::search;
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut out = command.take_output();
+  let mut names = Vec::new();
+  for arg in &command.args {
+    match arg.as_str() {
+      "-r" => shell.hash.clear(),
+      _ if arg.starts_with('-') => return Err(format!("{}: invalid option", arg)),
+      _ => names.push(arg.clone()),
+    }
+  }
+  if command.args.is_empty() {
+    let mut entries = shell.hash.entries().peekable();
+    if entries.peek().is_none() {
+      shell.report("hash: hash table empty");
+      return Ok(0);
+    }
+    writeln!(out, "hits\tcommand").map_err(|e| e.to_string())?;
+    for (_, hashed) in entries {
+      writeln!(out, "{:4}\t{}", hashed.hits, hashed.path.display()).map_err(|e| e.to_string())?;
+    }
+    return Ok(0);
+  }
+  let mut status = 0;
+  for name in names {
+    match search(shell.get_var("PATH"), &name) {
+      Ok(path) => shell.hash.insert(&name, path),
+      Err(_) => {
+        shell.report(&format!("hash: {}: not found", name));
+        status = 1;
+      }
+    }
+  }
+  Ok(status)
+}
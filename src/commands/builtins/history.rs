@@ -0,0 +1,187 @@
+use crate::runtime::histfile;
+use crate::runtime::shell::Shell;
+use crate::terminal::history::{read_entries, write_entries, Entry};
+use crate::tokens::command::Command;
+use std::ffi::CString;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Default)]
+struct Filter {
+  failed: bool,
+  cwd: Option<String>,
+  session: Option<u32>,
+  since: Option<u64>,
+}
+
+impl Filter {
+  fn matches(&self, entry: &Entry) -> bool {
+    (!self.failed || entry.status.map(|status| status != 0).unwrap_or(false))
+      && (self.cwd.is_none() || entry.cwd == self.cwd)
+      && (self.session.is_none() || entry.session == self.session)
+      && self
+        .since
+        .map(|since| entry.start.map(|start| start >= since).unwrap_or(false))
+        .unwrap_or(true)
+  }
+}
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut filter = Filter::default();
+  let mut count = None;
+  let mut args = command.args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-c" => {
+        shell.history.borrow_mut().reset();
+        return Ok(0);
+      }
+      "-d" => match args.next() {
+        Some(offset) => return delete(shell, offset),
+        None => return Err("-d: option requires an argument".to_string()),
+      },
+      "-w" | "-r" | "-a" => return file(shell, arg, args.next()),
+      "--failed" => filter.failed = true,
+      "--here" => filter.cwd = shell.get_var("PWD").map(String::from),
+      "--session" => filter.session = Some(process::id()),
+      "--since" => match args.next().map(|age| parse_age(age)) {
+        Some(Some(age)) => filter.since = Some(now().saturating_sub(age)),
+        Some(None) => return Err("--since: invalid age".to_string()),
+        None => return Err("--since: option requires an argument".to_string()),
+      },
+      _ if count.is_none() && !arg.starts_with('-') => match arg.parse::<usize>() {
+        Ok(n) => count = Some(n),
+        Err(_) => return Err(format!("{}: numeric argument required", arg)),
+      },
+      _ => return Err(format!("{}: invalid option", arg)),
+    }
+  }
+  let time_format = shell.get_var("HISTTIMEFORMAT").map(String::from);
+  let history = shell.history.borrow();
+  let entries: Vec<(usize, &Entry)> = history
+    .cmd_list
+    .iter()
+    .enumerate()
+    .filter(|(_, entry)| filter.matches(entry))
+    .collect();
+  let skip = entries.len().saturating_sub(count.unwrap_or(entries.len()));
+  let mut out = command.take_output();
+  for (i, entry) in &entries[skip..] {
+    let time = match (&time_format, entry.start) {
+      (Some(format), Some(start)) => format_time(start, format),
+      _ => String::new(),
+    };
+    writeln!(out, "{:5}  {}{}", i + 1, time, entry.command).map_err(|e| e.to_string())?;
+  }
+  Ok(0)
+}
+
+fn delete(shell: &mut Shell, offset: &str) -> Result<i32, String> {
+  let mut history = shell.history.borrow_mut();
+  let len = history.cmd_list.len() as i64;
+  let index = match offset.parse::<i64>() {
+    Ok(n) if n > 0 => n - 1,
+    Ok(n) if n < 0 => len + n,
+    _ => -1,
+  };
+  if index < 0 || index >= len {
+    return Err(format!("{}: history position out of range", offset));
+  }
+  history.remove(index as usize);
+  Ok(0)
+}
+
+fn file(shell: &mut Shell, flag: &str, path: Option<&String>) -> Result<i32, String> {
+  let histfile = histfile::path(shell);
+  let path = match (path, &histfile) {
+    (Some(path), _) => PathBuf::from(path),
+    (None, Some(path)) => path.clone(),
+    (None, None) => return Err("HISTFILE not set".to_string()),
+  };
+  let is_histfile = histfile.as_ref() == Some(&path);
+  let mut history = shell.history.borrow_mut();
+  let result = match flag {
+    "-w" => write_entries(&path, history.cmd_list.iter(), false).and_then(|_| match is_histfile {
+      true => history.mark_saved(&path),
+      false => Ok(()),
+    }),
+    "-a" if is_histfile => history.append(&path, false),
+    "-a" => write_entries(&path, history.unsaved().iter(), true),
+    _ => read_entries(&path).map(|entries| history.insert_saved(entries)),
+  };
+  result.map_err(|e| format!("{}: {}", path.display(), e))?;
+  Ok(0)
+}
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|time| time.as_secs())
+    .unwrap_or(0)
+}
+
+// Ages look like `90`, `30m`, `2h` or `1d`; plain numbers are seconds.
+fn parse_age(age: &str) -> Option<u64> {
+  let (digits, unit) = match age.char_indices().last()? {
+    (i, c) if c.is_ascii_alphabetic() => (&age[..i], c),
+    _ => (age, 's'),
+  };
+  let value = digits.parse::<u64>().ok()?;
+  let scale = match unit {
+    's' => 1,
+    'm' => 60,
+    'h' => 60 * 60,
+    'd' => 24 * 60 * 60,
+    'w' => 7 * 24 * 60 * 60,
+    _ => return None,
+  };
+  Some(value * scale)
+}
+
+pub fn format_time(secs: u64, format: &str) -> String {
+  let format = match CString::new(format) {
+    Ok(format) => format,
+    Err(_) => return String::new(),
+  };
+  let time = secs as libc::time_t;
+  let mut buf = [0u8; 256];
+  let len = unsafe {
+    let mut tm: libc::tm = std::mem::zeroed();
+    libc::localtime_r(&time, &mut tm);
+    libc::strftime(buf.as_mut_ptr() as *mut libc::c_char, buf.len(), format.as_ptr(), &tm)
+  };
+  String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(test)]
+pub mod history_test {
+  use super::*;
+  #[test]
+  fn parse_age_test() {
+    assert_eq!(parse_age("90"), Some(90));
+    assert_eq!(parse_age("2h"), Some(7200));
+    assert_eq!(parse_age("1d"), Some(86400));
+    assert_eq!(parse_age("1y"), None);
+  }
+  #[test]
+  fn filter_test() {
+    let entry = Entry {
+      command: "make".to_string(),
+      start: Some(1000),
+      cwd: Some("/src".to_string()),
+      status: Some(2),
+      ..Entry::default()
+    };
+    let filter = Filter {
+      failed: true,
+      cwd: Some("/src".to_string()),
+      since: Some(500),
+      ..Filter::default()
+    };
+    assert!(filter.matches(&entry));
+    assert!(!filter.matches(&Entry { status: Some(0), ..entry.clone() }));
+    assert!(!filter.matches(&Entry { start: Some(100), ..entry }));
+  }
+}
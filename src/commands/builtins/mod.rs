@@ -1,2 +1,70 @@
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+pub mod alias;
 pub mod cd;
-pub mod pwd;
\ No newline at end of file
+pub mod command;
+pub mod compgen;
+pub mod complete;
+pub mod dirs;
+pub mod echo;
+pub mod exit;
+pub mod export;
+pub mod false_;
+pub mod fc;
+pub mod hash;
+pub mod history;
+pub mod popd;
+pub mod printf;
+pub mod pushd;
+pub mod pwd;
+pub mod read;
+pub mod return_;
+pub mod set;
+pub mod shift;
+pub mod source;
+pub mod test;
+pub mod true_;
+pub mod type_;
+pub mod unalias;
+pub mod unset;
+
+pub type Builtin = fn(&mut Shell, Command) -> Result<i32, String>;
+
+pub const NAMES: &[&str] = &[
+  ".", "[", "alias", "cd", "command", "compgen", "complete", "dirs", "echo", "exit", "export", "false", "fc", "hash", "history",
+  "popd", "printf", "pushd", "pwd", "read", "return", "set", "shift", "source", "test", "true", "type",
+  "unalias", "unset",
+];
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+  match name {
+    "alias" => Some(alias::run),
+    "cd" => Some(cd::run),
+    "command" => Some(command::run),
+    "compgen" => Some(compgen::run),
+    "complete" => Some(complete::run),
+    "dirs" => Some(dirs::run),
+    "echo" => Some(echo::run),
+    "exit" => Some(exit::run),
+    "export" => Some(export::run),
+    "false" => Some(false_::run),
+    "fc" => Some(fc::run),
+    "hash" => Some(hash::run),
+    "history" => Some(history::run),
+    "popd" => Some(popd::run),
+    "printf" => Some(printf::run),
+    "pushd" => Some(pushd::run),
+    "pwd" => Some(pwd::run),
+    "read" => Some(read::run),
+    "return" => Some(return_::run),
+    "set" => Some(set::run),
+    "shift" => Some(shift::run),
+    "source" | "." => Some(source::run),
+    "test" | "[" => Some(test::run),
+    "true" => Some(true_::run),
+    "type" => Some(type_::run),
+    "unalias" => Some(unalias::run),
+    "unset" => Some(unset::run),
+    _ => None,
+  }
+}
@@ -0,0 +1,34 @@
+use super::dirs::{listing, parse_index, replace};
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut no_cd = false;
+  let mut operand = None;
+  for arg in &command.args {
+    match arg.as_str() {
+      "-n" => no_cd = true,
+      "--" => {}
+      _ if operand.is_some() => return Err("too many arguments".to_string()),
+      _ => operand = Some(arg.clone()),
+    }
+  }
+  let mut entries = shell.dir_entries();
+  if entries.len() < 2 {
+    return Err("directory stack empty".to_string());
+  }
+  let index = match operand {
+    None => 0,
+    Some(arg) => match parse_index(&arg, entries.len()) {
+      Some(index) => index?,
+      None => return Err(format!("{}: invalid argument", arg)),
+    },
+  };
+  let index = if no_cd && index == 0 { 1 } else { index };
+  entries.remove(index);
+  replace(shell, entries, index == 0)?;
+  let mut out = command.take_output();
+  out.write_all(listing(shell, false, false, false).as_bytes()).map_err(|e| e.to_string())?;
+  Ok(0)
+}
@@ -0,0 +1,462 @@
+use super::echo::{numeric, unescape};
+use crate::runtime::expand::is_name;
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut out = command.take_output();
+  let mut args = command.args.as_slice();
+  let mut var = None;
+  if args.first().map(|arg| arg.as_str()) == Some("-v") {
+    match args.get(1) {
+      Some(name) if is_name(name) => var = Some(name.clone()),
+      Some(name) => return Err(format!("`{}': not a valid identifier", name)),
+      None => return Err("-v: option requires an argument".to_string()),
+    }
+    args = &args[2..];
+  }
+  if args.first().map(|arg| arg.as_str()) == Some("--") {
+    args = &args[1..];
+  }
+  let (format, args) = match args.split_first() {
+    Some(split) => split,
+    None => return Err("usage: printf [-v var] format [arguments]".to_string()),
+  };
+  let mut formatter = Formatter::new(args);
+  let result = formatter.run(format);
+  for error in &formatter.errors {
+    shell.report(&format!("printf: {}", error));
+  }
+  match var {
+    Some(name) => shell.set_var(&name, &result),
+    None => {
+      out.write_all(result.as_bytes()).map_err(|e| e.to_string())?;
+      out.flush().map_err(|e| e.to_string())?;
+    }
+  }
+  Ok(if formatter.errors.is_empty() { 0 } else { 1 })
+}
+
+pub fn format(format: &str, args: &[String]) -> Result<String, Vec<String>> {
+  let mut formatter = Formatter::new(args);
+  let result = formatter.run(format);
+  match formatter.errors.is_empty() {
+    true => Ok(result),
+    false => Err(formatter.errors),
+  }
+}
+
+#[derive(Default)]
+struct Spec {
+  left: bool,
+  plus: bool,
+  space: bool,
+  alternate: bool,
+  zero: bool,
+  width: usize,
+  precision: Option<usize>,
+}
+
+struct Formatter<'a> {
+  args: &'a [String],
+  next: usize,
+  stopped: bool,
+  errors: Vec<String>,
+}
+
+impl<'a> Formatter<'a> {
+  fn new(args: &'a [String]) -> Formatter<'a> {
+    Formatter {
+      args,
+      next: 0,
+      stopped: false,
+      errors: Vec::new(),
+    }
+  }
+
+  // The format is reused while it keeps consuming arguments.
+  fn run(&mut self, format: &str) -> String {
+    let mut result = String::new();
+    loop {
+      let start = self.next;
+      self.pass(format, &mut result);
+      if self.stopped || self.next == start || self.next >= self.args.len() {
+        return result;
+      }
+    }
+  }
+
+  fn pass(&mut self, format: &str, result: &mut String) {
+    let mut chars = format.chars().peekable();
+    while let Some(ch) = chars.next() {
+      match ch {
+        '\\' => {
+          if !self.escape(&mut chars, result) {
+            self.stopped = true;
+            return;
+          }
+        }
+        '%' if chars.peek() == Some(&'%') => {
+          chars.next();
+          result.push('%');
+        }
+        '%' => {
+          if !self.conversion(&mut chars, result) {
+            self.stopped = true;
+            return;
+          }
+        }
+        _ => result.push(ch),
+      }
+    }
+  }
+
+  fn escape(&mut self, chars: &mut Peekable<Chars>, result: &mut String) -> bool {
+    match chars.peek() {
+      Some(c) if c.is_digit(8) => {
+        result.push(numeric(chars, 8, 3));
+        true
+      }
+      Some('"') | Some('\'') => {
+        result.push(chars.next().unwrap_or_default());
+        true
+      }
+      Some(&c) => {
+        chars.next();
+        let (text, stop) = unescape(&format!("\\{}", c));
+        result.push_str(&text);
+        !stop
+      }
+      None => {
+        result.push('\\');
+        true
+      }
+    }
+  }
+
+  fn arg(&mut self) -> Option<&'a str> {
+    let arg = self.args.get(self.next).map(|arg| arg.as_str());
+    self.next += 1;
+    arg
+  }
+
+  fn number(&mut self, chars: &mut Peekable<Chars>) -> Option<usize> {
+    if chars.peek() == Some(&'*') {
+      chars.next();
+      return Some(self.next_integer().max(0) as usize);
+    }
+    let mut digits = String::new();
+    while let Some(&c) = chars.peek() {
+      if !c.is_ascii_digit() {
+        break;
+      }
+      digits.push(c);
+      chars.next();
+    }
+    digits.parse().ok()
+  }
+
+  fn conversion(&mut self, chars: &mut Peekable<Chars>, result: &mut String) -> bool {
+    let mut spec = Spec::default();
+    while let Some(&c) = chars.peek() {
+      match c {
+        '-' => spec.left = true,
+        '+' => spec.plus = true,
+        ' ' => spec.space = true,
+        '#' => spec.alternate = true,
+        '0' => spec.zero = true,
+        _ => break,
+      }
+      chars.next();
+    }
+    spec.width = self.number(chars).unwrap_or(0);
+    if chars.peek() == Some(&'.') {
+      chars.next();
+      spec.precision = Some(self.number(chars).unwrap_or(0));
+    }
+    let conversion = match chars.next() {
+      Some(c) => c,
+      None => {
+        self.errors.push("`%': missing format character".to_string());
+        return false;
+      }
+    };
+    let text = match conversion {
+      's' => {
+        let arg = self.arg().unwrap_or("");
+        match spec.precision {
+          Some(precision) => arg.chars().take(precision).collect(),
+          None => arg.to_string(),
+        }
+      }
+      'b' => {
+        let (text, stop) = unescape(self.arg().unwrap_or(""));
+        if stop {
+          result.push_str(&pad(&spec, "", &text));
+          return false;
+        }
+        match spec.precision {
+          Some(precision) => text.chars().take(precision).collect(),
+          None => text,
+        }
+      }
+      'c' => self.arg().and_then(|arg| arg.chars().next()).map(String::from).unwrap_or_default(),
+      'd' | 'i' => {
+        let value = self.next_integer();
+        let sign = sign(&spec, value < 0);
+        let digits = precise(&spec, value.unsigned_abs().to_string());
+        result.push_str(&pad_number(&spec, &sign, &digits));
+        return true;
+      }
+      'u' | 'o' | 'x' | 'X' => {
+        let value = self.next_integer() as u64;
+        let (digits, prefix) = match conversion {
+          'o' => (format!("{:o}", value), "0"),
+          'x' => (format!("{:x}", value), "0x"),
+          'X' => (format!("{:X}", value), "0X"),
+          _ => (value.to_string(), ""),
+        };
+        let mut digits = precise(&spec, digits);
+        let prefix = match (spec.alternate && value != 0, conversion) {
+          (true, 'o') if !digits.starts_with('0') => prefix,
+          (true, 'x') | (true, 'X') => prefix,
+          _ => "",
+        };
+        if prefix == "0" {
+          digits.insert(0, '0');
+          result.push_str(&pad_number(&spec, "", &digits));
+        } else {
+          result.push_str(&pad_number(&spec, prefix, &digits));
+        }
+        return true;
+      }
+      'e' | 'E' | 'f' | 'F' | 'g' | 'G' => {
+        let value = self.next_float();
+        let sign = sign(&spec, value < 0.0);
+        let digits = float(&spec, conversion, value.abs());
+        let spec = Spec {
+          zero: spec.zero && value.is_finite(),
+          precision: None,
+          ..spec
+        };
+        result.push_str(&pad_number(&spec, &sign, &digits));
+        return true;
+      }
+      c => {
+        self.errors.push(format!("`{}': invalid format character", c));
+        return false;
+      }
+    };
+    result.push_str(&pad(&spec, "", &text));
+    true
+  }
+
+  fn next_integer(&mut self) -> i64 {
+    let arg = self.arg().unwrap_or("");
+    self.integer(arg)
+  }
+
+  fn next_float(&mut self) -> f64 {
+    let arg = self.arg().unwrap_or("");
+    self.float(arg)
+  }
+
+  fn integer(&mut self, arg: &str) -> i64 {
+    match parse_integer(arg) {
+      Ok(value) => value,
+      Err(value) => {
+        self.errors.push(format!("{}: invalid number", arg));
+        value
+      }
+    }
+  }
+
+  fn float(&mut self, arg: &str) -> f64 {
+    let trimmed = arg.trim();
+    if let Some(value) = char_value(trimmed) {
+      return value as f64;
+    }
+    if trimmed.is_empty() {
+      return 0.0;
+    }
+    match trimmed.parse::<f64>() {
+      Ok(value) => value,
+      Err(_) => {
+        self.errors.push(format!("{}: invalid number", arg));
+        0.0
+      }
+    }
+  }
+}
+
+// A leading quote makes the value the code point of the following char.
+fn char_value(arg: &str) -> Option<u32> {
+  let mut chars = arg.chars();
+  match chars.next() {
+    Some('\'') | Some('"') => Some(chars.next().map(|c| c as u32).unwrap_or(0)),
+    _ => None,
+  }
+}
+
+// On error the value parsed so far is returned, as C's strtol would.
+fn parse_integer(arg: &str) -> Result<i64, i64> {
+  let trimmed = arg.trim_start();
+  if let Some(value) = char_value(trimmed) {
+    return Ok(value as i64);
+  }
+  if trimmed.is_empty() {
+    return Ok(0);
+  }
+  let (negative, rest) = match trimmed.strip_prefix('-') {
+    Some(rest) => (true, rest),
+    None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+  };
+  let (radix, digits) = if let Some(hex) = rest.strip_prefix("0x").or_else(|| rest.strip_prefix("0X")) {
+    (16, hex)
+  } else if rest.len() > 1 && rest.starts_with('0') {
+    (8, &rest[1..])
+  } else {
+    (10, rest)
+  };
+  let mut value: i64 = 0;
+  let mut valid = !digits.is_empty();
+  for c in digits.chars() {
+    match c.to_digit(radix) {
+      Some(digit) => value = value.wrapping_mul(radix as i64).wrapping_add(digit as i64),
+      None => {
+        valid = false;
+        break;
+      }
+    }
+  }
+  let value = if negative { value.wrapping_neg() } else { value };
+  match valid {
+    true => Ok(value),
+    false => Err(value),
+  }
+}
+
+fn sign(spec: &Spec, negative: bool) -> String {
+  match (negative, spec.plus, spec.space) {
+    (true, _, _) => "-".to_string(),
+    (false, true, _) => "+".to_string(),
+    (false, false, true) => " ".to_string(),
+    _ => String::new(),
+  }
+}
+
+fn precise(spec: &Spec, digits: String) -> String {
+  match spec.precision {
+    Some(0) if digits == "0" => String::new(),
+    Some(precision) if precision > digits.len() => {
+      format!("{}{}", "0".repeat(precision - digits.len()), digits)
+    }
+    _ => digits,
+  }
+}
+
+fn pad(spec: &Spec, prefix: &str, text: &str) -> String {
+  let len = prefix.chars().count() + text.chars().count();
+  let fill = " ".repeat(spec.width.saturating_sub(len));
+  match spec.left {
+    true => format!("{}{}{}", prefix, text, fill),
+    false => format!("{}{}{}", fill, prefix, text),
+  }
+}
+
+fn pad_number(spec: &Spec, prefix: &str, digits: &str) -> String {
+  if spec.zero && !spec.left && spec.precision.is_none() {
+    let len = prefix.len() + digits.len();
+    let zeros = "0".repeat(spec.width.saturating_sub(len));
+    return format!("{}{}{}", prefix, zeros, digits);
+  }
+  pad(spec, prefix, digits)
+}
+
+fn float(spec: &Spec, conversion: char, value: f64) -> String {
+  let upper = conversion.is_ascii_uppercase();
+  let text = if value.is_nan() {
+    "nan".to_string()
+  } else if value.is_infinite() {
+    "inf".to_string()
+  } else {
+    let precision = spec.precision.unwrap_or(6);
+    match conversion.to_ascii_lowercase() {
+      'f' => format!("{:.*}", precision, value),
+      'e' => exponent(value, precision),
+      _ => general(value, precision, spec.alternate),
+    }
+  };
+  match upper {
+    true => text.to_uppercase(),
+    false => text,
+  }
+}
+
+fn exponent(value: f64, precision: usize) -> String {
+  let text = format!("{:.*e}", precision, value);
+  let (mantissa, exp) = text.split_at(text.find('e').unwrap_or(text.len()));
+  let exp: i32 = exp.get(1..).and_then(|exp| exp.parse().ok()).unwrap_or(0);
+  let sign = if exp < 0 { '-' } else { '+' };
+  format!("{}e{}{:02}", mantissa, sign, exp.abs())
+}
+
+fn general(value: f64, precision: usize, alternate: bool) -> String {
+  let precision = precision.max(1);
+  let exp = match value {
+    0.0 => 0,
+    _ => {
+      let text = format!("{:.*e}", precision - 1, value);
+      text[text.find('e').unwrap_or(0) + 1..].parse::<i32>().unwrap_or(0)
+    }
+  };
+  let text = if exp < -4 || exp >= precision as i32 {
+    exponent(value, precision - 1)
+  } else {
+    format!("{:.*}", (precision as i32 - 1 - exp) as usize, value)
+  };
+  if alternate {
+    return text;
+  }
+  let (mantissa, rest) = text.split_at(text.find('e').unwrap_or(text.len()));
+  let mantissa = match mantissa.contains('.') {
+    true => mantissa.trim_end_matches('0').trim_end_matches('.'),
+    false => mantissa,
+  };
+  format!("{}{}", mantissa, rest)
+}
+
+#[cfg(test)]
+pub mod printf_test {
+  use super::format;
+  fn run(fmt: &str, args: &[&str]) -> Result<String, Vec<String>> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    format(fmt, &args)
+  }
+  #[test]
+  fn string_test() {
+    assert_eq!(run("%s-%5s|%-5s|%.2s\\n", &["a", "b", "c", "def"]), Ok("a-    b|c    |de\n".to_string()));
+    assert_eq!(run("%s,", &["a", "b", "c"]), Ok("a,b,c,".to_string()));
+    assert_eq!(run("%b|%c|%%", &["x\\ty", "zed"]), Ok("x\ty|z|%".to_string()));
+    assert_eq!(run("a%bc", &["b\\cq"]), Ok("ab".to_string()));
+    assert_eq!(run("\\101\\n", &[]), Ok("A\n".to_string()));
+  }
+  #[test]
+  fn integer_test() {
+    assert_eq!(run("%d %i %05d %-4d| %+d % d", &["42", "-7", "42", "3", "5", "5"]), Ok("42 -7 00042 3   | +5  5".to_string()));
+    assert_eq!(run("%x %X %#x %o %#o %u", &["255", "255", "255", "8", "8", "10"]), Ok("ff FF 0xff 10 010 10".to_string()));
+    assert_eq!(run("%.3d %*d %d", &["7", "4", "1", "'A"]), Ok("007    1 65".to_string()));
+    assert_eq!(run("%d %d", &["0x10", "010"]), Ok("16 8".to_string()));
+    assert!(run("%d", &["abc"]).is_err());
+  }
+  #[test]
+  fn float_test() {
+    assert_eq!(run("%f %.2f %8.3f", &["1.5", "2.346", "3.14159"]), Ok("1.500000 2.35    3.142".to_string()));
+    assert_eq!(run("%e %E", &["1234.5", "0.00012"]), Ok("1.234500e+03 1.200000E-04".to_string()));
+    assert_eq!(run("%g %g %g %G", &["100000", "1000000", "0.0001", "1e-5"]), Ok("100000 1e+06 0.0001 1E-05".to_string()));
+    assert_eq!(run("%+.1f %08.2f", &["2", "-3.5"]), Ok("+2.0 -0003.50".to_string()));
+  }
+}
@@ -0,0 +1,51 @@
+use super::cd::{change_dir, search_cdpath};
+use super::dirs::{listing, parse_index, replace};
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut no_cd = false;
+  let mut operand = None;
+  for arg in &command.args {
+    match arg.as_str() {
+      "-n" => no_cd = true,
+      "--" => {}
+      _ if operand.is_some() => return Err("too many arguments".to_string()),
+      _ => operand = Some(arg.clone()),
+    }
+  }
+  let mut entries = shell.dir_entries();
+  match operand {
+    None if entries.len() < 2 => return Err("no other directory".to_string()),
+    None if no_cd => {}
+    None => {
+      entries.swap(0, 1);
+      replace(shell, entries, true)?;
+    }
+    Some(arg) => match parse_index(&arg, entries.len()) {
+      Some(index) if no_cd => {
+        entries[1..].rotate_left(index?.saturating_sub(1));
+        replace(shell, entries, false)?;
+      }
+      Some(index) => {
+        entries.rotate_left(index?);
+        replace(shell, entries, true)?;
+      }
+      None if no_cd => {
+        entries.insert(1, PathBuf::from(&arg));
+        replace(shell, entries, false)?;
+      }
+      None => {
+        let (target, _) = search_cdpath(shell.get_var("CDPATH"), &arg);
+        let dir = change_dir(shell, &target, false).map_err(|e| format!("{}: {}", arg, e))?;
+        entries.insert(0, dir);
+        replace(shell, entries, false)?;
+      }
+    },
+  }
+  let mut out = command.take_output();
+  out.write_all(listing(shell, false, false, false).as_bytes()).map_err(|e| e.to_string())?;
+  Ok(0)
+}
@@ -1,12 +1,30 @@
+use super::cd::describe;
+use crate::runtime::shell::Shell;
+use crate::terminal::reader::newline;
 use crate::tokens::command::Command;
 use std::env;
 use std::io::prelude::*;
+use std::os::unix::io::AsRawFd;
 
-pub fn run(cmd: Command) -> Result<(), String> {
-  let result = format!("{}\r\n", env::current_dir().unwrap().display());
-  let mut out = cmd.output.unwrap();
-  match out.write_all(result.as_bytes()) {
-    Ok(_) => Ok(()),
-    Err(_) => Err("Error: pwd".to_string()),
+pub fn run(shell: &mut Shell, mut cmd: Command) -> Result<i32, String> {
+  let mut physical = false;
+  for arg in &cmd.args {
+    match arg.as_str() {
+      "-L" => physical = false,
+      "-P" => physical = true,
+      _ if arg.starts_with('-') => return Err(format!("{}: invalid option", arg)),
+      _ => {}
+    }
+  }
+  let dir = match (physical, shell.logical_cwd()) {
+    (false, Some(dir)) => dir,
+    _ => env::current_dir()
+      .map_err(|e| format!("error retrieving current directory: {}", describe(&e)))?,
+  };
+  let mut out = cmd.take_output();
+  let result = format!("{}{}", dir.display(), newline(out.as_raw_fd()));
+  match out.write_all(result.as_bytes()).and_then(|_| out.flush()) {
+    Ok(_) => Ok(0),
+    Err(e) => Err(format!("write error: {}", e)),
   }
 }
@@ -0,0 +1,209 @@
+use crate::runtime::expand::is_name;
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use nix::errno::Errno;
+use nix::unistd::isatty;
+use std::io::{self, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+
+struct Options {
+  raw: bool,
+  prompt: Option<String>,
+  timeout: Option<f64>,
+  array: Option<String>,
+  names: Vec<String>,
+}
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let options = parse(&command.args)?;
+  for name in options.names.iter().chain(options.array.iter()) {
+    if !is_name(name) {
+      return Err(format!("`{}': not a valid identifier", name));
+    }
+  }
+  let input = command.take_input();
+  let fd = input.as_raw_fd();
+  let tty = isatty(fd).unwrap_or(false);
+  if let (Some(prompt), true) = (&options.prompt, tty) {
+    eprint!("{}", prompt);
+    io::stderr().flush().map_err(|e| e.to_string())?;
+  }
+  let deadline = match options.timeout {
+    Some(0.0) => return Ok(if ready(fd, Instant::now()) { 0 } else { 1 }),
+    Some(timeout) => Some(Instant::now() + Duration::from_secs_f64(timeout)),
+    None => None,
+  };
+  let (line, status) = read_line(fd, options.raw, deadline)?;
+  if status > 128 {
+    return Ok(status);
+  }
+  if let Some(array) = &options.array {
+    let fields = split_fields(&line, ifs(shell), usize::MAX, options.raw);
+    shell.vars.remove(array);
+    shell.arrays.insert(array.clone(), fields);
+  } else if options.names.is_empty() {
+    shell.set_var("REPLY", &line);
+  } else {
+    let mut fields = split_fields(&line, ifs(shell), options.names.len(), options.raw).into_iter();
+    for name in &options.names {
+      shell.set_var(name, &fields.next().unwrap_or_default());
+    }
+  }
+  Ok(status)
+}
+
+fn parse(args: &[String]) -> Result<Options, String> {
+  let mut options = Options {
+    raw: false,
+    prompt: None,
+    timeout: None,
+    array: None,
+    names: Vec::new(),
+  };
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    if arg == "--" {
+      options.names.extend(args.cloned());
+      break;
+    }
+    if !arg.starts_with('-') || arg.len() == 1 {
+      options.names.push(arg.clone());
+      options.names.extend(args.cloned());
+      break;
+    }
+    for (i, flag) in arg.char_indices().skip(1) {
+      if flag == 'r' {
+        options.raw = true;
+        continue;
+      }
+      let value = match &arg[i + 1..] {
+        "" => match args.next() {
+          Some(value) => value.clone(),
+          None => return Err(format!("-{}: option requires an argument", flag)),
+        },
+        rest => rest.to_string(),
+      };
+      match flag {
+        'p' => options.prompt = Some(value),
+        'a' => options.array = Some(value),
+        't' => match value.parse::<f64>() {
+          Ok(timeout) if timeout >= 0.0 => options.timeout = Some(timeout),
+          _ => return Err(format!("{}: invalid timeout specification", value)),
+        },
+        _ => return Err(format!("-{}: invalid option", flag)),
+      }
+      break;
+    }
+  }
+  Ok(options)
+}
+
+fn ifs(shell: &Shell) -> &str {
+  shell.get_var("IFS").unwrap_or(" \t\n")
+}
+
+fn ready(fd: RawFd, deadline: Instant) -> bool {
+  let remaining = deadline.saturating_duration_since(Instant::now());
+  let mut poll = libc::pollfd {
+    fd,
+    events: libc::POLLIN,
+    revents: 0,
+  };
+  unsafe { libc::poll(&mut poll, 1, remaining.as_millis() as libc::c_int) > 0 }
+}
+
+// Reads a byte at a time so nothing past the newline is consumed from a
+// shared descriptor. Status is 1 on end of file and 142 on timeout.
+fn read_line(fd: RawFd, raw: bool, deadline: Option<Instant>) -> Result<(String, i32), String> {
+  let mut bytes = Vec::new();
+  let mut escaped = false;
+  let status = loop {
+    if let Some(deadline) = deadline {
+      if !ready(fd, deadline) {
+        break 128 + libc::SIGALRM;
+      }
+    }
+    let mut byte = [0u8; 1];
+    match nix::unistd::read(fd, &mut byte) {
+      Ok(0) => break 1,
+      Ok(_) => {}
+      Err(nix::Error::Sys(Errno::EINTR)) => continue,
+      Err(e) => return Err(e.to_string()),
+    }
+    if byte[0] == b'\n' {
+      if !escaped {
+        break 0;
+      }
+      bytes.pop();
+      escaped = false;
+      continue;
+    }
+    escaped = !raw && byte[0] == b'\\' && !escaped;
+    bytes.push(byte[0]);
+  };
+  Ok((String::from_utf8_lossy(&bytes).into_owned(), status))
+}
+
+// Splits into at most `count` fields; the last one keeps the rest of the
+// line. Whitespace IFS characters collapse, any other IFS character
+// delimits exactly one field.
+pub fn split_fields(line: &str, ifs: &str, count: usize, raw: bool) -> Vec<String> {
+  let mut chars = Vec::new();
+  let mut iter = line.chars();
+  while let Some(ch) = iter.next() {
+    match ch {
+      '\\' if !raw => {
+        if let Some(next) = iter.next() {
+          chars.push((next, true));
+        }
+      }
+      _ => chars.push((ch, false)),
+    }
+  }
+  let is_ifs = |&(ch, escaped): &(char, bool)| !escaped && ifs.contains(ch);
+  let is_space = |c: &(char, bool)| is_ifs(c) && c.0.is_whitespace();
+  let mut fields = Vec::new();
+  let mut i = 0;
+  while i < chars.len() && is_space(&chars[i]) {
+    i += 1;
+  }
+  while i < chars.len() {
+    if fields.len() + 1 == count {
+      let mut end = chars.len();
+      while end > i && is_space(&chars[end - 1]) {
+        end -= 1;
+      }
+      fields.push(chars[i..end].iter().map(|c| c.0).collect());
+      break;
+    }
+    let start = i;
+    while i < chars.len() && !is_ifs(&chars[i]) {
+      i += 1;
+    }
+    fields.push(chars[start..i].iter().map(|c| c.0).collect());
+    while i < chars.len() && is_space(&chars[i]) {
+      i += 1;
+    }
+    if i < chars.len() && is_ifs(&chars[i]) {
+      i += 1;
+      while i < chars.len() && is_space(&chars[i]) {
+        i += 1;
+      }
+    }
+  }
+  fields
+}
+
+#[cfg(test)]
+pub mod read_test {
+  use super::split_fields;
+  #[test]
+  fn split_fields_test() {
+    assert_eq!(split_fields("  a  b c  ", " \t\n", 2, false), vec!["a", "b c"]);
+    assert_eq!(split_fields("a b", " \t\n", 3, false), vec!["a", "b"]);
+    assert_eq!(split_fields("a:b::c", ":", usize::MAX, false), vec!["a", "b", "", "c"]);
+    assert_eq!(split_fields("a\\ b c", " ", 5, false), vec!["a b", "c"]);
+    assert_eq!(split_fields("a\\ b c", " ", 5, true), vec!["a\\", "b", "c"]);
+  }
+}
@@ -0,0 +1,17 @@
+use crate::runtime::shell::{Flow, Shell};
+use crate::tokens::command::Command;
+
+pub fn run(shell: &mut Shell, command: Command) -> Result<i32, String> {
+  if shell.call_depth == 0 {
+    return Err("can only `return' from a function or sourced script".to_string());
+  }
+  let status = match command.args.first() {
+    Some(arg) => match arg.parse::<i32>() {
+      Ok(n) => n & 0xff,
+      Err(_) => return Err(format!("{}: numeric argument required", arg)),
+    },
+    None => shell.status,
+  };
+  shell.flow = Some(Flow::Return(status));
+  Ok(status)
+}
@@ -0,0 +1,57 @@
+use crate::runtime::expand::single_quote;
+use crate::runtime::shell::{Settings, Shell};
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut out = command.take_output();
+  if command.args.is_empty() {
+    for (name, var) in &shell.vars {
+      writeln!(out, "{}={}", name, single_quote(&var.value)).map_err(|e| e.to_string())?;
+    }
+    return Ok(0);
+  }
+  let mut args = command.args.iter();
+  while let Some(arg) = args.next() {
+    let value = match arg.chars().next() {
+      Some('-') => true,
+      Some('+') => false,
+      _ => {
+        shell.args = std::iter::once(arg).chain(args).cloned().collect();
+        return Ok(0);
+      }
+    };
+    if arg == "--" {
+      shell.args = args.cloned().collect();
+      return Ok(0);
+    }
+    for flag in arg[1..].chars() {
+      if flag == 'o' {
+        match args.next() {
+          Some(name) => {
+            if !shell.settings.set(name, value) {
+              return Err(format!("{}: invalid option name", name));
+            }
+          }
+          None => {
+            for name in Settings::names().iter() {
+              let state = match shell.settings.get(name) {
+                Some(true) => "on",
+                _ => "off",
+              };
+              writeln!(out, "{:<15}\t{}", name, state).map_err(|e| e.to_string())?;
+            }
+          }
+        }
+        continue;
+      }
+      match Settings::name_of(flag) {
+        Some(name) => {
+          shell.settings.set(name, value);
+        }
+        None => return Err(format!("{}{}: invalid option", &arg[..1], flag)),
+      }
+    }
+  }
+  Ok(0)
+}
@@ -0,0 +1,17 @@
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+
+pub fn run(shell: &mut Shell, command: Command) -> Result<i32, String> {
+  let n = match command.args.first() {
+    Some(arg) => match arg.parse::<usize>() {
+      Ok(n) => n,
+      Err(_) => return Err(format!("{}: numeric argument required", arg)),
+    },
+    None => 1,
+  };
+  if n > shell.args.len() {
+    return Err(format!("{}: shift count out of range", n));
+  }
+  shell.args.drain(..n);
+  Ok(0)
+}
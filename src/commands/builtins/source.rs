@@ -0,0 +1,51 @@
+use crate::runtime::script::run_script;
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::env;
+use std::fs;
+use std::mem;
+use std::path::PathBuf;
+
+pub fn run(shell: &mut Shell, command: Command) -> Result<i32, String> {
+  let name = match command.args.first() {
+    Some(name) => name,
+    None => return Err("filename argument required".to_string()),
+  };
+  let path = match find(shell, name) {
+    Some(path) => path,
+    None => return Err(format!("{}: file not found", name)),
+  };
+  let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", name, e))?;
+
+  let saved_args = if command.args.len() > 1 {
+    Some(mem::replace(&mut shell.args, command.args[1..].to_vec()))
+  } else {
+    None
+  };
+  shell.call_depth += 1;
+  let status = run_script(shell, &path.display().to_string(), &content);
+  shell.call_depth -= 1;
+  if let Some(args) = saved_args {
+    shell.args = args;
+  }
+  Ok(shell.take_return(status))
+}
+
+fn find(shell: &Shell, name: &str) -> Option<PathBuf> {
+  if name.contains('/') {
+    return Some(PathBuf::from(name));
+  }
+  if let Some(path) = shell.get_var("PATH") {
+    for dir in env::split_paths(path) {
+      let candidate = dir.join(name);
+      if candidate.is_file() {
+        return Some(candidate);
+      }
+    }
+  }
+  let candidate = PathBuf::from(name);
+  if candidate.is_file() {
+    return Some(candidate);
+  }
+  None
+}
@@ -0,0 +1,243 @@
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use nix::unistd::{access, isatty, AccessFlags};
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+
+pub fn run(shell: &mut Shell, command: Command) -> Result<i32, String> {
+  let mut args = command.args.clone();
+  if command.command == "[" {
+    if args.last().map(|arg| arg.as_str()) != Some("]") {
+      shell.report("[: missing `]'");
+      return Ok(2);
+    }
+    args.pop();
+  }
+  match evaluate(&args) {
+    Ok(true) => Ok(0),
+    Ok(false) => Ok(1),
+    Err(e) => {
+      shell.report(&format!("{}: {}", command.command, e));
+      Ok(2)
+    }
+  }
+}
+
+// POSIX fixes the meaning of up to four arguments by count; longer
+// expressions fall back to precedence parsing of `!`, `-a`, `-o` and parens.
+pub fn evaluate(args: &[String]) -> Result<bool, String> {
+  let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+  match args.len() {
+    0 => Ok(false),
+    1 => Ok(!args[0].is_empty()),
+    2 if args[0] == "!" => Ok(args[1].is_empty()),
+    2 if is_unary(args[0]) => unary(args[0], args[1]),
+    2 => Err(format!("{}: unary operator expected", args[0])),
+    3 if is_binary(args[1]) => binary(args[0], args[1], args[2]),
+    3 if args[0] == "!" => evaluate_strs(&args[1..]).map(|result| !result),
+    3 if args[0] == "(" && args[2] == ")" => Ok(!args[1].is_empty()),
+    4 if args[0] == "!" => evaluate_strs(&args[1..]).map(|result| !result),
+    4 if args[0] == "(" && args[3] == ")" => evaluate_strs(&args[1..3]),
+    _ => {
+      let mut parser = ExprParser { args: &args, pos: 0 };
+      let result = parser.or()?;
+      match parser.pos == args.len() {
+        true => Ok(result),
+        false => Err(format!("{}: unexpected argument", args[parser.pos])),
+      }
+    }
+  }
+}
+
+fn evaluate_strs(args: &[&str]) -> Result<bool, String> {
+  let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+  evaluate(&args)
+}
+
+struct ExprParser<'a> {
+  args: &'a [&'a str],
+  pos: usize,
+}
+
+impl<'a> ExprParser<'a> {
+  fn peek(&self) -> Option<&'a str> {
+    self.args.get(self.pos).copied()
+  }
+  fn next(&mut self) -> Result<&'a str, String> {
+    match self.peek() {
+      Some(arg) => {
+        self.pos += 1;
+        Ok(arg)
+      }
+      None => Err("argument expected".to_string()),
+    }
+  }
+  fn or(&mut self) -> Result<bool, String> {
+    let mut result = self.and()?;
+    while self.peek() == Some("-o") {
+      self.pos += 1;
+      let rhs = self.and()?;
+      result = result || rhs;
+    }
+    Ok(result)
+  }
+  fn and(&mut self) -> Result<bool, String> {
+    let mut result = self.not()?;
+    while self.peek() == Some("-a") {
+      self.pos += 1;
+      let rhs = self.not()?;
+      result = result && rhs;
+    }
+    Ok(result)
+  }
+  fn not(&mut self) -> Result<bool, String> {
+    if self.peek() == Some("!") {
+      self.pos += 1;
+      return self.not().map(|result| !result);
+    }
+    self.primary()
+  }
+  fn primary(&mut self) -> Result<bool, String> {
+    let arg = self.next()?;
+    if arg == "(" {
+      let result = self.or()?;
+      return match self.next()? {
+        ")" => Ok(result),
+        other => Err(format!("{}: `)' expected", other)),
+      };
+    }
+    if is_unary(arg) && self.pos < self.args.len() {
+      let operand = self.next()?;
+      return unary(arg, operand);
+    }
+    if let Some(op) = self.peek() {
+      if is_binary(op) && self.pos + 1 < self.args.len() {
+        self.pos += 1;
+        let rhs = self.next()?;
+        return binary(arg, op, rhs);
+      }
+    }
+    Ok(!arg.is_empty())
+  }
+}
+
+fn is_unary(op: &str) -> bool {
+  matches!(
+    op,
+    "-b" | "-c" | "-d" | "-e" | "-f" | "-g" | "-h" | "-k" | "-L" | "-n" | "-p" | "-r" | "-s"
+      | "-S" | "-t" | "-u" | "-w" | "-x" | "-z" | "-O" | "-G"
+  )
+}
+
+fn is_binary(op: &str) -> bool {
+  matches!(
+    op,
+    "=" | "==" | "!=" | "<" | ">" | "-eq" | "-ne" | "-lt" | "-le" | "-gt" | "-ge" | "-nt"
+      | "-ot" | "-ef"
+  )
+}
+
+fn unary(op: &str, operand: &str) -> Result<bool, String> {
+  let metadata = fs::metadata(operand);
+  let mode = metadata.as_ref().map(|m| m.permissions().mode()).unwrap_or(0);
+  Ok(match op {
+    "-n" => !operand.is_empty(),
+    "-z" => operand.is_empty(),
+    "-e" => metadata.is_ok(),
+    "-f" => metadata.map(|m| m.is_file()).unwrap_or(false),
+    "-d" => metadata.map(|m| m.is_dir()).unwrap_or(false),
+    "-s" => metadata.map(|m| m.len() > 0).unwrap_or(false),
+    "-b" => metadata.map(|m| m.file_type().is_block_device()).unwrap_or(false),
+    "-c" => metadata.map(|m| m.file_type().is_char_device()).unwrap_or(false),
+    "-p" => metadata.map(|m| m.file_type().is_fifo()).unwrap_or(false),
+    "-S" => metadata.map(|m| m.file_type().is_socket()).unwrap_or(false),
+    "-h" | "-L" => fs::symlink_metadata(operand)
+      .map(|m| m.file_type().is_symlink())
+      .unwrap_or(false),
+    "-g" => mode & 0o2000 != 0,
+    "-u" => mode & 0o4000 != 0,
+    "-k" => mode & 0o1000 != 0,
+    "-O" => metadata.map(|m| m.uid() == nix::unistd::geteuid().as_raw()).unwrap_or(false),
+    "-G" => metadata.map(|m| m.gid() == nix::unistd::getegid().as_raw()).unwrap_or(false),
+    "-r" => access(operand, AccessFlags::R_OK).is_ok(),
+    "-w" => access(operand, AccessFlags::W_OK).is_ok(),
+    "-x" => access(operand, AccessFlags::X_OK).is_ok(),
+    "-t" => isatty(integer(operand)? as i32).unwrap_or(false),
+    _ => return Err(format!("{}: unary operator expected", op)),
+  })
+}
+
+fn binary(lhs: &str, op: &str, rhs: &str) -> Result<bool, String> {
+  Ok(match op {
+    "=" | "==" => lhs == rhs,
+    "!=" => lhs != rhs,
+    "<" => lhs < rhs,
+    ">" => lhs > rhs,
+    "-eq" => integer(lhs)? == integer(rhs)?,
+    "-ne" => integer(lhs)? != integer(rhs)?,
+    "-lt" => integer(lhs)? < integer(rhs)?,
+    "-le" => integer(lhs)? <= integer(rhs)?,
+    "-gt" => integer(lhs)? > integer(rhs)?,
+    "-ge" => integer(lhs)? >= integer(rhs)?,
+    "-nt" | "-ot" => {
+      let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+      match (modified(lhs), modified(rhs), op) {
+        (Some(l), Some(r), "-nt") => l > r,
+        (Some(l), Some(r), _) => l < r,
+        (Some(_), None, "-nt") => true,
+        (None, Some(_), "-ot") => true,
+        _ => false,
+      }
+    }
+    "-ef" => match (fs::metadata(lhs), fs::metadata(rhs)) {
+      (Ok(l), Ok(r)) => l.dev() == r.dev() && l.ino() == r.ino(),
+      _ => false,
+    },
+    _ => return Err(format!("{}: binary operator expected", op)),
+  })
+}
+
+fn integer(s: &str) -> Result<i64, String> {
+  s.trim()
+    .parse::<i64>()
+    .map_err(|_| format!("{}: integer expression expected", s))
+}
+
+#[cfg(test)]
+pub mod test_test {
+  use super::evaluate;
+  fn eval(args: &[&str]) -> Result<bool, String> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    evaluate(&args)
+  }
+  #[test]
+  fn string_test() {
+    assert_eq!(eval(&[]), Ok(false));
+    assert_eq!(eval(&["a"]), Ok(true));
+    assert_eq!(eval(&["-n", ""]), Ok(false));
+    assert_eq!(eval(&["-z", ""]), Ok(true));
+    assert_eq!(eval(&["a", "=", "a"]), Ok(true));
+    assert_eq!(eval(&["a", "!=", "a"]), Ok(false));
+    assert_eq!(eval(&["!", "a", "=", "b"]), Ok(true));
+    assert_eq!(eval(&["-n", "="]), Ok(true));
+  }
+  #[test]
+  fn integer_test() {
+    assert_eq!(eval(&["3", "-gt", "2"]), Ok(true));
+    assert_eq!(eval(&["3", "-le", "2"]), Ok(false));
+    assert!(eval(&["a", "-eq", "2"]).is_err());
+  }
+  #[test]
+  fn file_test() {
+    assert_eq!(eval(&["-d", "/"]), Ok(true));
+    assert_eq!(eval(&["-f", "/"]), Ok(false));
+    assert_eq!(eval(&["-e", "/nonexistent/path"]), Ok(false));
+  }
+  #[test]
+  fn compound_test() {
+    assert_eq!(eval(&["a", "-a", "", "-o", "b"]), Ok(true));
+    assert_eq!(eval(&["(", "a", "-o", "", ")", "-a", "", ]), Ok(false));
+    assert_eq!(eval(&["!", "(", "1", "-eq", "2", ")", "-a", "x"]), Ok(true));
+    assert!(eval(&["(", "a", "-a", "b"]).is_err());
+  }
+}
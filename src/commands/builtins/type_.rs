@@ -0,0 +1,51 @@
+use crate::runtime::lookup::{kind_of, Kind};
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+use std::io::Write;
+
+pub fn run(shell: &mut Shell, mut command: Command) -> Result<i32, String> {
+  let mut out = command.take_output();
+  let mut terse = false;
+  let mut status = 0;
+  for name in &command.args {
+    if name == "-t" {
+      terse = true;
+      continue;
+    }
+    let kind = match kind_of(shell, name) {
+      Some(kind) => kind,
+      None => {
+        if !terse {
+          shell.report(&format!("type: {}: not found", name));
+        }
+        status = 1;
+        continue;
+      }
+    };
+    let line = match terse {
+      true => kind_word(&kind).to_string(),
+      false => describe(name, &kind),
+    };
+    writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+  }
+  Ok(status)
+}
+
+pub fn describe(name: &str, kind: &Kind) -> String {
+  match kind {
+    Kind::Alias(value) => format!("{} is aliased to `{}'", name, value),
+    Kind::Function => format!("{} is a function", name),
+    Kind::Builtin => format!("{} is a shell builtin", name),
+    Kind::File(path, true) => format!("{} is hashed ({})", name, path.display()),
+    Kind::File(path, false) => format!("{} is {}", name, path.display()),
+  }
+}
+
+fn kind_word(kind: &Kind) -> &'static str {
+  match kind {
+    Kind::Alias(_) => "alias",
+    Kind::Function => "function",
+    Kind::Builtin => "builtin",
+    Kind::File(_, _) => "file",
+  }
+}
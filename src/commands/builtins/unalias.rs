@@ -0,0 +1,20 @@
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+
+pub fn run(shell: &mut Shell, command: Command) -> Result<i32, String> {
+  if command.args.is_empty() {
+    return Err("usage: unalias [-a] name [name ...]".to_string());
+  }
+  let mut status = 0;
+  for arg in &command.args {
+    if arg == "-a" {
+      shell.aliases.clear();
+      continue;
+    }
+    if shell.aliases.remove(arg).is_none() {
+      shell.report(&format!("unalias: {}: not found", arg));
+      status = 1;
+    }
+  }
+  Ok(status)
+}
@@ -0,0 +1,29 @@
+use crate::runtime::shell::Shell;
+use crate::tokens::command::Command;
+
+pub fn run(shell: &mut Shell, command: Command) -> Result<i32, String> {
+  let mut functions = false;
+  let mut variables = false;
+  let mut names = Vec::new();
+  for arg in &command.args {
+    match arg.as_str() {
+      "-f" => functions = true,
+      "-v" => variables = true,
+      _ if arg.starts_with('-') => return Err(format!("{}: invalid option", arg)),
+      _ => names.push(arg),
+    }
+  }
+  for name in names {
+    if functions && !variables {
+      shell.functions.remove(name.as_str());
+      continue;
+    }
+    let exists = shell.vars.contains_key(name.as_str()) || shell.arrays.contains_key(name.as_str());
+    if exists || variables {
+      shell.unset_var(name);
+    } else {
+      shell.functions.remove(name.as_str());
+    }
+  }
+  Ok(0)
+}
@@ -6,3 +6,22 @@ pub const LT: &str = "<";
 
 #[allow(dead_code)]
 pub const GT: &str = ">";
+
+#[allow(dead_code)]
+pub const SHELL_NAME: &str = "rustsh";
+
+#[allow(dead_code)]
+pub const RC_FILE: &str = ".rustshrc";
+
+#[allow(dead_code)]
+pub const PROFILE_FILE: &str = ".rustsh_profile";
+
+#[allow(dead_code)]
+pub const DEFAULT_PROMPT: &str = "$ ";
+
+// searched when PATH is unset; the current directory is left out on purpose
+#[allow(dead_code)]
+pub const DEFAULT_PATH: &str = "/usr/bin:/bin";
+
+#[allow(dead_code)]
+pub const HISTORY_FILE: &str = ".rustsh_history";
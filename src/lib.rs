@@ -3,4 +3,8 @@
 pub mod commands;
 mod constants;
 pub mod parser;
-pub mod terminal;mod tokens;
+pub mod runtime;
+pub mod terminal;
+#[cfg(test)]
+mod testing;
+mod tokens;
@@ -0,0 +1,85 @@
+use super::list::{is_separator, lex};
+use crate::runtime::expand::is_assignment;
+use std::collections::BTreeMap;
+
+pub fn expand_aliases(aliases: &BTreeMap<String, String>, input: &str) -> String {
+  expand(aliases, input, &mut Vec::new())
+}
+
+// `active` holds the aliases being expanded so `alias ls='ls -F'` and
+// mutually recursive aliases stop instead of looping.
+fn expand(aliases: &BTreeMap<String, String>, input: &str, active: &mut Vec<String>) -> String {
+  let (lexemes, _) = lex(input);
+  let mut result = String::new();
+  let mut command_position = true;
+  let mut i = 0;
+  while i < lexemes.len() {
+    let lexeme = lexemes[i];
+    if is_separator(&lexeme) {
+      if lexeme.ch == '\n' || ";&|".contains(lexeme.ch) {
+        command_position = true;
+      }
+      result.push(lexeme.ch);
+      i += 1;
+      continue;
+    }
+    let mut j = i;
+    while j < lexemes.len() && !is_separator(&lexemes[j]) {
+      j += 1;
+    }
+    let end = lexemes.get(j).map(|lexeme| lexeme.idx).unwrap_or(input.len());
+    let word = &input[lexeme.idx..end];
+    let quoted = lexemes[i..j].iter().any(|lexeme| lexeme.quoted);
+    let eligible = command_position && !quoted && !active.iter().any(|name| name == word);
+    // assignments ahead of the command leave it in command position
+    command_position = command_position && is_assignment(word);
+    match aliases.get(word) {
+      Some(value) if eligible => {
+        active.push(word.to_string());
+        result.push_str(&expand(aliases, value, active));
+        active.pop();
+        command_position = value.ends_with(' ') || value.ends_with('\t');
+      }
+      _ => result.push_str(word),
+    }
+    i = j;
+  }
+  result
+}
+
+#[cfg(test)]
+pub mod alias_test {
+  use super::*;
+  fn aliases(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+      .iter()
+      .map(|(name, value)| (name.to_string(), value.to_string()))
+      .collect()
+  }
+  #[test]
+  fn first_word_test() {
+    let aliases = aliases(&[("ll", "ls -l")]);
+    assert_eq!(expand_aliases(&aliases, "ll ll"), "ls -l ll");
+    assert_eq!(expand_aliases(&aliases, "'ll' ll"), "'ll' ll");
+    assert_eq!(expand_aliases(&aliases, "ll | ll; ll && ll"), "ls -l | ls -l; ls -l && ls -l");
+  }
+  #[test]
+  fn assignment_test() {
+    let aliases = aliases(&[("ll", "ls -l")]);
+    assert_eq!(expand_aliases(&aliases, "FOO=1 ll"), "FOO=1 ls -l");
+    assert_eq!(expand_aliases(&aliases, "A=1 B=\"x y\" ll ll"), "A=1 B=\"x y\" ls -l ll");
+    assert_eq!(expand_aliases(&aliases, "echo FOO=1 ll"), "echo FOO=1 ll");
+  }
+  #[test]
+  fn recursive_test() {
+    let aliases = aliases(&[("ls", "ls -F"), ("l", "ls -a"), ("a", "b"), ("b", "a")]);
+    assert_eq!(expand_aliases(&aliases, "l"), "ls -F -a");
+    assert_eq!(expand_aliases(&aliases, "a"), "a");
+  }
+  #[test]
+  fn trailing_space_test() {
+    let aliases = aliases(&[("sudo", "sudo "), ("ll", "ls -l"), ("x", "echo")]);
+    assert_eq!(expand_aliases(&aliases, "sudo ll"), "sudo  ls -l");
+    assert_eq!(expand_aliases(&aliases, "x ll"), "echo ll");
+  }
+}
@@ -0,0 +1,429 @@
+use super::list::lex;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expansion {
+  pub line: String,
+  // `:p` asks for the result to be shown and recorded but not run
+  pub print_only: bool,
+  pub changed: bool,
+}
+
+// csh-style history expansion. `history` holds past commands, oldest
+// first; event numbers are positions in it starting at 1.
+pub fn expand_history(history: &[String], line: &str) -> Result<Expansion, String> {
+  let mut expander = Expander {
+    history,
+    chars: line.chars().collect(),
+    pos: 0,
+    result: String::new(),
+    print_only: false,
+    changed: false,
+    substitution: None,
+  };
+  expander.run()?;
+  Ok(Expansion {
+    line: expander.result,
+    print_only: expander.print_only,
+    changed: expander.changed,
+  })
+}
+
+struct Substitution {
+  old: String,
+  new: String,
+}
+
+struct Expander<'a> {
+  history: &'a [String],
+  chars: Vec<char>,
+  pos: usize,
+  result: String,
+  print_only: bool,
+  changed: bool,
+  substitution: Option<Substitution>,
+}
+
+impl<'a> Expander<'a> {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+  fn peek_at(&self, offset: usize) -> Option<char> {
+    self.chars.get(self.pos + offset).copied()
+  }
+
+  fn run(&mut self) -> Result<(), String> {
+    if self.peek() == Some('^') {
+      self.quick_substitution()?;
+    }
+    let mut single = false;
+    let mut double = false;
+    while let Some(c) = self.peek() {
+      match c {
+        '\\' if !single => {
+          self.result.push(c);
+          self.pos += 1;
+          if let Some(next) = self.peek() {
+            self.result.push(next);
+            self.pos += 1;
+          }
+          continue;
+        }
+        '\'' if !double => single = !single,
+        '"' if !single => double = !double,
+        '!' if !single && self.is_event_start(double) => {
+          self.pos += 1;
+          let text = self.event()?;
+          self.result.push_str(&text);
+          self.changed = true;
+          continue;
+        }
+        _ => {}
+      }
+      self.result.push(c);
+      self.pos += 1;
+    }
+    Ok(())
+  }
+
+  fn is_event_start(&self, double: bool) -> bool {
+    match self.peek_at(1) {
+      None => false,
+      Some(c) if c.is_whitespace() || c == '=' || c == '(' => false,
+      Some('"') if double => false,
+      _ => true,
+    }
+  }
+
+  // `^old^new^` is shorthand for `!!:s/old/new/`.
+  fn quick_substitution(&mut self) -> Result<(), String> {
+    self.pos += 1;
+    let old = self.delimited('^');
+    let new = self.delimited('^');
+    let mut text = self.last()?.to_string();
+    if old.is_empty() || !text.contains(&old) {
+      return Err(format!("{}: substitution failed", old));
+    }
+    text = text.replacen(&old, &new, 1);
+    self.substitution = Some(Substitution { old, new });
+    let text = self.modifiers(text)?;
+    self.result.push_str(&text);
+    self.changed = true;
+    Ok(())
+  }
+
+  fn last(&self) -> Result<&'a str, String> {
+    match self.history.last() {
+      Some(command) => Ok(command),
+      None => Err("!!: event not found".to_string()),
+    }
+  }
+
+  fn event(&mut self) -> Result<String, String> {
+    let start = self.pos;
+    let command: &str = match self.peek() {
+      Some('!') => {
+        self.pos += 1;
+        self.last()?
+      }
+      Some('#') => {
+        self.pos += 1;
+        return Ok(self.result.clone());
+      }
+      Some(c) if "$^*%:".contains(c) => self.last()?,
+      Some(c) if c.is_ascii_digit() || (c == '-' && self.peek_at(1).is_some_and(|c| c.is_ascii_digit())) => {
+        let negative = c == '-';
+        if negative {
+          self.pos += 1;
+        }
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        let n: usize = digits.parse().map_err(|_| format!("!{}: event not found", digits))?;
+        let index = match negative {
+          true => self.history.len().checked_sub(n),
+          false => n.checked_sub(1),
+        };
+        let not_found = || format!("!{}: event not found", self.span(start));
+        match index.and_then(|i| self.history.get(i)) {
+          Some(command) => command,
+          None => return Err(not_found()),
+        }
+      }
+      Some('?') => {
+        self.pos += 1;
+        let needle = self.delimited('?');
+        match self.history.iter().rev().find(|command| command.contains(&needle)) {
+          Some(command) => command,
+          None => return Err(format!("!?{}: event not found", needle)),
+        }
+      }
+      _ => {
+        let prefix = self.take_while(|c| !c.is_whitespace() && c != ':' && c != '"' && c != '\'');
+        match self.history.iter().rev().find(|command| command.starts_with(&prefix)) {
+          Some(command) => command,
+          None => return Err(format!("!{}: event not found", prefix)),
+        }
+      }
+    };
+    let text = self.words(command)?;
+    self.modifiers(text)
+  }
+
+  fn span(&self, start: usize) -> String {
+    self.chars[start..self.pos].iter().collect()
+  }
+
+  fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> String {
+    let mut result = String::new();
+    while let Some(c) = self.peek() {
+      if !f(c) {
+        break;
+      }
+      result.push(c);
+      self.pos += 1;
+    }
+    result
+  }
+
+  // Reads up to `delimiter` or the end of the word; a backslash quotes
+  // the delimiter.
+  fn delimited(&mut self, delimiter: char) -> String {
+    let mut result = String::new();
+    while let Some(c) = self.peek() {
+      self.pos += 1;
+      match c {
+        '\\' if self.peek() == Some(delimiter) => {
+          result.push(delimiter);
+          self.pos += 1;
+        }
+        _ if c == delimiter => return result,
+        _ => result.push(c),
+      }
+    }
+    result
+  }
+
+  fn words(&mut self, command: &str) -> Result<String, String> {
+    let designator = match (self.peek(), self.peek_at(1)) {
+      (Some(':'), Some(c)) if c.is_ascii_digit() || "^$*-%".contains(c) => {
+        self.pos += 1;
+        true
+      }
+      (Some(c), _) if "^$*%".contains(c) => true,
+      _ => false,
+    };
+    if !designator {
+      return Ok(command.to_string());
+    }
+    let words = split_words(command);
+    let last = words.len() - 1;
+    let bad = || "bad word specifier".to_string();
+    let first = match self.peek() {
+      Some('*') => {
+        self.pos += 1;
+        return Ok(words.get(1..).map(|words| words.join(" ")).unwrap_or_default());
+      }
+      Some('^') => {
+        self.pos += 1;
+        1
+      }
+      Some('$') => {
+        self.pos += 1;
+        last
+      }
+      Some('%') => {
+        self.pos += 1;
+        last
+      }
+      Some('-') => 0,
+      _ => self.take_while(|c| c.is_ascii_digit()).parse::<usize>().map_err(|_| bad())?,
+    };
+    let end = match self.peek() {
+      Some('*') => {
+        self.pos += 1;
+        last
+      }
+      Some('-') => {
+        self.pos += 1;
+        match self.peek() {
+          Some('$') => {
+            self.pos += 1;
+            last
+          }
+          Some(c) if c.is_ascii_digit() => {
+            self.take_while(|c| c.is_ascii_digit()).parse::<usize>().map_err(|_| bad())?
+          }
+          _ => last.saturating_sub(1),
+        }
+      }
+      _ => first,
+    };
+    if first > last || end > last || first > end {
+      return Err(bad());
+    }
+    Ok(words[first..=end].join(" "))
+  }
+
+  fn modifiers(&mut self, mut text: String) -> Result<String, String> {
+    while self.peek() == Some(':') {
+      let global = self.peek_at(1) == Some('g');
+      let modifier = match global {
+        true => self.peek_at(2),
+        false => self.peek_at(1),
+      };
+      match modifier {
+        Some('h') | Some('t') | Some('r') | Some('e') | Some('p') if !global => {
+          self.pos += 2;
+        }
+        Some('s') | Some('&') => {
+          self.pos += if global { 3 } else { 2 };
+        }
+        _ => return Ok(text),
+      }
+      text = match modifier {
+        Some('h') => match text.rfind('/') {
+          Some(0) => "/".to_string(),
+          Some(idx) => text[..idx].to_string(),
+          None => text,
+        },
+        Some('t') => match text.rfind('/') {
+          Some(idx) => text[idx + 1..].to_string(),
+          None => text,
+        },
+        Some('r') => match extension(&text) {
+          Some(idx) => text[..idx].to_string(),
+          None => text,
+        },
+        Some('e') => match extension(&text) {
+          Some(idx) => text[idx..].to_string(),
+          None => String::new(),
+        },
+        Some('p') => {
+          self.print_only = true;
+          text
+        }
+        Some('s') => {
+          let delimiter = match self.peek() {
+            Some(c) => c,
+            None => return Err("bad substitution".to_string()),
+          };
+          self.pos += 1;
+          let old = self.delimited(delimiter);
+          let new = self.delimited(delimiter);
+          let old = match (old.is_empty(), &self.substitution) {
+            (true, Some(previous)) => previous.old.clone(),
+            (true, None) => return Err("no previous substitution".to_string()),
+            (false, _) => old,
+          };
+          let new = new.replace('&', &old);
+          self.substitution = Some(Substitution { old, new });
+          substitute(&text, self.substitution.as_ref(), global)?
+        }
+        _ => substitute(&text, self.substitution.as_ref(), global)?,
+      };
+    }
+    Ok(text)
+  }
+}
+
+fn substitute(text: &str, substitution: Option<&Substitution>, global: bool) -> Result<String, String> {
+  let substitution = match substitution {
+    Some(substitution) => substitution,
+    None => return Err("no previous substitution".to_string()),
+  };
+  if !text.contains(&substitution.old) {
+    return Err(format!("{}: substitution failed", substitution.old));
+  }
+  Ok(match global {
+    true => text.replace(&substitution.old, &substitution.new),
+    false => text.replacen(&substitution.old, &substitution.new, 1),
+  })
+}
+
+// Index of the `.` starting the suffix of the last path component.
+fn extension(text: &str) -> Option<usize> {
+  let base = text.rfind('/').map(|idx| idx + 1).unwrap_or(0);
+  match text[base..].rfind('.') {
+    Some(0) | None => None,
+    Some(idx) => Some(base + idx),
+  }
+}
+
+// Splits a command into words on unquoted whitespace.
+fn split_words(command: &str) -> Vec<String> {
+  let (lexemes, _) = lex(command);
+  let mut words = Vec::new();
+  let mut word = String::new();
+  for lexeme in lexemes {
+    if !lexeme.quoted && lexeme.ch.is_whitespace() {
+      if !word.is_empty() {
+        words.push(std::mem::take(&mut word));
+      }
+      continue;
+    }
+    word.push(lexeme.ch);
+  }
+  if !word.is_empty() || words.is_empty() {
+    words.push(word);
+  }
+  words
+}
+
+#[cfg(test)]
+pub mod bang_test {
+  use super::*;
+  fn history() -> Vec<String> {
+    vec![
+      "cd /usr/src/linux".to_string(),
+      "tar xzf archive.tar.gz 'two words'".to_string(),
+      "echo hello world".to_string(),
+    ]
+  }
+  fn expand(line: &str) -> Result<String, String> {
+    expand_history(&history(), line).map(|expansion| expansion.line)
+  }
+  #[test]
+  fn event_test() {
+    assert_eq!(expand("!!"), Ok("echo hello world".to_string()));
+    assert_eq!(expand("sudo !!"), Ok("sudo echo hello world".to_string()));
+    assert_eq!(expand("!1"), Ok("cd /usr/src/linux".to_string()));
+    assert_eq!(expand("!-2"), Ok("tar xzf archive.tar.gz 'two words'".to_string()));
+    assert_eq!(expand("!cd"), Ok("cd /usr/src/linux".to_string()));
+    assert_eq!(expand("!?hello?"), Ok("echo hello world".to_string()));
+    assert_eq!(expand("!nope"), Err("!nope: event not found".to_string()));
+    assert_eq!(expand("!9"), Err("!9: event not found".to_string()));
+  }
+  #[test]
+  fn word_test() {
+    assert_eq!(expand("ls !$"), Ok("ls world".to_string()));
+    assert_eq!(expand("!!:0"), Ok("echo".to_string()));
+    assert_eq!(expand("!tar:1-2"), Ok("xzf archive.tar.gz".to_string()));
+    assert_eq!(expand("!tar:$"), Ok("'two words'".to_string()));
+    assert_eq!(expand("!!:*"), Ok("hello world".to_string()));
+    assert_eq!(expand("!-2:2-"), Ok("archive.tar.gz".to_string()));
+    assert_eq!(expand("!!:5"), Err("bad word specifier".to_string()));
+  }
+  #[test]
+  fn modifier_test() {
+    assert_eq!(expand("!1:1:h"), Ok("/usr/src".to_string()));
+    assert_eq!(expand("!1:1:t"), Ok("linux".to_string()));
+    assert_eq!(expand("!2:2:r"), Ok("archive.tar".to_string()));
+    assert_eq!(expand("!2:2:e"), Ok(".gz".to_string()));
+    assert_eq!(expand("!!:s/hello/bye/"), Ok("echo bye world".to_string()));
+    assert_eq!(expand("!!:gs/o/0/"), Ok("ech0 hell0 w0rld".to_string()));
+    let expansion = expand_history(&history(), "!!:p").unwrap();
+    assert!(expansion.print_only);
+  }
+  #[test]
+  fn quick_substitution_test() {
+    assert_eq!(expand("^hello^bye"), Ok("echo bye world".to_string()));
+    assert_eq!(expand("^hello^bye^ again"), Ok("echo bye world again".to_string()));
+    assert!(expand("^nothere^x").is_err());
+  }
+  #[test]
+  fn quoting_test() {
+    assert_eq!(expand("echo '!!'"), Ok("echo '!!'".to_string()));
+    assert_eq!(expand("echo \\!!"), Ok("echo \\!!".to_string()));
+    assert_eq!(expand("echo \"!!\""), Ok("echo \"echo hello world\"".to_string()));
+    assert_eq!(expand("echo ! a!= !"), Ok("echo ! a!= !".to_string()));
+    assert!(!expand_history(&history(), "echo hi").unwrap().changed);
+  }
+}
@@ -1,16 +1,16 @@
 use std::error::Error;
 use std::fmt::{self, Debug, Display};
 pub enum ParserError {
-  PipeError,
-  SyntaxError,
-  RedirectionError,
+  Pipe,
+  Syntax,
+  Redirection,
 }
 impl Debug for ParserError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match *self {
-      ParserError::PipeError => write!(f, "Pipes should be succeded/preceded by command"),
-      ParserError::SyntaxError => write!(f, "Error in parsing"),
-      ParserError::RedirectionError => {
+      ParserError::Pipe => write!(f, "Pipes should be succeded/preceded by command"),
+      ParserError::Syntax => write!(f, "Error in parsing"),
+      ParserError::Redirection => {
         write!(f, "Redirection should be succeded/preceded by command")
       }
     }
@@ -19,9 +19,9 @@ impl Debug for ParserError {
 impl Display for ParserError {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match *self {
-      ParserError::PipeError => write!(f, "Pipes should be succeded/preceded by command"),
-      ParserError::SyntaxError => write!(f, "Error in parsing"),
-      ParserError::RedirectionError => {
+      ParserError::Pipe => write!(f, "Pipes should be succeded/preceded by command"),
+      ParserError::Syntax => write!(f, "Error in parsing"),
+      ParserError::Redirection => {
         write!(f, "Redirection should be succeded/preceded by command")
       }
     }
@@ -0,0 +1,207 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+  Always,
+  And,
+  Or,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Lexeme {
+  pub idx: usize,
+  pub ch: char,
+  pub quoted: bool,
+}
+
+// Marks every char that is inside quotes or escaped; the flag is true when
+// the input ends inside a quote or right after a backslash.
+pub(crate) fn lex(input: &str) -> (Vec<Lexeme>, bool) {
+  let mut lexemes = Vec::new();
+  let mut quote: Option<char> = None;
+  let mut escaped = false;
+  for (idx, ch) in input.char_indices() {
+    let quoted = escaped || quote.is_some();
+    if escaped {
+      escaped = false;
+    } else {
+      match quote {
+        Some(q) if ch == q => quote = None,
+        Some('"') if ch == '\\' => escaped = true,
+        Some(_) => {}
+        None => match ch {
+          '\'' | '"' => quote = Some(ch),
+          '\\' => escaped = true,
+          _ => {}
+        },
+      }
+    }
+    lexemes.push(Lexeme {
+      idx,
+      ch,
+      quoted: quoted || quote.is_some() || escaped,
+    });
+  }
+  (lexemes, escaped || quote.is_some())
+}
+
+pub(crate) fn is_separator(lexeme: &Lexeme) -> bool {
+  !lexeme.quoted && (lexeme.ch.is_whitespace() || ";&|".contains(lexeme.ch))
+}
+
+fn word_start(lexemes: &[Lexeme], i: usize) -> bool {
+  i == 0 || is_separator(&lexemes[i - 1])
+}
+
+fn word_end(lexemes: &[Lexeme], i: usize) -> bool {
+  i + 1 == lexemes.len() || is_separator(&lexemes[i + 1])
+}
+
+pub fn strip_comments(input: &str) -> String {
+  let (lexemes, _) = lex(input);
+  let mut result = String::new();
+  let mut comment = false;
+  for (i, lexeme) in lexemes.iter().enumerate() {
+    if comment {
+      if lexeme.ch == '\n' {
+        comment = false;
+        result.push('\n');
+      }
+      continue;
+    }
+    if !lexeme.quoted && lexeme.ch == '#' && word_start(&lexemes, i) {
+      comment = true;
+      continue;
+    }
+    result.push(lexeme.ch);
+  }
+  result
+}
+
+fn brace_depth(lexemes: &[Lexeme], i: usize) -> i32 {
+  if lexemes[i].quoted || !word_start(lexemes, i) || !word_end(lexemes, i) {
+    return 0;
+  }
+  match lexemes[i].ch {
+    '{' => 1,
+    '}' => -1,
+    _ => 0,
+  }
+}
+
+pub fn is_complete(input: &str) -> bool {
+  let input = strip_comments(input);
+  let (lexemes, open) = lex(&input);
+  let mut depth = 0;
+  for i in 0..lexemes.len() {
+    depth += brace_depth(&lexemes, i);
+  }
+  !open && depth <= 0
+}
+
+pub fn split(input: &str) -> Vec<(Connector, String)> {
+  let input = strip_comments(input);
+  let (lexemes, _) = lex(&input);
+  let mut items = Vec::new();
+  let mut connector = Connector::Always;
+  let mut start = 0;
+  let mut depth = 0;
+  let mut i = 0;
+  while i < lexemes.len() {
+    let lexeme = lexemes[i];
+    depth += brace_depth(&lexemes, i);
+    let next = lexemes.get(i + 1).map(|next| next.ch);
+    let found = match lexeme.ch {
+      _ if lexeme.quoted || depth > 0 => None,
+      ';' | '\n' => Some((Connector::Always, 1)),
+      '&' if next == Some('&') => Some((Connector::And, 2)),
+      '|' if next == Some('|') => Some((Connector::Or, 2)),
+      _ => None,
+    };
+    if let Some((next_connector, len)) = found {
+      push_item(&mut items, connector, &input[start..lexeme.idx]);
+      connector = next_connector;
+      i += len;
+      start = lexeme.idx + len;
+      continue;
+    }
+    i += 1;
+  }
+  push_item(&mut items, connector, &input[start..]);
+  items
+}
+
+fn push_item(items: &mut Vec<(Connector, String)>, connector: Connector, item: &str) {
+  let item = item.trim();
+  if !item.is_empty() {
+    items.push((connector, item.to_string()));
+  }
+}
+
+pub fn function_definition(item: &str) -> Option<(String, String)> {
+  let mut rest = item.trim();
+  let keyword = rest.starts_with("function ");
+  if keyword {
+    rest = rest["function".len()..].trim_start();
+  }
+  let name_len = rest
+    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+    .unwrap_or(rest.len());
+  if name_len == 0 {
+    return None;
+  }
+  let name = &rest[..name_len];
+  rest = rest[name_len..].trim_start();
+  if rest.starts_with("()") {
+    rest = rest[2..].trim_start();
+  } else if !keyword {
+    return None;
+  }
+  if !rest.starts_with('{') || !rest.ends_with('}') || rest.len() < 2 {
+    return None;
+  }
+  Some((name.to_string(), rest[1..rest.len() - 1].to_string()))
+}
+
+#[cfg(test)]
+pub mod list_test {
+  use super::*;
+  #[test]
+  fn split_test() {
+    let items = split("a; b && c || 'd;e' # f; g\nh");
+    let expected = vec![
+      (Connector::Always, "a".to_string()),
+      (Connector::Always, "b".to_string()),
+      (Connector::And, "c".to_string()),
+      (Connector::Or, "'d;e'".to_string()),
+      (Connector::Always, "h".to_string()),
+    ];
+    assert_eq!(items, expected);
+  }
+  #[test]
+  fn split_braces_test() {
+    let items = split("f() { a; b; }; f");
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].1, "f() { a; b; }");
+  }
+  #[test]
+  fn is_complete_test() {
+    assert!(is_complete("echo 'a' \"b\""));
+    assert!(!is_complete("echo 'a"));
+    assert!(!is_complete("echo a \\"));
+    assert!(!is_complete("f() {\n echo a"));
+    assert!(is_complete("f() {\n echo a # }\n}"));
+    assert!(is_complete("echo {a}"));
+  }
+  #[test]
+  fn function_definition_test() {
+    assert_eq!(
+      function_definition("greet () { echo hi; }"),
+      Some(("greet".to_string(), " echo hi; ".to_string()))
+    );
+    assert_eq!(
+      function_definition("function greet {\necho hi\n}"),
+      Some(("greet".to_string(), "\necho hi\n".to_string()))
+    );
+    assert_eq!(function_definition("greet { echo hi; }"), None);
+    assert_eq!(function_definition("echo hi"), None);
+  }
+}
@@ -1,2 +1,7 @@
+pub mod alias;
+pub mod bang;
+pub mod list;
+#[allow(clippy::module_inception)]
 pub mod parser;
+pub mod span;
 mod error;
\ No newline at end of file
@@ -18,13 +18,13 @@ pub struct Parser {
 
 impl Parser {
   pub fn new(input: String) -> Parser {
-    return Parser {
+    Parser {
       pos: 0,
-      input: input,
+      input,
       artifacts: Vec::new(),
       commands: Vec::new(),
       pipes: Vec::new(),
-    };
+    }
   }
   pub fn build(&mut self) -> Option<ParserError> {
     let _tokens = self.parse();
@@ -32,7 +32,7 @@ impl Parser {
     match self.setup_pipes(self.artifacts.clone()) {
       Ok(cmd) => {
         self.commands = cmd;
-        return None;
+        None
       }
       Err(e) => Some(e),
     }
@@ -46,7 +46,7 @@ impl Parser {
         Some(token) => {
           if self.is_pipe(token) {
             if !self.is_next_cmd(i) || !self.is_prev_cmd(i) {
-              return Err(ParserError::PipeError);
+              return Err(ParserError::Pipe);
             } else {
               if let Some(Token::Command(cmd)) = artifacts.get_mut(i - 1) {
                 cmd.inp(prev_output.clone());
@@ -55,11 +55,16 @@ impl Parser {
                 prev_output = unsafe { Input::from(File::from_raw_fd(pipe.0)) };
                 commands.push(cmd.clone());
               } else {
-                return Err(ParserError::PipeError);
+                return Err(ParserError::Pipe);
               }
             }
           } else if self.is_command(token) {
-            if self.pipes.len() == 0 {
+            if let Token::Command(cmd) = token {
+              if cmd.redirects.iter().any(|(r, target)| r.takes_target() && target.is_empty()) {
+                return Err(ParserError::Redirection);
+              }
+            }
+            if self.pipes.is_empty() {
               if let Some(Token::Command(cmd)) = artifacts.get_mut(i) {
                 cmd.inp(prev_output.clone());
                 cmd.out(Output::Stdout(io::stdout()));
@@ -67,7 +72,7 @@ impl Parser {
               }
             }
           } else {
-            unreachable!();
+            return Err(ParserError::Redirection);
           }
         }
         None => {
@@ -80,7 +85,7 @@ impl Parser {
   }
   fn is_prev_cmd(&self, i: usize) -> bool {
     if i == 0 {
-      return false;
+      false
     } else {
       match self.artifacts.get(i - 1) {
         Some(token) => self.is_command(token),
@@ -96,105 +101,145 @@ impl Parser {
   }
 
   fn is_pipe(&self, token: &Token) -> bool {
-    match token {
-      Token::Pipe => true,
-      _ => false,
-    }
+    matches!(token, Token::Pipe)
   }
   fn is_command(&self, token: &Token) -> bool {
-    match token {
-      Token::Command(_c) => true,
-      _ => false,
-    }
+    matches!(token, Token::Command(_))
   }
   fn parse(&mut self) -> Vec<Token> {
     let mut parsed_vector: Vec<Token> = Vec::new();
     let input_len = self.input.len();
     loop {
+      self.skip_whitespace();
       if self.pos >= input_len {
         break;
       }
       let token = self.parse_token();
       parsed_vector.push(token)
     }
-    return parsed_vector;
+    parsed_vector
   }
   fn skip_whitespace(&mut self) {
     let input_len = self.input.len();
     while self.pos < input_len && self.get(self.pos).is_whitespace() {
-      self.pos += 1
+      self.pos += self.get(self.pos).len_utf8();
     }
   }
   fn peek_next_token(&mut self) -> String {
     self.skip_whitespace();
-    let mut j = self.pos;
-    let mut tok = String::new();
-    let input_len = self.input.len();
-    while j < input_len && valid_char(self.get(j)) {
-      tok.push(self.get(j));
-      j += 1;
-    }
-    return tok;
+    let end = self.token_end(self.pos);
+    self.input[self.pos..end].to_string()
   }
   fn next_token(&mut self) {
-    let mut j = self.pos;
-    let input_len = self.input.len();
-    while j < input_len && valid_char(self.get(j)) {
-      j += 1;
+    self.pos = self.token_end(self.pos);
+  }
+  // Quoted or escaped whitespace does not end a word; quotes are kept for expansion.
+  fn token_end(&self, start: usize) -> usize {
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, ch) in self.input[start..].char_indices() {
+      if escaped {
+        escaped = false;
+        continue;
+      }
+      match quote {
+        Some(q) if ch == q => quote = None,
+        Some('"') if ch == '\\' => escaped = true,
+        Some(_) => {}
+        None => match ch {
+          '\'' | '"' => quote = Some(ch),
+          '\\' => escaped = true,
+          _ if !valid_char(ch) => return start + i,
+          _ => {}
+        },
+      }
     }
-    self.pos = j;
+    self.input.len()
   }
   fn parse_token(&mut self) -> Token {
     self.skip_whitespace();
     let tok = self.peek_next_token();
-    return match tok {
+    match tok {
       _ if tok == PIPE => self.handle_pipe(),
       _ if tok == LT || tok == GT => self.handle_redirection(),
       _ => self.handle_command(),
-    };
+    }
   }
   fn handle_pipe(&mut self) -> Token {
     self.pipes.push(pipe().unwrap());
     self.next_token();
-    return Token::Pipe;
+    Token::Pipe
   }
   fn handle_redirection(&mut self) -> Token {
     let token = self.peek_next_token();
     self.next_token();
-    return match token {
+    match token {
       _ if token == LT => Token::Redirection(Redirection::Lt),
       _ if token == GT => Token::Redirection(Redirection::Gt),
       _ => panic!("LT or GT"),
-    };
+    }
   }
   fn handle_command(&mut self) -> Token {
     let command = self.peek_next_token();
     self.next_token();
     let input_len = self.input.len();
     let mut command_args: Vec<String> = Vec::new();
+    let mut redirects: Vec<(Redirection, String)> = Vec::new();
     while self.pos < input_len {
       let next_token = self.peek_next_token();
       match next_token {
-        _ if next_token == PIPE || next_token == LT || next_token == GT => {
+        _ if next_token.is_empty() => {
           break;
         }
-        _ => {
-          command_args.push(next_token);
-          self.next_token();
+        _ if next_token == PIPE => {
+          break;
         }
+        _ => match redirection_prefix(&next_token) {
+          Some((redirection, rest)) => {
+            self.next_token();
+            let target = match rest.is_empty() && redirection.takes_target() {
+              true => self.redirection_target(),
+              false => rest,
+            };
+            redirects.push((redirection, target));
+          }
+          None => {
+            command_args.push(next_token);
+            self.next_token();
+          }
+        },
       }
     }
-    return Token::Command(Command::new(command, command_args, None, None));
+    let mut command = Command::new(command, command_args, None, None);
+    command.redirects = redirects;
+    Token::Command(command)
+  }
+  fn redirection_target(&mut self) -> String {
+    let target = self.peek_next_token();
+    if target == PIPE || redirection_prefix(&target).is_some() {
+      return String::new();
+    }
+    self.next_token();
+    target
+  }
+  fn get(&self, pos: usize) -> char {
+    self.input[pos..].chars().next().unwrap()
   }
-  fn get(&mut self, pos: usize) -> char {
-    return self.input[pos..].chars().next().unwrap();
+}
+fn redirection_prefix(token: &str) -> Option<(Redirection, String)> {
+  for (operator, redirection) in Redirection::operators().iter() {
+    if let Some(rest) = token.strip_prefix(operator) {
+      let rest = rest.to_string();
+      if !redirection.takes_target() && !rest.is_empty() {
+        return None;
+      }
+      return Some((redirection.clone(), rest));
+    }
   }
+  None
 }
 fn valid_char(ch: char) -> bool {
-  return match ch {
-    ' ' => false,
-    _ => true,
-  };
+  !ch.is_whitespace()
 }
 
 #[cfg(test)]
@@ -241,4 +286,30 @@ pub mod parser_test {
     ];
     assert_eq!(parser.artifacts, expected);
   }
+  #[test]
+  fn redirection_test() {
+    let input = "cat <in -n >> out 2>&1 2>err | wc >'a b'".to_string();
+    let mut parser = Parser::new(input);
+    assert!(parser.build().is_none());
+    let mut cat = command::Command::new(String::from("cat"), vec![String::from("-n")], None, None);
+    cat.redirects = vec![
+      (tokens::Redirection::Lt, String::from("in")),
+      (tokens::Redirection::Append, String::from("out")),
+      (tokens::Redirection::ErrToOut, String::new()),
+      (tokens::Redirection::ErrGt, String::from("err")),
+    ];
+    let mut wc = command::Command::new(String::from("wc"), Vec::new(), None, None);
+    wc.redirects = vec![(tokens::Redirection::Gt, String::from("'a b'"))];
+    let expected = vec![
+      tokens::Token::Command(cat),
+      tokens::Token::Pipe,
+      tokens::Token::Command(wc),
+    ];
+    assert_eq!(parser.artifacts, expected);
+  }
+  #[test]
+  fn redirection_error_test() {
+    let mut parser = Parser::new("echo >".to_string());
+    assert!(parser.build().is_some());
+  }
 }
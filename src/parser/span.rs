@@ -0,0 +1,335 @@
+// Splits a command line into spans for the editor to color. Unlike the
+// parser this takes any input: an unclosed quote runs to the end and a
+// dangling operator is still an operator. Offsets count chars.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+  Command,
+  Argument,
+  String,
+  Variable,
+  Operator,
+  Redirection,
+  Comment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+  pub kind: Kind,
+}
+
+pub fn spans(input: &str) -> Vec<Span> {
+  let chars: Vec<char> = input.chars().collect();
+  let mut spans = Vec::new();
+  // the next word names a command, or is what a redirection reads or writes
+  let mut command = true;
+  let mut target = false;
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    if c == '\n' {
+      command = true;
+      i += 1;
+      continue;
+    }
+    if c.is_whitespace() {
+      i += 1;
+      continue;
+    }
+    if c == '#' {
+      let end = chars[i..].iter().position(|&c| c == '\n').map_or(chars.len(), |n| i + n);
+      push(&mut spans, i, end, Kind::Comment);
+      i = end;
+      continue;
+    }
+    if let Some((len, takes_target)) = redirection(&chars[i..]) {
+      push(&mut spans, i, i + len, Kind::Redirection);
+      target = takes_target;
+      i += len;
+      continue;
+    }
+    if let Some(len) = operator(&chars[i..]) {
+      push(&mut spans, i, i + len, Kind::Operator);
+      command = true;
+      target = false;
+      i += len;
+      continue;
+    }
+    let end = word_end(&chars, i);
+    let text: String = chars[i..end].iter().collect();
+    if target {
+      word(&chars, i, end, &mut spans);
+      target = false;
+    } else if command && (text == "{" || text == "!") {
+      push(&mut spans, i, end, Kind::Operator);
+    } else if text == "}" {
+      push(&mut spans, i, end, Kind::Operator);
+      command = false;
+    } else if command && assignment(&text) {
+      let eq = i + text.find('=').map_or(0, |n| text[..n].chars().count());
+      push(&mut spans, i, eq, Kind::Variable);
+      word(&chars, eq, end, &mut spans);
+    } else if command {
+      push(&mut spans, i, end, Kind::Command);
+      command = false;
+    } else {
+      word(&chars, i, end, &mut spans);
+    }
+    i = end;
+  }
+  spans
+}
+
+// The pair of brackets or quotes the cursor at `pos` sits on or just
+// after, if both ends are there.
+pub fn matching(input: &str, pos: usize) -> Option<(usize, usize)> {
+  let pairs = pairs(&input.chars().collect::<Vec<_>>());
+  let find = |at: usize| pairs.iter().copied().find(|&(open, close)| open == at || close == at);
+  find(pos).or_else(|| pos.checked_sub(1).and_then(find))
+}
+
+fn pairs(chars: &[char]) -> Vec<(usize, usize)> {
+  let mut pairs = Vec::new();
+  let mut open: Vec<(usize, char)> = Vec::new();
+  let mut quote: Option<(usize, char)> = None;
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    match quote {
+      Some((start, q)) if c == q => {
+        pairs.push((start, i));
+        quote = None;
+      }
+      Some((_, '"')) if c == '\\' => i += 1,
+      Some(_) => {}
+      None => match c {
+        '\\' => i += 1,
+        '\'' | '"' => quote = Some((i, c)),
+        '(' | '[' | '{' => open.push((i, c)),
+        ')' | ']' | '}' => {
+          let opening = match c {
+            ')' => '(',
+            ']' => '[',
+            _ => '{',
+          };
+          if let Some(n) = open.iter().rposition(|&(_, o)| o == opening) {
+            pairs.push((open[n].0, i));
+            open.truncate(n);
+          }
+        }
+        _ => {}
+      },
+    }
+    i += 1;
+  }
+  pairs
+}
+
+fn push(spans: &mut Vec<Span>, start: usize, end: usize, kind: Kind) {
+  if start < end {
+    spans.push(Span { start, end, kind });
+  }
+}
+
+// `>`, `>>`, `<`, `<<`, `>&2`, `2>&1`, `&>` and the like: the length and
+// whether a file name follows.
+fn redirection(chars: &[char]) -> Option<(usize, bool)> {
+  let mut i = chars.iter().take_while(|c| c.is_ascii_digit()).count();
+  if i == 0 && chars.starts_with(&['&', '>']) {
+    i = 1;
+  }
+  match chars.get(i) {
+    Some('<') | Some('>') => i += 1,
+    _ => return None,
+  }
+  if let Some('>') | Some('<') | Some('&') | Some('|') = chars.get(i) {
+    i += 1;
+  }
+  if chars[i - 1] == '&' {
+    let fd = chars[i..].iter().take_while(|c| c.is_ascii_digit() || **c == '-').count();
+    if fd > 0 {
+      return Some((i + fd, false));
+    }
+  }
+  Some((i, true))
+}
+
+fn operator(chars: &[char]) -> Option<usize> {
+  match chars {
+    ['&', '&', ..] | ['|', '|', ..] | [';', ';', ..] => Some(2),
+    [';', ..] | ['&', ..] | ['|', ..] | ['(', ..] | [')', ..] => Some(1),
+    _ => None,
+  }
+}
+
+fn word_end(chars: &[char], start: usize) -> usize {
+  let mut quote: Option<char> = None;
+  let mut i = start;
+  while i < chars.len() {
+    let c = chars[i];
+    match quote {
+      Some(q) if c == q => quote = None,
+      Some('"') if c == '\\' => i += 1,
+      Some(_) => {}
+      None => match c {
+        '\\' => i += 1,
+        '\'' | '"' => quote = Some(c),
+        c if c.is_whitespace() || ";&|()<>".contains(c) => return i,
+        _ => {}
+      },
+    }
+    i += 1;
+  }
+  chars.len()
+}
+
+fn assignment(text: &str) -> bool {
+  match text.find('=') {
+    Some(n) if n > 0 => {
+      let name = &text[..n];
+      !name.starts_with(|c: char| c.is_ascii_digit()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+    _ => false,
+  }
+}
+
+// Splits an argument into its plain, quoted and variable parts.
+fn word(chars: &[char], start: usize, end: usize, spans: &mut Vec<Span>) {
+  let mut kinds = vec![Kind::Argument; end - start];
+  let mut quote: Option<char> = None;
+  let mut i = start;
+  while i < end {
+    let c = chars[i];
+    let kind = match quote {
+      Some(_) => Kind::String,
+      None if c == '\'' || c == '"' => Kind::String,
+      None => Kind::Argument,
+    };
+    match quote {
+      Some(q) if c == q => quote = None,
+      Some('\'') => {}
+      _ if c == '\\' => {
+        kinds[i - start] = kind;
+        if i + 1 < end {
+          kinds[i + 1 - start] = kind;
+        }
+        i += 2;
+        continue;
+      }
+      _ if c == '$' => {
+        let len = variable(&chars[i..end]);
+        let kind = if len > 1 { Kind::Variable } else { kind };
+        for k in &mut kinds[i - start..i - start + len] {
+          *k = kind;
+        }
+        i += len;
+        continue;
+      }
+      None if c == '\'' || c == '"' => quote = Some(c),
+      _ => {}
+    }
+    kinds[i - start] = kind;
+    i += 1;
+  }
+  let mut from = start;
+  for n in start..end {
+    if n + 1 == end || kinds[n + 1 - start] != kinds[n - start] {
+      push(spans, from, n + 1, kinds[n - start]);
+      from = n + 1;
+    }
+  }
+}
+
+// Length of `$name`, `${...}`, `$1` or `$?` at the start of `chars`; 1
+// for a lone `$`.
+fn variable(chars: &[char]) -> usize {
+  match chars.get(1) {
+    Some('{') => chars.iter().position(|&c| c == '}').map_or(chars.len(), |n| n + 1),
+    Some(c) if c.is_ascii_digit() || "?$!#@*-".contains(*c) => 2,
+    Some(c) if c.is_alphabetic() || *c == '_' => {
+      1 + chars[1..].iter().take_while(|c| c.is_alphanumeric() || **c == '_').count()
+    }
+    _ => 1,
+  }
+}
+
+#[cfg(test)]
+pub mod span_test {
+  use super::*;
+  fn kinds(input: &str) -> Vec<(String, Kind)> {
+    let chars: Vec<char> = input.chars().collect();
+    spans(input).iter().map(|span| (chars[span.start..span.end].iter().collect(), span.kind)).collect()
+  }
+  fn pairs(list: &[(&str, Kind)]) -> Vec<(String, Kind)> {
+    list.iter().map(|(text, kind)| (text.to_string(), *kind)).collect()
+  }
+  #[test]
+  fn command_test() {
+    assert_eq!(
+      kinds("ls -l | grep \"a $x\" && echo $HOME # done"),
+      pairs(&[
+        ("ls", Kind::Command),
+        ("-l", Kind::Argument),
+        ("|", Kind::Operator),
+        ("grep", Kind::Command),
+        ("\"a ", Kind::String),
+        ("$x", Kind::Variable),
+        ("\"", Kind::String),
+        ("&&", Kind::Operator),
+        ("echo", Kind::Command),
+        ("$HOME", Kind::Variable),
+        ("# done", Kind::Comment),
+      ])
+    );
+  }
+  #[test]
+  fn redirection_test() {
+    assert_eq!(
+      kinds("A=1 cat <in 2>&1 >>out"),
+      pairs(&[
+        ("A", Kind::Variable),
+        ("=1", Kind::Argument),
+        ("cat", Kind::Command),
+        ("<", Kind::Redirection),
+        ("in", Kind::Argument),
+        ("2>&1", Kind::Redirection),
+        (">>", Kind::Redirection),
+        ("out", Kind::Argument),
+      ])
+    );
+  }
+  #[test]
+  fn incomplete_test() {
+    assert_eq!(
+      kinds("echo 'it is\nopen"),
+      pairs(&[("echo", Kind::Command), ("'it is\nopen", Kind::String)])
+    );
+    assert_eq!(kinds("ls &&"), pairs(&[("ls", Kind::Command), ("&&", Kind::Operator)]));
+    assert_eq!(
+      kinds("echo ${PA"),
+      pairs(&[("echo", Kind::Command), ("${PA", Kind::Variable)])
+    );
+    assert_eq!(
+      kinds("f() { ls; }"),
+      pairs(&[
+        ("f", Kind::Command),
+        ("(", Kind::Operator),
+        (")", Kind::Operator),
+        ("{", Kind::Operator),
+        ("ls", Kind::Command),
+        (";", Kind::Operator),
+        ("}", Kind::Operator),
+      ])
+    );
+  }
+  #[test]
+  fn matching_test() {
+    assert_eq!(matching("echo (a [b] 'c')", 5), Some((5, 15)));
+    assert_eq!(matching("echo (a [b] 'c')", 16), Some((5, 15)));
+    assert_eq!(matching("echo (a [b] 'c')", 9), Some((8, 10)));
+    assert_eq!(matching("echo (a [b] 'c')", 12), Some((12, 14)));
+    assert_eq!(matching("echo (a", 5), None);
+    assert_eq!(matching("echo '(' )", 6), Some((5, 7)));
+  }
+}
@@ -0,0 +1,315 @@
+use super::exec::call_function;
+use super::expand::{expand_word, single_quote};
+use super::lookup::{is_executable, kind_of};
+use super::shell::Shell;
+use crate::commands::builtins;
+use crate::constants::DEFAULT_PATH;
+use crate::terminal::complete::{complete_files, Candidate, Completer, Context, Request};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+// What `complete` registered for a command. Every source that is set
+// contributes candidates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spec {
+  // expanded and split each time it is used, like bash's -W
+  pub words: Option<String>,
+  pub function: Option<String>,
+  pub commands: bool,
+  pub files: bool,
+  pub directories: bool,
+}
+
+impl Spec {
+  // The spec as the `complete` command that recreates it.
+  pub fn command(&self, name: &str) -> String {
+    let mut result = "complete".to_string();
+    if self.commands {
+      result.push_str(" -c");
+    }
+    if self.directories {
+      result.push_str(" -d");
+    }
+    if self.files {
+      result.push_str(" -f");
+    }
+    if let Some(words) = &self.words {
+      result.push_str(&format!(" -W {}", single_quote(words)));
+    }
+    if let Some(function) = &self.function {
+      result.push_str(&format!(" -F {}", function));
+    }
+    format!("{} {}", result, name)
+  }
+}
+
+// Per-command completion: specs from the `complete` builtin and
+// completers registered by code embedding the shell, which win.
+#[derive(Default)]
+pub struct Completions {
+  specs: BTreeMap<String, Spec>,
+  completers: BTreeMap<String, Box<dyn Completer>>,
+}
+
+impl fmt::Debug for Completions {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Completions")
+      .field("specs", &self.specs)
+      .field("completers", &self.completers.keys().collect::<Vec<_>>())
+      .finish()
+  }
+}
+
+impl Completions {
+  pub fn register(&mut self, name: &str, completer: Box<dyn Completer>) {
+    self.completers.insert(name.to_string(), completer);
+  }
+  pub fn unregister(&mut self, name: &str) -> Option<Box<dyn Completer>> {
+    self.completers.remove(name)
+  }
+  pub fn get(&self, name: &str) -> Option<&Spec> {
+    self.specs.get(name)
+  }
+  pub fn insert(&mut self, name: &str, spec: Spec) {
+    self.specs.insert(name.to_string(), spec);
+  }
+  pub fn remove(&mut self, name: &str) -> Option<Spec> {
+    self.specs.remove(name)
+  }
+  pub fn clear(&mut self) {
+    self.specs.clear();
+  }
+  pub fn specs(&self) -> impl Iterator<Item = (&String, &Spec)> {
+    self.specs.iter()
+  }
+}
+
+impl Completer for Shell {
+  fn complete(&mut self, request: &Request) -> Vec<Candidate> {
+    let home = self.home();
+    // a registered completion that finds nothing falls back to file names
+    if request.context == Context::Argument {
+      let name = request.words[0].clone();
+      let candidates = match self.completions.completers.get_mut(&name) {
+        Some(completer) => completer.complete(request),
+        None => match self.completions.get(&name).cloned() {
+          Some(spec) => generate(self, &spec, request),
+          None => Vec::new(),
+        },
+      };
+      if !candidates.is_empty() {
+        return candidates;
+      }
+    }
+    match request.context {
+      Context::Variable => variables(self, &request.word),
+      Context::Command if !request.word.contains('/') => commands(self, &request.word),
+      Context::Command => complete_files(&request.word, home.as_deref(), true),
+      Context::Argument | Context::Redirection => complete_files(&request.word, home.as_deref(), false),
+    }
+  }
+
+  fn is_command(&mut self, name: &str) -> Option<bool> {
+    if name.contains('/') {
+      let path = match (name.strip_prefix("~/"), self.home()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(name),
+      };
+      return Some(is_executable(&path));
+    }
+    Some(kind_of(self, name).is_some())
+  }
+}
+
+pub fn generate(shell: &mut Shell, spec: &Spec, request: &Request) -> Vec<Candidate> {
+  let word = &request.word;
+  let home = shell.home();
+  let mut candidates = Vec::new();
+  if let Some(words) = &spec.words {
+    let words = expand_word(shell, words);
+    let matching = words.split_whitespace().filter(|candidate| candidate.starts_with(word.as_str()));
+    candidates.extend(matching.map(Candidate::new));
+  }
+  if spec.commands {
+    candidates.extend(commands(shell, word));
+  }
+  if spec.files {
+    candidates.extend(complete_files(word, home.as_deref(), false));
+  } else if spec.directories {
+    let files = complete_files(word, home.as_deref(), false);
+    candidates.extend(files.into_iter().filter(|candidate| candidate.suffix.is_none()));
+  }
+  if let Some(name) = &spec.function {
+    candidates.extend(call(shell, name, request).iter().map(|reply| Candidate::new(reply)));
+  }
+  candidates
+}
+
+// Runs a completion function with bash's COMP_* variables and returns
+// the COMPREPLY it leaves behind.
+fn call(shell: &mut Shell, name: &str, request: &Request) -> Vec<String> {
+  let function = match shell.functions.get(name) {
+    Some(function) => function.clone(),
+    None => return Vec::new(),
+  };
+  let cword = request.words.len() - 1;
+  let previous = match cword {
+    0 => String::new(),
+    _ => request.words[cword - 1].clone(),
+  };
+  let args = vec![request.words[0].clone(), request.word.clone(), previous];
+  shell.arrays.insert("COMP_WORDS".to_string(), request.words.clone());
+  shell.set_var("COMP_CWORD", &cword.to_string());
+  shell.set_var("COMP_LINE", &request.line);
+  shell.set_var("COMP_POINT", &request.pos.to_string());
+  shell.unset_var("COMPREPLY");
+  let status = shell.status;
+  call_function(shell, name, function, args);
+  shell.status = status;
+  // Without array assignment a plain COMPREPLY holds one candidate per
+  // word; `compgen -V COMPREPLY` and `read -a COMPREPLY` fill the array.
+  let reply = match (shell.arrays.get("COMPREPLY"), shell.get_var("COMPREPLY")) {
+    (Some(reply), _) => reply.clone(),
+    (None, Some(reply)) => reply.split_whitespace().map(String::from).collect(),
+    (None, None) => Vec::new(),
+  };
+  for name in &["COMP_WORDS", "COMP_CWORD", "COMP_LINE", "COMP_POINT", "COMPREPLY"] {
+    shell.unset_var(name);
+  }
+  reply
+}
+
+// Aliases, functions, builtins and executables on $PATH, described by
+// what kind of command each is.
+pub fn commands(shell: &Shell, prefix: &str) -> Vec<Candidate> {
+  let mut names: BTreeMap<String, String> = BTreeMap::new();
+  let mut add = |name: &str, description: String| {
+    if name.starts_with(prefix) && !names.contains_key(name) {
+      names.insert(name.to_string(), description);
+    }
+  };
+  for (name, value) in &shell.aliases {
+    add(name, format!("alias for {}", value));
+  }
+  for name in shell.functions.keys() {
+    add(name, "function".to_string());
+  }
+  for name in builtins::NAMES {
+    add(name, "builtin".to_string());
+  }
+  for dir in env::split_paths(shell.get_var("PATH").unwrap_or(DEFAULT_PATH)) {
+    let entries = match fs::read_dir(&dir) {
+      Ok(entries) => entries,
+      Err(_) => continue,
+    };
+    for entry in entries.flatten() {
+      let name = entry.file_name().to_string_lossy().to_string();
+      if name.starts_with(prefix) && is_executable(&entry.path()) {
+        add(&name, entry.path().display().to_string());
+      }
+    }
+  }
+  names
+    .into_iter()
+    .map(|(name, description)| Candidate {
+      description: Some(description),
+      ..Candidate::new(&name)
+    })
+    .collect()
+}
+
+pub fn variables(shell: &Shell, prefix: &str) -> Vec<Candidate> {
+  let names = shell.vars.keys().chain(shell.arrays.keys());
+  names
+    .filter(|name| name.starts_with(prefix))
+    .map(|name| Candidate::new(name))
+    .collect()
+}
+
+#[cfg(test)]
+pub mod complete_test {
+  use super::*;
+  use crate::runtime::exec::run_line;
+  use crate::terminal::complete::request;
+  use crate::testing::TempDir;
+  fn values(candidates: Vec<Candidate>) -> Vec<String> {
+    candidates.into_iter().map(|candidate| candidate.value).collect()
+  }
+  #[test]
+  fn commands_test() {
+    let mut shell = Shell::new(false);
+    shell.aliases.insert("histx".to_string(), "history".to_string());
+    shell.vars.remove("PATH");
+    shell.set_var("PATH", "/nonexistent");
+    let names = values(shell.complete(&request("his", 3)));
+    assert_eq!(names, vec!["history".to_string(), "histx".to_string()]);
+  }
+  #[test]
+  fn is_command_test() {
+    let mut shell = Shell::new(false);
+    shell.aliases.insert("ll".to_string(), "ls -l".to_string());
+    shell.set_var("PATH", "/bin:/usr/bin");
+    assert_eq!(shell.is_command("ll"), Some(true));
+    assert_eq!(shell.is_command("cd"), Some(true));
+    assert_eq!(shell.is_command("sh"), Some(true));
+    assert_eq!(shell.is_command("/bin/sh"), Some(true));
+    assert_eq!(shell.is_command("no-such-command"), Some(false));
+    assert_eq!(shell.is_command("/no/such/command"), Some(false));
+  }
+  #[test]
+  fn variables_test() {
+    let mut shell = Shell::new(false);
+    shell.set_var("COMPLETE_TEST_VAR", "1");
+    let names = values(shell.complete(&request("echo $COMPLETE_T", 16)));
+    assert_eq!(names, vec!["COMPLETE_TEST_VAR".to_string()]);
+  }
+  #[test]
+  fn spec_test() {
+    let mut shell = Shell::new(false);
+    run_line(&mut shell, "complete -W 'start stop status' svc");
+    assert_eq!(values(shell.complete(&request("svc st", 6))), vec!["start", "stop", "status"]);
+    run_line(&mut shell, "_tool() { COMPREPLY=\"$2-one $3-two\"; }; complete -F _tool tool");
+    assert_eq!(values(shell.complete(&request("tool x y", 8))), vec!["y-one", "x-two"]);
+    assert!(shell.get_var("COMPREPLY").is_none());
+    run_line(&mut shell, "_svc() { compgen -V COMPREPLY -W 'start stop' -- \"$2\"; }; complete -F _svc svc");
+    assert_eq!(values(shell.complete(&request("svc sto", 7))), vec!["stop"]);
+    assert!(!shell.arrays.contains_key("COMPREPLY"));
+  }
+  #[test]
+  fn fallback_test() {
+    struct Empty;
+    impl Completer for Empty {
+      fn complete(&mut self, _request: &Request) -> Vec<Candidate> {
+        Vec::new()
+      }
+    }
+    let dir = TempDir::new("complete-fallback");
+    fs::write(dir.join("notes.txt"), "").unwrap();
+    let mut shell = Shell::new(false);
+    run_line(&mut shell, "complete -W 'start stop' svc");
+    shell.completions.register("tool", Box::new(Empty));
+    for command in ["svc", "tool"].iter() {
+      let line = format!("{} {}/no", command, dir.path().display());
+      let candidates = shell.complete(&request(&line, line.len()));
+      let displays: Vec<&str> = candidates.iter().map(|c| c.display.as_str()).collect();
+      assert_eq!(displays, vec!["notes.txt"]);
+    }
+    assert_eq!(values(shell.complete(&request("svc st", 6))), vec!["start", "stop"]);
+  }
+  #[test]
+  fn register_test() {
+    struct Fixed;
+    impl Completer for Fixed {
+      fn complete(&mut self, _request: &Request) -> Vec<Candidate> {
+        vec![Candidate::new("fixed")]
+      }
+    }
+    let mut shell = Shell::new(false);
+    shell.completions.register("tool", Box::new(Fixed));
+    assert_eq!(values(shell.complete(&request("tool a", 6))), vec!["fixed"]);
+    assert_eq!(values(shell.complete(&request("other a", 7))), Vec::<String>::new());
+  }
+}
@@ -0,0 +1,452 @@
+use super::expand::{is_assignment, single_quote, try_expand_word};
+use super::lookup;
+use super::script::run_script_at;
+use super::shell::{Flow, Function, Shell, Variable};
+use crate::commands::builtins;
+use crate::parser::alias::expand_aliases;
+use crate::parser::list::{self, Connector};
+use crate::parser::parser::Parser;
+use crate::tokens::command::Command;
+use crate::tokens::input::Input;
+use crate::tokens::output::Output;
+use crate::tokens::tokens::Redirection;
+use nix::errno::Errno;
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{close, dup, dup2, fork, ForkResult, Pid};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{self, Child, Stdio};
+
+enum Exit {
+  Child(String, Child),
+  Forked(String, Pid),
+  Status(i32),
+}
+
+pub fn run_line(shell: &mut Shell, line: &str) -> i32 {
+  run_list(shell, line, true)
+}
+
+fn run_list(shell: &mut Shell, line: &str, aliases: bool) -> i32 {
+  let items = list::split(line);
+  for (i, (connector, item)) in items.iter().enumerate() {
+    let connector = *connector;
+    match connector {
+      Connector::And if shell.status != 0 => continue,
+      Connector::Or if shell.status == 0 => continue,
+      _ => {}
+    }
+    match list::function_definition(item) {
+      Some((name, body)) => {
+        let source = shell.location.clone();
+        shell.functions.insert(name, Function { body, source });
+        shell.status = 0;
+      }
+      None if aliases && !shell.aliases.is_empty() => {
+        let expanded = expand_aliases(&shell.aliases, item);
+        match expanded == *item {
+          true => run_pipeline(shell, item),
+          false => run_list(shell, &expanded, false),
+        };
+      }
+      None => {
+        run_pipeline(shell, item);
+      }
+    }
+    let guarded = matches!(items.get(i + 1), Some((Connector::And, _)) | Some((Connector::Or, _)));
+    if shell.settings.errexit && shell.status != 0 && !guarded && shell.flow.is_none() {
+      shell.flow = Some(Flow::Exit(shell.status));
+    }
+    if shell.flow.is_some() {
+      break;
+    }
+  }
+  shell.status
+}
+
+pub fn run_pipeline(shell: &mut Shell, line: &str) -> i32 {
+  let mut parser = Parser::new(line.to_string());
+  if let Some(e) = parser.build() {
+    shell.report(&e.to_string());
+    shell.status = 2;
+    return shell.status;
+  }
+  if parser.commands.is_empty() {
+    return shell.status;
+  }
+  shell.status = execute(shell, parser.commands);
+  shell.status
+}
+
+pub fn execute(shell: &mut Shell, commands: Vec<Command>) -> i32 {
+  let mut exits = Vec::new();
+  let last = commands.len().saturating_sub(1);
+  for (i, command) in commands.into_iter().enumerate() {
+    exits.push(spawn(shell, command, i < last));
+  }
+  let mut status = 0;
+  let mut failed = 0;
+  for exit in exits {
+    status = wait(shell, exit);
+    if status != 0 {
+      failed = status;
+    }
+  }
+  match shell.settings.pipefail {
+    true => failed,
+    false => status,
+  }
+}
+
+pub fn run_command(shell: &mut Shell, command: Command, functions: bool) -> i32 {
+  let exit = dispatch(shell, command, Vec::new(), functions);
+  wait(shell, exit)
+}
+
+fn wait(shell: &Shell, exit: Exit) -> i32 {
+  match exit {
+    Exit::Child(name, mut child) => match child.wait() {
+      Ok(exit_status) => match exit_status.code() {
+        Some(code) => code,
+        None => 128 + exit_status.signal().unwrap_or(0),
+      },
+      Err(e) => {
+        shell.report(&format!("{}: {}", name, e));
+        1
+      }
+    },
+    Exit::Forked(name, pid) => loop {
+      match waitpid(pid, None) {
+        Ok(WaitStatus::Exited(_, code)) => break code,
+        Ok(WaitStatus::Signaled(_, signal, _)) => break 128 + signal as i32,
+        Ok(_) => break 0,
+        Err(nix::Error::Sys(Errno::EINTR)) => continue,
+        Err(e) => {
+          shell.report(&format!("{}: {}", name, e));
+          break 1;
+        }
+      }
+    },
+    Exit::Status(status) => status,
+  }
+}
+
+// `piped` is set for every stage but the last of a pipeline.
+fn spawn(shell: &mut Shell, command: Command, piped: bool) -> Exit {
+  match prepare(shell, command) {
+    Ok(Some((command, assignments))) if piped && in_process(shell, &command) => {
+      fork_stage(shell, command, assignments)
+    }
+    Ok(Some((command, assignments))) => dispatch(shell, command, assignments, true),
+    Ok(None) => Exit::Status(0),
+    Err(e) => {
+      shell.report(&e);
+      if !shell.interactive && shell.settings.nounset {
+        shell.flow = Some(Flow::Exit(1));
+      }
+      Exit::Status(1)
+    }
+  }
+}
+
+type Assignments = Vec<(String, String)>;
+
+fn in_process(shell: &Shell, command: &Command) -> bool {
+  shell.functions.contains_key(&command.command) || builtins::lookup(&command.command).is_some()
+}
+
+// Runs a stage that would otherwise run inside the shell in a child of
+// its own. The stages after it are only started once it returns, so run
+// in place it would block for good on a full pipe nobody reads yet.
+fn fork_stage(shell: &mut Shell, command: Command, assignments: Assignments) -> Exit {
+  let name = command.command.clone();
+  match fork() {
+    Ok(ForkResult::Child) => {
+      let exit = dispatch(shell, command, assignments, true);
+      let status = wait(shell, exit);
+      let _ = io::stdout().flush();
+      let _ = io::stderr().flush();
+      unsafe { libc::_exit(status) }
+    }
+    Ok(ForkResult::Parent { child }) => Exit::Forked(name, child),
+    Err(e) => {
+      shell.report(&format!("{}: {}", name, e));
+      Exit::Status(1)
+    }
+  }
+}
+
+// Expands words and redirection targets and opens redirected files.
+// Returns None when the command only assigned variables.
+fn prepare(shell: &mut Shell, mut command: Command) -> Result<Option<(Command, Assignments)>, String> {
+  let mut words = vec![command.command.clone()];
+  words.extend(command.args.iter().cloned());
+  let mut assignments = Vec::new();
+  while !words.is_empty() && is_assignment(&words[0]) {
+    let word = words.remove(0);
+    let idx = word.find('=').unwrap();
+    let value = try_expand_word(shell, &word[idx + 1..])?;
+    assignments.push((word[..idx].to_string(), value));
+  }
+  let mut expanded = Vec::new();
+  for word in &words {
+    expanded.push(try_expand_word(shell, word)?);
+  }
+  for (redirection, target) in command.redirects.clone() {
+    let target = try_expand_word(shell, &target)?;
+    open_redirect(&mut command, &redirection, &target)
+      .map_err(|e| format!("{}: {}", target, e))?;
+  }
+  if shell.settings.xtrace {
+    trace(shell, &assignments, &expanded);
+  }
+  if expanded.is_empty() {
+    for (name, value) in assignments {
+      shell.set_var(&name, &value);
+    }
+    return Ok(None);
+  }
+  command.command = expanded.remove(0);
+  command.args = expanded;
+  Ok(Some((command, assignments)))
+}
+
+fn open_redirect(command: &mut Command, redirection: &Redirection, target: &str) -> io::Result<()> {
+  let append = OpenOptions::new().append(true).create(true).clone();
+  match redirection {
+    Redirection::Lt => command.inp(Input::from(File::open(target)?)),
+    Redirection::Gt => command.out(Output::from(File::create(target)?)),
+    Redirection::Append => command.out(Output::from(append.open(target)?)),
+    Redirection::ErrGt => command.error = Some(Output::from(File::create(target)?)),
+    Redirection::ErrAppend => command.error = Some(Output::from(append.open(target)?)),
+    Redirection::ErrToOut => {
+      command.error = Some(match &command.output {
+        Some(output) => output.clone(),
+        None => Output::from(io::stdout()),
+      })
+    }
+    Redirection::OutToErr => {
+      command.output = Some(match &command.error {
+        Some(error) => error.clone(),
+        None => Output::Stderr(io::stderr()),
+      })
+    }
+  }
+  Ok(())
+}
+
+fn trace(shell: &Shell, assignments: &[(String, String)], words: &[String]) {
+  let mut line: Vec<String> = assignments
+    .iter()
+    .map(|(name, value)| format!("{}={}", name, quote_word(value)))
+    .collect();
+  line.extend(words.iter().map(|word| quote_word(word)));
+  let ps4 = shell.get_var("PS4").unwrap_or("+ ");
+  eprintln!("{}{}", ps4, line.join(" "));
+}
+
+fn quote_word(word: &str) -> String {
+  match word.is_empty() || word.contains(|c: char| c.is_whitespace() || "'\"$\\|&;<>".contains(c)) {
+    true => single_quote(word),
+    false => word.to_string(),
+  }
+}
+
+fn dispatch(
+  shell: &mut Shell,
+  mut command: Command,
+  assignments: Vec<(String, String)>,
+  functions: bool,
+) -> Exit {
+  let function = match functions {
+    true => shell.functions.get(&command.command).cloned(),
+    false => None,
+  };
+  if let Some(function) = function {
+    let saved_fds = redirect(&mut command, true);
+    let saved_vars = assign(shell, &assignments);
+    let status = call_function(shell, &command.command, function, command.args);
+    unassign(shell, saved_vars);
+    restore(saved_fds);
+    return Exit::Status(status);
+  }
+
+  if let Some(builtin) = builtins::lookup(&command.command) {
+    let name = command.command.clone();
+    let saved_fds = redirect(&mut command, false);
+    let saved_vars = assign(shell, &assignments);
+    let status = match builtin(shell, command) {
+      Ok(status) => status,
+      Err(e) => {
+        shell.report(&format!("{}: {}", name, e));
+        1
+      }
+    };
+    unassign(shell, saved_vars);
+    restore(saved_fds);
+    return Exit::Status(status);
+  }
+
+  let path = match lookup::resolve(shell, &command.command) {
+    Ok(path) => path,
+    Err(e) => {
+      shell.report(&format!("{}: {}", command.command, e));
+      return Exit::Status(e.status());
+    }
+  };
+  let mut process = process::Command::new(path);
+  process
+    .arg0(&command.command)
+    .args(&command.args)
+    .envs(assignments);
+  if let Some(input) = command.input.take() {
+    process.stdin(Stdio::from(input));
+  }
+  if let Some(output) = command.output.take() {
+    process.stdout(Stdio::from(output));
+  }
+  if let Some(error) = command.error.take() {
+    process.stderr(Stdio::from(error));
+  }
+  match process.spawn() {
+    Ok(child) => Exit::Child(command.command, child),
+    Err(e) => {
+      shell.report(&format!("{}: {}", command.command, e));
+      match e.kind() {
+        io::ErrorKind::NotFound => Exit::Status(127),
+        _ => Exit::Status(126),
+      }
+    }
+  }
+}
+
+// Sets the `NAME=value` prefixes of a builtin or function call for its
+// duration, exported like they would be for an external command, and
+// returns what they replaced.
+fn assign(shell: &mut Shell, assignments: &[(String, String)]) -> Vec<(String, Option<Variable>)> {
+  let mut saved = Vec::new();
+  for (name, value) in assignments {
+    saved.push((name.clone(), shell.vars.get(name).cloned()));
+    shell.set_var(name, value);
+    shell.export_var(name);
+  }
+  saved
+}
+
+fn unassign(shell: &mut Shell, saved: Vec<(String, Option<Variable>)>) {
+  for (name, previous) in saved.into_iter().rev() {
+    shell.restore_var(&name, previous);
+  }
+}
+
+pub fn call_function(shell: &mut Shell, name: &str, function: Function, args: Vec<String>) -> i32 {
+  let saved_args = mem::replace(&mut shell.args, args);
+  let (source, line) = function.source.unwrap_or((name.to_string(), 1));
+  shell.call_depth += 1;
+  let status = run_script_at(shell, &source, line, &function.body);
+  shell.call_depth -= 1;
+  shell.args = saved_args;
+  shell.take_return(status)
+}
+
+// Points the shell's own descriptors at the command's pipes or files while
+// in-process code runs, returning the saved descriptors. Builtins handle
+// their own input and output, so only stderr is swapped for them.
+fn redirect(command: &mut Command, all: bool) -> Vec<(RawFd, RawFd)> {
+  let mut saved = Vec::new();
+  if all {
+    if let Some(Input::File(file)) = command.input.take() {
+      replace_fd(&mut saved, file.as_raw_fd(), 0);
+    }
+    match command.output.take() {
+      Some(Output::Stdout(_)) | None => {}
+      Some(output) => replace_fd(&mut saved, output.as_raw_fd(), 1),
+    }
+  }
+  match command.error.take() {
+    Some(Output::Stderr(_)) | None => {}
+    Some(error) => replace_fd(&mut saved, error.as_raw_fd(), 2),
+  }
+  saved
+}
+
+fn replace_fd(saved: &mut Vec<(RawFd, RawFd)>, fd: RawFd, target: RawFd) {
+  if let Ok(copy) = dup(target) {
+    if dup2(fd, target).is_ok() {
+      saved.push((target, copy));
+    } else {
+      let _ = close(copy);
+    }
+  }
+}
+
+fn restore(saved: Vec<(RawFd, RawFd)>) {
+  let _ = io::stdout().flush();
+  let _ = io::stderr().flush();
+  for (target, copy) in saved {
+    let _ = dup2(copy, target);
+    let _ = close(copy);
+  }
+}
+
+#[cfg(test)]
+pub mod exec_test {
+  use super::*;
+  use crate::testing::TempDir;
+  use std::fs;
+
+  // Runs `line` with its output sent to a file and returns what was written.
+  fn output(name: &str, line: &str) -> String {
+    let dir = TempDir::new(name);
+    let out = dir.join("out");
+    let mut shell = Shell::new(false);
+    run_line(&mut shell, &format!("{} > {}", line, out.display()));
+    fs::read_to_string(&out).unwrap().trim().to_string()
+  }
+  #[test]
+  fn function_pipe_test() {
+    let line = "big() { printf \"%0100000d\" 0; }; big | wc -c";
+    assert_eq!(output("function-pipe", line), "100000");
+  }
+  #[test]
+  fn builtin_pipe_test() {
+    assert_eq!(output("builtin-pipe", "printf \"%0100000d\" 0 | wc -c"), "100000");
+  }
+  #[test]
+  fn interrupted_wait_test() {
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+    use std::{thread, time::Duration};
+    extern "C" fn ignore(_: libc::c_int) {}
+    // without SA_RESTART, so the signal does cut the wait short
+    let action = SigAction::new(SigHandler::Handler(ignore), SaFlags::empty(), SigSet::empty());
+    let previous = unsafe { sigaction(Signal::SIGWINCH, &action).unwrap() };
+    let waiting = unsafe { libc::pthread_self() };
+    let resize = thread::spawn(move || {
+      thread::sleep(Duration::from_millis(100));
+      unsafe { libc::pthread_kill(waiting, libc::SIGWINCH) };
+    });
+    let mut shell = Shell::new(false);
+    run_line(&mut shell, "set -o pipefail; f() { sleep 0.3; }; f | true");
+    resize.join().unwrap();
+    unsafe { sigaction(Signal::SIGWINCH, &previous).unwrap() };
+    assert_eq!(shell.status, 0);
+  }
+  #[test]
+  fn prefix_assignment_test() {
+    let dir = TempDir::new("prefix-assignment");
+    let input = dir.join("in");
+    fs::write(&input, "a:b c\n").unwrap();
+    let mut shell = Shell::new(false);
+    shell.unset_var("IFS");
+    run_line(&mut shell, &format!("IFS=: read -r a b < {}", input.display()));
+    assert_eq!(shell.get_var("a"), Some("a"));
+    assert_eq!(shell.get_var("b"), Some("b c"));
+    assert_eq!(shell.get_var("IFS"), None);
+    run_line(&mut shell, "f() { b=$RUSTSH_PREFIX_TEST; }; RUSTSH_PREFIX_TEST=1 f");
+    assert_eq!(shell.get_var("b"), Some("1"));
+    assert_eq!(shell.get_var("RUSTSH_PREFIX_TEST"), None);
+  }
+}
@@ -0,0 +1,283 @@
+use super::shell::Shell;
+use std::iter::Peekable;
+use std::process;
+use std::str::Chars;
+
+pub fn expand_word(shell: &Shell, word: &str) -> String {
+  expand(shell, word, false).unwrap_or_default()
+}
+
+// Like expand_word, but fails on unset variables when `set -u` is active.
+pub fn try_expand_word(shell: &Shell, word: &str) -> Result<String, String> {
+  expand(shell, word, shell.settings.nounset)
+}
+
+fn expand(shell: &Shell, word: &str, strict: bool) -> Result<String, String> {
+  let mut result = String::new();
+  let mut chars = word.chars().peekable();
+  if word.starts_with('~') {
+    let prefix = &word[1..word.find('/').unwrap_or(word.len())];
+    if let Some(dir) = tilde(shell, prefix) {
+      result.push_str(&dir);
+      for _ in 0..=prefix.chars().count() {
+        chars.next();
+      }
+    }
+  }
+  while let Some(ch) = chars.next() {
+    match ch {
+      '\'' => {
+        for c in &mut chars {
+          if c == '\'' {
+            break;
+          }
+          result.push(c);
+        }
+      }
+      '"' => {
+        while let Some(c) = chars.next() {
+          match c {
+            '"' => break,
+            '\\' => match chars.peek() {
+              Some(&next) if next == '$' || next == '"' || next == '\\' || next == '`' => {
+                result.push(next);
+                chars.next();
+              }
+              _ => result.push(c),
+            },
+            '$' => expand_variable(shell, &mut chars, &mut result, strict)?,
+            _ => result.push(c),
+          }
+        }
+      }
+      '\\' => {
+        if let Some(c) = chars.next() {
+          result.push(c);
+        }
+      }
+      '$' => expand_variable(shell, &mut chars, &mut result, strict)?,
+      _ => result.push(ch),
+    }
+  }
+  Ok(result)
+}
+
+pub fn single_quote(value: &str) -> String {
+  format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+pub fn is_assignment(word: &str) -> bool {
+  match word.find('=') {
+    Some(idx) => is_name(&word[..idx]),
+    None => false,
+  }
+}
+
+pub fn is_name(name: &str) -> bool {
+  let mut chars = name.chars();
+  match chars.next() {
+    Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+    _ => return false,
+  }
+  chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn expand_variable(
+  shell: &Shell,
+  chars: &mut Peekable<Chars>,
+  result: &mut String,
+  strict: bool,
+) -> Result<(), String> {
+  let name = match chars.peek() {
+    Some('{') => {
+      chars.next();
+      let mut name = String::new();
+      for c in chars {
+        if c == '}' {
+          break;
+        }
+        name.push(c);
+      }
+      name
+    }
+    Some(&c) if "?$#@*".contains(c) || c.is_ascii_digit() => {
+      chars.next();
+      c.to_string()
+    }
+    Some(&c) if c.is_ascii_alphabetic() || c == '_' => {
+      let mut name = String::new();
+      while let Some(&c) = chars.peek() {
+        if !(c.is_ascii_alphanumeric() || c == '_') {
+          break;
+        }
+        name.push(c);
+        chars.next();
+      }
+      name
+    }
+    _ => {
+      result.push('$');
+      return Ok(());
+    }
+  };
+  match lookup(shell, &name) {
+    Some(value) => result.push_str(&value),
+    None if strict => return Err(format!("{}: unbound variable", name)),
+    None => {}
+  }
+  Ok(())
+}
+
+// `~N`, `~+N` and `~-N` name entries of the directory stack.
+fn tilde(shell: &Shell, prefix: &str) -> Option<String> {
+  match prefix {
+    "" => return shell.get_var("HOME").map(String::from),
+    "+" => return shell.get_var("PWD").map(String::from),
+    "-" => return shell.get_var("OLDPWD").map(String::from),
+    _ => {}
+  }
+  let entries = shell.dir_entries();
+  let index = match prefix.strip_prefix('-') {
+    Some(n) => entries.len().checked_sub(n.parse::<usize>().ok()? + 1)?,
+    None => prefix.strip_prefix('+').unwrap_or(prefix).parse::<usize>().ok()?,
+  };
+  entries.get(index).map(|dir| dir.display().to_string())
+}
+
+fn array_values(shell: &Shell, name: &str) -> Option<Vec<String>> {
+  match name {
+    "DIRSTACK" => Some(
+      shell
+        .dir_entries()
+        .iter()
+        .map(|dir| dir.display().to_string())
+        .collect(),
+    ),
+    _ => shell.arrays.get(name).cloned(),
+  }
+}
+
+fn lookup(shell: &Shell, name: &str) -> Option<String> {
+  if name.len() > 1 && name.starts_with('#') {
+    let name = &name[1..];
+    return match subscript(name) {
+      Some((array, "@")) | Some((array, "*")) => {
+        Some(array_values(shell, array).map(|a| a.len()).unwrap_or(0).to_string())
+      }
+      _ => lookup(shell, name).map(|value| value.chars().count().to_string()),
+    };
+  }
+  if let Some((array, index)) = subscript(name) {
+    let values = array_values(shell, array)?;
+    return match index {
+      "@" | "*" => Some(values.join(" ")),
+      _ => index.parse::<usize>().ok().and_then(|i| values.get(i).cloned()),
+    };
+  }
+  match name {
+    "?" => Some(shell.status.to_string()),
+    "$" => Some(process::id().to_string()),
+    "#" => Some(shell.args.len().to_string()),
+    "@" | "*" => Some(shell.args.join(" ")),
+    "0" => Some(shell.name.clone()),
+    _ => match name.parse::<usize>() {
+      Ok(n) if n > 0 => shell.args.get(n - 1).cloned(),
+      _ => match shell.get_var(name) {
+        Some(value) => Some(value.to_string()),
+        None => array_values(shell, name).and_then(|values| values.first().cloned()),
+      },
+    },
+  }
+}
+
+fn subscript(name: &str) -> Option<(&str, &str)> {
+  let open = name.find('[')?;
+  if !name.ends_with(']') {
+    return None;
+  }
+  Some((&name[..open], &name[open + 1..name.len() - 1]))
+}
+
+#[cfg(test)]
+pub mod expand_test {
+  use super::*;
+  #[test]
+  fn quote_removal_test() {
+    let shell = Shell::new(false);
+    assert_eq!(expand_word(&shell, "'a b'\"c\"\\ d"), "a bc d");
+  }
+  #[test]
+  fn variable_test() {
+    let mut shell = Shell::new(false);
+    shell.set_var("RUSTSH_EXPAND_TEST", "value");
+    assert_eq!(
+      expand_word(&shell, "$RUSTSH_EXPAND_TEST/${RUSTSH_EXPAND_TEST}x"),
+      "value/valuex"
+    );
+    assert_eq!(expand_word(&shell, "\"$RUSTSH_EXPAND_TEST\""), "value");
+    assert_eq!(expand_word(&shell, "'$RUSTSH_EXPAND_TEST'"), "$RUSTSH_EXPAND_TEST");
+    assert_eq!(expand_word(&shell, "\\$RUSTSH_EXPAND_TEST"), "$RUSTSH_EXPAND_TEST");
+    assert_eq!(expand_word(&shell, "$"), "$");
+  }
+  #[test]
+  fn positional_test() {
+    let mut shell = Shell::new(false);
+    shell.args = vec!["a".to_string(), "b".to_string()];
+    assert_eq!(expand_word(&shell, "$1-$2-$3-$#-\"$@\""), "a-b--2-a b");
+  }
+  #[test]
+  fn array_test() {
+    let mut shell = Shell::new(false);
+    shell.arrays.insert(
+      "RUSTSH_ARRAY".to_string(),
+      vec!["a".to_string(), "bc".to_string()],
+    );
+    assert_eq!(
+      expand_word(&shell, "${RUSTSH_ARRAY[1]} ${RUSTSH_ARRAY[@]} ${#RUSTSH_ARRAY[@]} $RUSTSH_ARRAY"),
+      "bc a bc 2 a"
+    );
+    assert_eq!(expand_word(&shell, "${#RUSTSH_ARRAY[1]}"), "2");
+  }
+  #[test]
+  fn nounset_test() {
+    let mut shell = Shell::new(false);
+    assert_eq!(try_expand_word(&shell, "$RUSTSH_UNSET_VAR"), Ok(String::new()));
+    shell.settings.nounset = true;
+    assert!(try_expand_word(&shell, "$RUSTSH_UNSET_VAR").is_err());
+    assert_eq!(try_expand_word(&shell, "$#"), Ok("0".to_string()));
+  }
+  #[test]
+  fn tilde_test() {
+    let mut shell = Shell::new(false);
+    shell.vars.remove("HOME");
+    shell.set_var("HOME", "/home/user");
+    assert_eq!(expand_word(&shell, "~/src"), "/home/user/src");
+    assert_eq!(expand_word(&shell, "'~'/src"), "~/src");
+  }
+  #[test]
+  fn tilde_stack_test() {
+    let mut shell = Shell::new(false);
+    shell.vars.remove("PWD");
+    shell.set_var("PWD", "/a");
+    shell.dir_stack = vec!["/b".into(), "/c".into()];
+    assert_eq!(expand_word(&shell, "~1/x"), "/b/x");
+    assert_eq!(expand_word(&shell, "~-0"), "/c");
+    assert_eq!(expand_word(&shell, "~+"), "/a");
+    assert_eq!(expand_word(&shell, "~5"), "~5");
+    assert_eq!(expand_word(&shell, "${#DIRSTACK[@]}"), "3");
+  }
+  #[test]
+  fn single_quote_test() {
+    let shell = Shell::new(false);
+    let value = "it's $HOME";
+    assert_eq!(single_quote(value), "'it'\\''s $HOME'");
+    assert_eq!(expand_word(&shell, &single_quote(value)), value);
+  }
+  #[test]
+  fn assignment_test() {
+    assert!(is_assignment("PATH=/bin"));
+    assert!(is_assignment("_a1="));
+    assert!(!is_assignment("1a=b"));
+    assert!(!is_assignment("ls"));
+  }
+}
@@ -0,0 +1,112 @@
+use super::shell::Shell;
+use crate::constants::HISTORY_FILE;
+use crate::terminal::history::{Entry, Policy, DEFAULT_SIZE};
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant};
+
+// Sets the bash-style defaults unless a startup file already chose
+// values, then reads the history file.
+pub fn load(shell: &mut Shell) {
+  if shell.get_var("HISTFILE").is_none() {
+    if let Some(home) = shell.home() {
+      shell.set_var("HISTFILE", &home.join(HISTORY_FILE).display().to_string());
+    }
+  }
+  if shell.get_var("HISTSIZE").is_none() {
+    shell.set_var("HISTSIZE", &DEFAULT_SIZE.to_string());
+  }
+  if shell.get_var("HISTFILESIZE").is_none() {
+    let size = shell.get_var("HISTSIZE").unwrap_or_default().to_string();
+    shell.set_var("HISTFILESIZE", &size);
+  }
+  let history = shell.history.clone();
+  let mut history = history.borrow_mut();
+  history.set_size(limit(shell, "HISTSIZE"));
+  if let Some(path) = path(shell) {
+    if path.exists() {
+      if let Err(e) = history.load(&path) {
+        shell.report(&format!("history: {}: {}", path.display(), e));
+      }
+    }
+  }
+}
+
+// Called before each prompt: applies $HISTCONTROL, $HISTIGNORE and
+// `set -o prefixsearch`, and merges other sessions' entries when
+// `set -o sharehistory` is on.
+pub fn sync(shell: &mut Shell) {
+  {
+    let mut history = shell.history.borrow_mut();
+    history.policy = Policy::new(shell.get_var("HISTCONTROL"), shell.get_var("HISTIGNORE"));
+    history.prefix_search = shell.settings.prefixsearch;
+  }
+  if !shell.settings.sharehistory {
+    return;
+  }
+  if let Some(path) = path(shell) {
+    if let Err(e) = shell.history.borrow_mut().merge(&path) {
+      shell.report(&format!("history: {}: {}", path.display(), e));
+    }
+  }
+}
+
+// Called when an accepted line starts running.
+pub fn start(shell: &mut Shell) -> Instant {
+  let cwd = shell.get_var("PWD").map(String::from);
+  if let Some(entry) = shell.history.borrow_mut().pending_mut() {
+    *entry = Entry {
+      cwd,
+      session: Some(process::id()),
+      ..Entry::new(entry.command.clone())
+    };
+  }
+  Instant::now()
+}
+
+pub fn finish(shell: &mut Shell, elapsed: Duration) {
+  if let Some(entry) = shell.history.borrow_mut().pending_mut() {
+    entry.status = Some(shell.status);
+    entry.duration = Some(elapsed.as_millis() as u64);
+  }
+  record(shell);
+}
+
+// Appends after each line has run, so a crash loses at most that line.
+pub fn record(shell: &mut Shell) {
+  let history = shell.history.clone();
+  let mut history = history.borrow_mut();
+  history.set_size(limit(shell, "HISTSIZE"));
+  if let Some(path) = path(shell) {
+    if let Err(e) = history.append(&path, shell.settings.sharehistory) {
+      shell.report(&format!("history: {}: {}", path.display(), e));
+    }
+  }
+}
+
+pub fn save(shell: &mut Shell) {
+  record(shell);
+  if let Some(path) = path(shell) {
+    let limit = limit(shell, "HISTFILESIZE");
+    if let Err(e) = shell.history.borrow_mut().truncate_file(&path, limit) {
+      shell.report(&format!("history: {}: {}", path.display(), e));
+    }
+  }
+}
+
+pub fn path(shell: &Shell) -> Option<PathBuf> {
+  match shell.get_var("HISTFILE") {
+    Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+    _ => None,
+  }
+}
+
+// Negative values mean no limit; anything unparsable falls back to the
+// default.
+fn limit(shell: &Shell, name: &str) -> usize {
+  match shell.get_var(name).map(|value| value.trim().parse::<i64>()) {
+    Some(Ok(n)) if n < 0 => usize::MAX,
+    Some(Ok(n)) => n as usize,
+    _ => DEFAULT_SIZE,
+  }
+}
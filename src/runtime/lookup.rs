@@ -0,0 +1,190 @@
+use super::shell::Shell;
+use crate::commands::builtins;
+use crate::constants::DEFAULT_PATH;
+use nix::unistd::{access, AccessFlags};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hashed {
+  pub path: PathBuf,
+  pub hits: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct CommandHash {
+  table: BTreeMap<String, Hashed>,
+}
+
+impl CommandHash {
+  pub fn clear(&mut self) {
+    self.table.clear();
+  }
+  pub fn get(&mut self, name: &str) -> Option<&mut Hashed> {
+    self.table.get_mut(name)
+  }
+  pub fn insert(&mut self, name: &str, path: PathBuf) {
+    self
+      .table
+      .insert(name.to_string(), Hashed { path, hits: 0 });
+  }
+  pub fn remove(&mut self, name: &str) {
+    self.table.remove(name);
+  }
+  pub fn entries(&self) -> impl Iterator<Item = (&String, &Hashed)> {
+    self.table.iter()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LookupError {
+  NotFound,
+  PermissionDenied,
+  IsDirectory,
+}
+
+impl LookupError {
+  pub fn status(&self) -> i32 {
+    match *self {
+      LookupError::NotFound => 127,
+      LookupError::PermissionDenied | LookupError::IsDirectory => 126,
+    }
+  }
+}
+
+impl Display for LookupError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      LookupError::NotFound => write!(f, "command not found"),
+      LookupError::PermissionDenied => write!(f, "Permission denied"),
+      LookupError::IsDirectory => write!(f, "Is a directory"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+  Alias(String),
+  Function,
+  Builtin,
+  File(PathBuf, bool),
+}
+
+pub fn kind_of(shell: &mut Shell, name: &str) -> Option<Kind> {
+  if let Some(value) = shell.aliases.get(name) {
+    return Some(Kind::Alias(value.clone()));
+  }
+  if shell.functions.contains_key(name) {
+    return Some(Kind::Function);
+  }
+  if builtins::lookup(name).is_some() {
+    return Some(Kind::Builtin);
+  }
+  if let Some(hashed) = shell.hash.get(name) {
+    if is_executable(&hashed.path) {
+      return Some(Kind::File(hashed.path.clone(), true));
+    }
+  }
+  match search(shell.get_var("PATH"), name) {
+    Ok(path) => Some(Kind::File(path, false)),
+    Err(_) => None,
+  }
+}
+
+pub fn resolve(shell: &mut Shell, name: &str) -> Result<PathBuf, LookupError> {
+  if name.contains('/') {
+    return check(Path::new(name));
+  }
+  if let Some(hashed) = shell.hash.get(name) {
+    if is_executable(&hashed.path) {
+      hashed.hits += 1;
+      return Ok(hashed.path.clone());
+    }
+  }
+  let path = search(shell.get_var("PATH"), name)?;
+  shell.hash.insert(name, path.clone());
+  if let Some(hashed) = shell.hash.get(name) {
+    hashed.hits += 1;
+  }
+  Ok(path)
+}
+
+pub fn search(path_var: Option<&str>, name: &str) -> Result<PathBuf, LookupError> {
+  if name.contains('/') {
+    return check(Path::new(name));
+  }
+  let mut error = LookupError::NotFound;
+  for dir in env::split_paths(path_var.unwrap_or(DEFAULT_PATH)) {
+    let dir = if dir.as_os_str().is_empty() {
+      PathBuf::from(".")
+    } else {
+      dir
+    };
+    match check(&dir.join(name)) {
+      Ok(path) => return Ok(path),
+      Err(LookupError::PermissionDenied) => error = LookupError::PermissionDenied,
+      Err(_) => {}
+    }
+  }
+  Err(error)
+}
+
+fn check(path: &Path) -> Result<PathBuf, LookupError> {
+  match path.metadata() {
+    Ok(metadata) if metadata.is_dir() => Err(LookupError::IsDirectory),
+    Ok(_) if is_executable(path) => Ok(path.to_path_buf()),
+    Ok(_) => Err(LookupError::PermissionDenied),
+    Err(_) => Err(LookupError::NotFound),
+  }
+}
+
+pub fn is_executable(path: &Path) -> bool {
+  path.is_file() && access(path, AccessFlags::X_OK).is_ok()
+}
+
+#[cfg(test)]
+pub mod lookup_test {
+  use super::*;
+  use crate::testing::TempDir;
+  use std::fs;
+  use std::os::unix::fs::PermissionsExt;
+
+  fn write_file(path: &Path, mode: u32) {
+    fs::write(path, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+  }
+
+  #[test]
+  fn search_test() {
+    let dir = TempDir::new("search");
+    write_file(&dir.join("runnable"), 0o755);
+    write_file(&dir.join("plain"), 0o644);
+    let path = dir.path().display().to_string();
+    assert_eq!(search(Some(&path), "runnable"), Ok(dir.join("runnable")));
+    assert_eq!(search(Some(&path), "missing"), Err(LookupError::NotFound));
+    assert_eq!(search(Some(&path), "plain"), Err(LookupError::PermissionDenied));
+    assert_eq!(search(None, &path), Err(LookupError::IsDirectory));
+  }
+  #[test]
+  fn unset_path_test() {
+    let found = search(None, "sh").unwrap();
+    assert!(found == Path::new("/usr/bin/sh") || found == Path::new("/bin/sh"));
+    assert_eq!(search(None, "no-such-command"), Err(LookupError::NotFound));
+  }
+  #[test]
+  fn resolve_hash_test() {
+    let dir = TempDir::new("resolve");
+    write_file(&dir.join("tool"), 0o755);
+    let mut shell = Shell::new(false);
+    shell.vars.remove("PATH");
+    shell.set_var("PATH", &dir.path().display().to_string());
+    assert_eq!(resolve(&mut shell, "tool"), Ok(dir.join("tool")));
+    assert_eq!(resolve(&mut shell, "tool"), Ok(dir.join("tool")));
+    assert_eq!(shell.hash.get("tool").map(|h| h.hits), Some(2));
+    shell.set_var("PATH", "/nonexistent");
+    assert!(shell.hash.get("tool").is_none());
+    assert_eq!(resolve(&mut shell, "tool"), Err(LookupError::NotFound));
+  }
+}
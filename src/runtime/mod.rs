@@ -0,0 +1,9 @@
+pub mod complete;
+pub mod exec;
+pub mod expand;
+pub mod histfile;
+pub mod lookup;
+pub mod script;
+pub mod shell;
+pub mod startup;
+pub mod tempfile;
@@ -0,0 +1,91 @@
+use super::exec::run_line;
+use super::shell::Shell;
+use crate::parser::list::is_complete;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub fn run_file(shell: &mut Shell, path: &Path) -> io::Result<i32> {
+  let content = fs::read_to_string(path)?;
+  Ok(run_script(shell, &path.display().to_string(), &content))
+}
+
+pub fn run_script(shell: &mut Shell, name: &str, content: &str) -> i32 {
+  run_script_at(shell, name, 1, content)
+}
+
+pub fn run_script_at(shell: &mut Shell, name: &str, first_line: usize, content: &str) -> i32 {
+  let saved_location = shell.location.take();
+  let mut status = 0;
+  let mut pending = String::new();
+  let mut start = first_line;
+  for (i, line) in content.lines().enumerate() {
+    if pending.is_empty() {
+      start = first_line + i;
+    } else if pending.ends_with('\\') {
+      pending.pop();
+    } else {
+      pending.push('\n');
+    }
+    pending.push_str(line);
+    if !is_complete(&pending) {
+      continue;
+    }
+    shell.location = Some((name.to_string(), start));
+    status = run_line(shell, &pending);
+    pending.clear();
+    if shell.flow.is_some() {
+      break;
+    }
+  }
+  if !pending.is_empty() {
+    shell.location = Some((name.to_string(), start));
+    shell.report("syntax error: unexpected end of file");
+    status = 2;
+  }
+  shell.location = saved_location;
+  status
+}
+
+#[cfg(test)]
+pub mod script_test {
+  use super::*;
+  use crate::testing::collect_reports;
+  #[test]
+  fn run_script_test() {
+    let mut shell = Shell::new(false);
+    let script = "# comment\n\nRUSTSH_SCRIPT_A=one\nRUSTSH_SCRIPT_B=\"$RUSTSH_SCRIPT_A two\"\n";
+    assert_eq!(run_script(&mut shell, "test", script), 0);
+    assert_eq!(shell.get_var("RUSTSH_SCRIPT_B"), Some("one two"));
+    assert_eq!(shell.location, None);
+  }
+  #[test]
+  fn run_script_error_test() {
+    let mut shell = Shell::new(false);
+    let reported = collect_reports(&mut shell);
+    let script = "| a\nRUSTSH_SCRIPT_C=set\n| b\n";
+    assert_eq!(run_script(&mut shell, "test", script), 2);
+    assert_eq!(shell.get_var("RUSTSH_SCRIPT_C"), Some("set"));
+    assert_eq!(
+      *reported.borrow(),
+      vec![
+        "rustsh: test:1: Pipes should be succeded/preceded by command",
+        "rustsh: test:3: Pipes should be succeded/preceded by command",
+      ]
+    );
+  }
+  #[test]
+  fn function_test() {
+    let mut shell = Shell::new(false);
+    let script = "set_b() {\n  RUSTSH_SCRIPT_D=$1\n  return 3\n  RUSTSH_SCRIPT_D=no\n}\nset_b yes\n";
+    assert_eq!(run_script(&mut shell, "test", script), 3);
+    assert_eq!(shell.get_var("RUSTSH_SCRIPT_D"), Some("yes"));
+    assert_eq!(shell.functions["set_b"].source, Some(("test".to_string(), 1)));
+    assert_eq!(shell.flow, None);
+  }
+  #[test]
+  fn unterminated_test() {
+    let mut shell = Shell::new(false);
+    assert_eq!(run_script(&mut shell, "test", "f() {\necho a\n"), 2);
+  }
+}
@@ -0,0 +1,312 @@
+use super::complete::Completions;
+use super::expand::expand_word;
+use super::lookup::CommandHash;
+use crate::constants::{DEFAULT_PROMPT, SHELL_NAME};
+use crate::terminal::history::History;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+  pub value: String,
+  pub exported: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+  pub body: String,
+  pub source: Option<(String, usize)>,
+}
+
+// Where `report` sends its messages: stderr, or a list the caller reads
+// back.
+#[derive(Debug, Clone, Default)]
+pub enum Reports {
+  #[default]
+  Stderr,
+  Collect(Rc<RefCell<Vec<String>>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+  Return(i32),
+  Exit(i32),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Settings {
+  pub errexit: bool,
+  pub nounset: bool,
+  pub xtrace: bool,
+  pub pipefail: bool,
+  pub prefixsearch: bool,
+  pub sharehistory: bool,
+  pub vi: bool,
+}
+
+impl Settings {
+  pub fn names() -> [&'static str; 8] {
+    ["emacs", "errexit", "nounset", "pipefail", "prefixsearch", "sharehistory", "vi", "xtrace"]
+  }
+  pub fn name_of(flag: char) -> Option<&'static str> {
+    match flag {
+      'e' => Some("errexit"),
+      'u' => Some("nounset"),
+      'x' => Some("xtrace"),
+      _ => None,
+    }
+  }
+  pub fn get(&self, name: &str) -> Option<bool> {
+    match name {
+      "errexit" => Some(self.errexit),
+      "nounset" => Some(self.nounset),
+      "xtrace" => Some(self.xtrace),
+      "pipefail" => Some(self.pipefail),
+      "prefixsearch" => Some(self.prefixsearch),
+      "sharehistory" => Some(self.sharehistory),
+      "emacs" => Some(!self.vi),
+      "vi" => Some(self.vi),
+      _ => None,
+    }
+  }
+  pub fn set(&mut self, name: &str, value: bool) -> bool {
+    match name {
+      "errexit" => self.errexit = value,
+      "nounset" => self.nounset = value,
+      "xtrace" => self.xtrace = value,
+      "pipefail" => self.pipefail = value,
+      "prefixsearch" => self.prefixsearch = value,
+      "sharehistory" => self.sharehistory = value,
+      // the two editing modes exclude each other
+      "emacs" => self.vi = !value,
+      "vi" => self.vi = value,
+      _ => return false,
+    }
+    true
+  }
+}
+
+#[derive(Debug)]
+pub struct Shell {
+  pub name: String,
+  pub args: Vec<String>,
+  pub vars: BTreeMap<String, Variable>,
+  pub arrays: BTreeMap<String, Vec<String>>,
+  pub functions: BTreeMap<String, Function>,
+  pub aliases: BTreeMap<String, String>,
+  pub hash: CommandHash,
+  pub status: i32,
+  pub settings: Settings,
+  pub interactive: bool,
+  pub location: Option<(String, usize)>,
+  pub flow: Option<Flow>,
+  pub call_depth: usize,
+  pub dir_stack: Vec<PathBuf>,
+  pub history: Rc<RefCell<History>>,
+  pub completions: Completions,
+  pub reports: Reports,
+}
+
+impl Shell {
+  pub fn new(interactive: bool) -> Shell {
+    let vars = env::vars()
+      .map(|(name, value)| {
+        (
+          name,
+          Variable {
+            value,
+            exported: true,
+          },
+        )
+      })
+      .collect();
+    let mut shell = Shell {
+      name: SHELL_NAME.to_string(),
+      args: Vec::new(),
+      vars,
+      arrays: BTreeMap::new(),
+      functions: BTreeMap::new(),
+      aliases: BTreeMap::new(),
+      hash: CommandHash::default(),
+      status: 0,
+      settings: Settings::default(),
+      interactive,
+      location: None,
+      flow: None,
+      call_depth: 0,
+      dir_stack: Vec::new(),
+      history: Rc::new(RefCell::new(History::new())),
+      completions: Completions::default(),
+      reports: Reports::default(),
+    };
+    shell.sync_pwd();
+    shell
+  }
+  // Keeps an inherited $PWD only when it still names the current
+  // directory, so the logical path through symlinks survives exec.
+  pub fn sync_pwd(&mut self) {
+    if self.logical_cwd().is_some() {
+      return;
+    }
+    if let Ok(dir) = env::current_dir() {
+      self.set_var("PWD", &dir.display().to_string());
+    }
+  }
+  // The current directory followed by the pushd stack, as `dirs` lists it.
+  pub fn dir_entries(&self) -> Vec<PathBuf> {
+    let current = match self.get_var("PWD") {
+      Some(pwd) if !pwd.is_empty() => PathBuf::from(pwd),
+      _ => env::current_dir().unwrap_or_default(),
+    };
+    let mut entries = vec![current];
+    entries.extend(self.dir_stack.iter().cloned());
+    entries
+  }
+  pub fn logical_cwd(&self) -> Option<PathBuf> {
+    let value = self.get_var("PWD")?;
+    let pwd = Path::new(value);
+    let clean = pwd.is_absolute() && value.split('/').all(|part| part != "." && part != "..");
+    let (logical, current) = (fs::metadata(pwd).ok()?, fs::metadata(".").ok()?);
+    match clean && logical.dev() == current.dev() && logical.ino() == current.ino() {
+      true => Some(pwd.to_path_buf()),
+      false => None,
+    }
+  }
+  pub fn get_var(&self, name: &str) -> Option<&str> {
+    self.vars.get(name).map(|var| var.value.as_str())
+  }
+  pub fn set_var(&mut self, name: &str, value: &str) {
+    let exported = match self.vars.get(name) {
+      Some(var) => var.exported,
+      None => false,
+    };
+    if exported {
+      env::set_var(name, value);
+    }
+    if name == "PATH" {
+      self.hash.clear();
+    }
+    self.vars.insert(
+      name.to_string(),
+      Variable {
+        value: value.to_string(),
+        exported,
+      },
+    );
+  }
+  pub fn export_var(&mut self, name: &str) {
+    let var = self.vars.entry(name.to_string()).or_insert(Variable {
+      value: String::new(),
+      exported: false,
+    });
+    var.exported = true;
+    env::set_var(name, &var.value);
+  }
+  pub fn unset_var(&mut self, name: &str) {
+    self.arrays.remove(name);
+    if self.vars.remove(name).is_some() {
+      env::remove_var(name);
+    }
+    if name == "PATH" {
+      self.hash.clear();
+    }
+  }
+  // Puts back a variable as it was before a temporary assignment.
+  pub fn restore_var(&mut self, name: &str, previous: Option<Variable>) {
+    match previous {
+      Some(var) => {
+        match var.exported {
+          true => env::set_var(name, &var.value),
+          false => env::remove_var(name),
+        }
+        self.vars.insert(name.to_string(), var);
+      }
+      None => {
+        self.vars.remove(name);
+        env::remove_var(name);
+      }
+    }
+    if name == "PATH" {
+      self.hash.clear();
+    }
+  }
+  pub fn take_return(&mut self, status: i32) -> i32 {
+    match self.flow {
+      Some(Flow::Return(status)) => {
+        self.flow = None;
+        status
+      }
+      _ => status,
+    }
+  }
+  pub fn exiting(&self) -> Option<i32> {
+    match self.flow {
+      Some(Flow::Exit(status)) => Some(status),
+      _ => None,
+    }
+  }
+  pub fn home(&self) -> Option<PathBuf> {
+    match self.get_var("HOME") {
+      Some(home) if !home.is_empty() => Some(PathBuf::from(home)),
+      _ => None,
+    }
+  }
+  pub fn prompt(&self) -> String {
+    match self.get_var("PS1") {
+      Some(ps1) => expand_word(self, ps1),
+      None => DEFAULT_PROMPT.to_string(),
+    }
+  }
+  pub fn report(&self, msg: &str) {
+    let message = match &self.location {
+      Some((file, line)) => format!("{}: {}:{}: {}", SHELL_NAME, file, line, msg),
+      None => format!("{}: {}", SHELL_NAME, msg),
+    };
+    match &self.reports {
+      Reports::Stderr => eprintln!("{}", message),
+      Reports::Collect(messages) => messages.borrow_mut().push(message),
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod shell_test {
+  use super::Shell;
+  #[test]
+  fn set_var_test() {
+    let mut shell = Shell::new(false);
+    shell.set_var("RUSTSH_SET_VAR_TEST", "value");
+    assert_eq!(shell.get_var("RUSTSH_SET_VAR_TEST"), Some("value"));
+    assert_eq!(std::env::var("RUSTSH_SET_VAR_TEST").ok(), None);
+  }
+  #[test]
+  fn logical_cwd_test() {
+    let mut shell = Shell::new(false);
+    shell.vars.remove("PWD");
+    let current = std::env::current_dir().unwrap().display().to_string();
+    shell.set_var("PWD", &current);
+    assert_eq!(shell.logical_cwd(), Some(current.clone().into()));
+    shell.set_var("PWD", &format!("{}/.", current));
+    assert_eq!(shell.logical_cwd(), None);
+    shell.set_var("PWD", "/nonexistent");
+    assert_eq!(shell.logical_cwd(), None);
+  }
+  #[test]
+  fn export_var_test() {
+    let mut shell = Shell::new(false);
+    shell.set_var("RUSTSH_EXPORT_VAR_TEST", "value");
+    shell.export_var("RUSTSH_EXPORT_VAR_TEST");
+    assert_eq!(
+      std::env::var("RUSTSH_EXPORT_VAR_TEST").ok(),
+      Some("value".to_string())
+    );
+    shell.unset_var("RUSTSH_EXPORT_VAR_TEST");
+    assert_eq!(shell.get_var("RUSTSH_EXPORT_VAR_TEST"), None);
+    assert_eq!(std::env::var("RUSTSH_EXPORT_VAR_TEST").ok(), None);
+  }
+}
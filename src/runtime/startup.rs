@@ -0,0 +1,104 @@
+use super::expand::expand_word;
+use super::script::run_file;
+use super::shell::Shell;
+use crate::constants::{PROFILE_FILE, RC_FILE};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+  pub login: bool,
+  pub norc: bool,
+  pub rcfile: Option<PathBuf>,
+  pub script: Option<PathBuf>,
+  pub args: Vec<String>,
+}
+
+impl Options {
+  pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    if let Some(name) = args.first() {
+      options.login = name.starts_with('-');
+    }
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+      match arg.as_str() {
+        "-l" | "--login" => options.login = true,
+        "--norc" => options.norc = true,
+        "--rcfile" => match iter.next() {
+          Some(path) => options.rcfile = Some(PathBuf::from(path)),
+          None => return Err("--rcfile: option requires an argument".to_string()),
+        },
+        "--" => {
+          options.script = iter.next().map(PathBuf::from);
+          break;
+        }
+        _ if arg.starts_with('-') => return Err(format!("{}: invalid option", arg)),
+        _ => {
+          options.script = Some(PathBuf::from(arg));
+          break;
+        }
+      }
+    }
+    options.args = iter.cloned().collect();
+    Ok(options)
+  }
+}
+
+pub fn load(shell: &mut Shell, options: &Options) {
+  if options.login {
+    if let Some(home) = shell.home() {
+      source_if_exists(shell, &home.join(PROFILE_FILE));
+    }
+  }
+  if !shell.interactive || options.norc {
+    return;
+  }
+  let rcfile = match &options.rcfile {
+    Some(path) => Some(path.clone()),
+    None => match shell.get_var("ENV") {
+      Some(env) if !env.is_empty() => Some(PathBuf::from(expand_word(shell, env))),
+      _ => shell.home().map(|home| home.join(RC_FILE)),
+    },
+  };
+  if let Some(path) = rcfile {
+    source_if_exists(shell, &path);
+  }
+}
+
+fn source_if_exists(shell: &mut Shell, path: &Path) {
+  if !path.exists() {
+    return;
+  }
+  if let Err(e) = run_file(shell, path) {
+    shell.report(&format!("{}: {}", path.display(), e));
+  }
+}
+
+#[cfg(test)]
+pub mod startup_test {
+  use super::*;
+  fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+  }
+  #[test]
+  fn parse_login_test() {
+    assert!(Options::parse(&args(&["-rustsh"])).unwrap().login);
+    assert!(Options::parse(&args(&["rustsh", "-l"])).unwrap().login);
+    assert!(!Options::parse(&args(&["rustsh"])).unwrap().login);
+  }
+  #[test]
+  fn parse_rcfile_test() {
+    let options = Options::parse(&args(&["rustsh", "--norc", "--rcfile", "/tmp/rc"])).unwrap();
+    assert!(options.norc);
+    assert_eq!(options.rcfile, Some(PathBuf::from("/tmp/rc")));
+    assert!(Options::parse(&args(&["rustsh", "--rcfile"])).is_err());
+    assert!(Options::parse(&args(&["rustsh", "--bogus"])).is_err());
+  }
+  #[test]
+  fn parse_script_test() {
+    let options = Options::parse(&args(&["rustsh", "script.sh", "-l", "a"])).unwrap();
+    assert!(!options.login);
+    assert_eq!(options.script, Some(PathBuf::from("script.sh")));
+    assert_eq!(options.args, args(&["-l", "a"]));
+  }
+}
@@ -0,0 +1,68 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::io::FromRawFd;
+use std::path::{Path, PathBuf};
+
+// A file handed to an editor. mkstemps creates it under a name nobody can
+// guess ahead of time, only if nothing is there yet, and readable by this
+// user alone, so another user of a shared temp dir cannot plant a symlink
+// or swap what is read back. It is removed when dropped.
+pub struct TempFile {
+  path: PathBuf,
+}
+
+impl TempFile {
+  pub fn create(name: &str, suffix: &str, content: &str) -> io::Result<TempFile> {
+    let template = env::temp_dir().join(format!("rustsh-{}-XXXXXX{}", name, suffix));
+    let mut bytes = template.as_os_str().as_bytes().to_vec();
+    bytes.push(0);
+    let fd = unsafe { libc::mkstemps(bytes.as_mut_ptr() as *mut libc::c_char, suffix.len() as libc::c_int) };
+    if fd < 0 {
+      return Err(io::Error::last_os_error());
+    }
+    bytes.pop();
+    let temp = TempFile {
+      path: PathBuf::from(OsString::from_vec(bytes)),
+    };
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(content.as_bytes())?;
+    Ok(temp)
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn read(&self) -> io::Result<String> {
+    fs::read_to_string(&self.path)
+  }
+}
+
+impl Drop for TempFile {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+#[cfg(test)]
+pub mod tempfile_test {
+  use super::TempFile;
+  use std::fs;
+  use std::os::unix::fs::PermissionsExt;
+  #[test]
+  fn create_test() {
+    let first = TempFile::create("test", ".sh", "echo hi\n").unwrap();
+    let second = TempFile::create("test", ".sh", "").unwrap();
+    assert_ne!(first.path(), second.path());
+    assert!(first.path().display().to_string().ends_with(".sh"));
+    assert_eq!(first.read().unwrap(), "echo hi\n");
+    let mode = fs::metadata(first.path()).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    let path = first.path().to_path_buf();
+    drop(first);
+    assert!(!path.exists());
+  }
+}
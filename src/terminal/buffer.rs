@@ -1,32 +1,40 @@
 use std::iter::FromIterator;
+use unicode_segmentation::UnicodeSegmentation;
+
+// The line being edited, one entry per grapheme cluster so a letter and
+// its combining marks, or a joined emoji, move and delete as one.
 #[derive(Clone, Default, Debug)]
 pub struct Buffer {
-  data: Vec<char>,
+  data: Vec<String>,
 }
 impl FromIterator<char> for Buffer {
   fn from_iter<T: IntoIterator<Item = char>>(t: T) -> Self {
-    Self {
-      data: t.into_iter().collect(),
-    }
+    Self::from(t.into_iter().collect::<String>())
   }
 }
 impl From<Buffer> for String {
   fn from(buf: Buffer) -> Self {
-    Self::from_iter(buf.data)
+    buf.data.concat()
   }
 }
 
 impl From<String> for Buffer {
   fn from(s: String) -> Self {
-    Self::from_iter(s.chars())
+    Self::from(s.as_str())
   }
 }
 
 impl<'a> From<&'a str> for Buffer {
   fn from(s: &'a str) -> Self {
-    Self::from_iter(s.chars())
+    Self {
+      data: s.graphemes(true).map(String::from).collect(),
+    }
   }
 }
+// First char of a grapheme, which decides what kind of text it is.
+pub fn base(grapheme: &str) -> char {
+  grapheme.chars().next().unwrap_or(' ')
+}
 impl Buffer {
   pub fn new() -> Self {
     Self { data: Vec::new() }
@@ -37,24 +45,90 @@ impl Buffer {
   }
 
   pub fn as_str(&self) -> String {
-    String::from(self.clone())
+    self.data.concat()
+  }
+
+  pub fn graphemes(&self) -> &[String] {
+    &self.data
   }
 
-  pub fn remove(&mut self, start: usize, end: usize) -> Vec<char> {
+  pub fn remove(&mut self, start: usize, end: usize) -> String {
     self.data.drain(start..end).collect()
   }
 
-  pub fn insert_str(&mut self, idx: usize, string: &str) {
-    for (i, c) in string.chars().enumerate() {
-      self.data.insert(idx + i, c)
-    }
+  // Inserts `string` before grapheme `idx` and returns the index just
+  // past it. Marks that combine with their neighbours merge into them.
+  pub fn insert_str(&mut self, idx: usize, string: &str) -> usize {
+    self.replace(idx, idx, string)
   }
   pub fn clear(&mut self) {
     self.data = Vec::new();
   }
+
+  pub fn grapheme(&self, idx: usize) -> Option<&str> {
+    self.data.get(idx).map(String::as_str)
+  }
+
+  pub fn slice(&self, start: usize, end: usize) -> String {
+    self.data[start..end].concat()
+  }
+
+  // Replaces graphemes `start..end` with `string`, returning the index
+  // just past the new text.
+  pub fn replace(&mut self, start: usize, end: usize, string: &str) -> usize {
+    let head = format!("{}{}", self.data[..start].concat(), string);
+    let pos = head.graphemes(true).count();
+    let line = format!("{}{}", head, self.data[end..].concat());
+    self.data = line.graphemes(true).map(String::from).collect();
+    pos.min(self.data.len())
+  }
+
+  // Chars before grapheme `idx`, for code that counts in chars.
+  pub fn char_offset(&self, idx: usize) -> usize {
+    self.data[..idx.min(self.data.len())].iter().map(|g| g.chars().count()).sum()
+  }
+
+  // The grapheme holding char `offset`, or the one after when it falls
+  // inside a cluster.
+  pub fn index_of(&self, offset: usize) -> usize {
+    let mut chars = 0;
+    for (idx, grapheme) in self.data.iter().enumerate() {
+      if chars >= offset {
+        return idx;
+      }
+      chars += grapheme.chars().count();
+    }
+    self.data.len()
+  }
+
+  // Start of the word at or before `pos`, skipping separators first.
+  pub fn word_start<F: Fn(char) -> bool>(&self, pos: usize, is_word: F) -> usize {
+    let mut idx = pos.min(self.data.len());
+    while idx > 0 && !is_word(base(&self.data[idx - 1])) {
+      idx -= 1;
+    }
+    while idx > 0 && is_word(base(&self.data[idx - 1])) {
+      idx -= 1;
+    }
+    idx
+  }
+
+  // End of the word at or after `pos`, skipping separators first.
+  pub fn word_end<F: Fn(char) -> bool>(&self, pos: usize, is_word: F) -> usize {
+    let mut idx = pos.min(self.data.len());
+    while idx < self.data.len() && !is_word(base(&self.data[idx])) {
+      idx += 1;
+    }
+    while idx < self.data.len() && is_word(base(&self.data[idx])) {
+      idx += 1;
+    }
+    idx
+  }
 }
 
 #[cfg(test)]
+// the older tests pass their literals through .into()
+#[allow(clippy::useless_conversion)]
 pub mod terminal_test {
   use super::Buffer;
 
@@ -63,7 +137,7 @@ pub mod terminal_test {
     let string: String = "awdwad7*&D*WA%^V57a a w  a\n".into();
     let buffer = Buffer::from(string.clone());
     for (buf_data, str_data) in buffer.data.iter().zip(string.chars()) {
-      assert_eq!(*buf_data, str_data);
+      assert_eq!(*buf_data, str_data.to_string());
     }
   }
   #[test]
@@ -95,10 +169,43 @@ pub mod terminal_test {
     assert_eq!(buffer.len(), string.len());
   }
   #[test]
+  fn word_test() {
+    let buffer = Buffer::from("git  commit -m fix/bug");
+    let is_word = |c: char| c.is_alphanumeric();
+    assert_eq!(buffer.word_start(11, is_word), 5);
+    assert_eq!(buffer.word_start(5, is_word), 0);
+    assert_eq!(buffer.word_end(3, is_word), 11);
+    assert_eq!(buffer.word_end(22, is_word), 22);
+    assert_eq!(buffer.word_start(22, |c: char| !c.is_whitespace()), 15);
+    assert_eq!(buffer.word_start(22, |c: char| c.is_alphanumeric() || c == '/'), 15);
+  }
+  #[test]
+  fn replace_test() {
+    let mut buffer = Buffer::from("one two");
+    buffer.replace(4, 7, "three");
+    assert_eq!(buffer.as_str(), "one three");
+    assert_eq!(buffer.slice(0, 3), "one");
+    assert_eq!(buffer.grapheme(4), Some("t"));
+  }
+  #[test]
+  fn grapheme_test() {
+    let mut buffer = Buffer::from("cafe\u{301} 👍🏽!");
+    assert_eq!(buffer.len(), 7);
+    assert_eq!(buffer.grapheme(3), Some("e\u{301}"));
+    assert_eq!(buffer.grapheme(5), Some("👍🏽"));
+    assert_eq!(buffer.char_offset(6), 8);
+    assert_eq!(buffer.index_of(8), 6);
+    assert_eq!(buffer.index_of(4), 4);
+    assert_eq!(buffer.insert_str(1, "\u{301}"), 1);
+    assert_eq!(buffer.grapheme(0), Some("c\u{301}"));
+    buffer.remove(5, 6);
+    assert_eq!(buffer.as_str(), "c\u{301}afe\u{301} !");
+  }
+  #[test]
   fn clear_test() {
     let string: String = "awdwad7*&Ddawdawd a wd ad wa wd%^V57a a w  a\n".into();
     let mut buffer = Buffer::from(string.clone());
     buffer.clear();
-    assert_eq!(buffer.data, Vec::new());
+    assert_eq!(buffer.data, Vec::<String>::new());
   }
 }
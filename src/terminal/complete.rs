@@ -0,0 +1,384 @@
+use crate::parser::list::lex;
+use crate::runtime::expand::is_assignment;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Context {
+  Command,
+  Argument,
+  Redirection,
+  Variable,
+}
+
+// The word under the cursor and where it sits in the line. Positions
+// are char offsets into `line`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+  pub line: String,
+  pub pos: usize,
+  pub start: usize,
+  // the word as typed, up to the cursor
+  pub raw: String,
+  // the same with quoting removed
+  pub word: String,
+  // unquoted words of the current command, ending with `word`
+  pub words: Vec<String>,
+  pub context: Context,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+  // replaces the whole word, before quoting
+  pub value: String,
+  pub display: String,
+  // appended once the word is complete; None after a directory
+  pub suffix: Option<char>,
+  // shown next to the candidate in the completion menu
+  pub description: Option<String>,
+}
+impl Candidate {
+  pub fn new(value: &str) -> Candidate {
+    Candidate {
+      value: value.to_string(),
+      display: value.to_string(),
+      suffix: Some(' '),
+      description: None,
+    }
+  }
+}
+
+pub trait Completer {
+  fn complete(&mut self, request: &Request) -> Vec<Candidate>;
+  // Whether `name` would run as a command, for coloring it; None when
+  // there is no way to tell.
+  fn is_command(&mut self, _name: &str) -> Option<bool> {
+    None
+  }
+}
+
+// Completes file names everywhere; used when no shell is around.
+pub struct Files;
+impl Completer for Files {
+  fn complete(&mut self, request: &Request) -> Vec<Candidate> {
+    complete_files(&request.word, None, false)
+  }
+}
+
+fn is_boundary(ch: char) -> bool {
+  ch.is_whitespace() || ";&|<>()".contains(ch)
+}
+
+pub fn request(line: &str, pos: usize) -> Request {
+  let before: Vec<char> = line.chars().take(pos).collect();
+  let pos = before.len();
+  let text: String = before.iter().collect();
+  let (lexemes, _) = lex(&text);
+  let mut words: Vec<String> = Vec::new();
+  let mut word = String::new();
+  let mut start = 0;
+  let mut target = false;
+  let mut command = true;
+  for (i, lexeme) in lexemes.iter().enumerate() {
+    if lexeme.quoted || !is_boundary(lexeme.ch) {
+      if word.is_empty() {
+        start = i;
+      }
+      word.push(lexeme.ch);
+      continue;
+    }
+    if !word.is_empty() {
+      let finished = unquote(&std::mem::take(&mut word));
+      if target {
+        target = false;
+      } else if !(command && is_assignment(&finished)) {
+        command = command && finished == "{";
+        if !command {
+          words.push(finished);
+        }
+      }
+    }
+    match lexeme.ch {
+      ';' | '&' | '|' | '(' => {
+        words.clear();
+        command = true;
+        target = false;
+      }
+      '<' | '>' => target = true,
+      _ => {}
+    }
+    start = i + 1;
+  }
+  let raw: String = before[start..].iter().collect();
+  let mut context = match (target, command) {
+    (true, _) => Context::Redirection,
+    (_, true) => Context::Command,
+    _ => Context::Argument,
+  };
+  let mut word = unquote(&raw);
+  if let Some(offset) = variable_start(&raw) {
+    context = Context::Variable;
+    start += offset;
+    word = raw.chars().skip(offset).collect();
+  }
+  let raw: String = before[start..].iter().collect();
+  words.push(word.clone());
+  Request {
+    line: line.to_string(),
+    pos,
+    start,
+    raw,
+    word,
+    words,
+    context,
+  }
+}
+
+// Char offset of a variable name being typed at the end of `raw`, just
+// past `$` or `${`.
+fn variable_start(raw: &str) -> Option<usize> {
+  let chars: Vec<char> = raw.chars().collect();
+  let mut name = chars.len();
+  while name > 0 && (chars[name - 1].is_ascii_alphanumeric() || chars[name - 1] == '_') {
+    name -= 1;
+  }
+  let dollar = match name {
+    _ if name >= 2 && chars[name - 1] == '{' && chars[name - 2] == '$' => name - 2,
+    _ if name >= 1 && chars[name - 1] == '$' => name - 1,
+    _ => return None,
+  };
+  let mut single = false;
+  let mut escaped = false;
+  for &c in &chars[..dollar] {
+    match c {
+      _ if escaped => escaped = false,
+      '\\' if !single => escaped = true,
+      '\'' => single = !single,
+      _ => {}
+    }
+  }
+  match single || escaped {
+    true => None,
+    false => Some(name),
+  }
+}
+
+pub fn unquote(raw: &str) -> String {
+  let mut result = String::new();
+  let mut quote: Option<char> = None;
+  let mut chars = raw.chars().peekable();
+  while let Some(c) = chars.next() {
+    match (quote, c) {
+      (None, '\'') | (None, '"') => quote = Some(c),
+      (Some(q), _) if c == q => quote = None,
+      (None, '\\') => {
+        if let Some(next) = chars.next() {
+          result.push(next);
+        }
+      }
+      (Some('"'), '\\') => match chars.peek() {
+        Some(&next) if "\"$`\\".contains(next) => {
+          result.push(next);
+          chars.next();
+        }
+        _ => result.push(c),
+      },
+      _ => result.push(c),
+    }
+  }
+  result
+}
+
+// Quotes `text` so the shell reads it back unchanged, continuing an
+// unclosed quote when the word started with one.
+pub fn quote(text: &str, open: Option<char>) -> String {
+  let mut result = String::new();
+  match open {
+    Some('\'') => {
+      result.push('\'');
+      result.push_str(&text.replace('\'', "'\\''"));
+    }
+    Some(_) => {
+      result.push('"');
+      for c in text.chars() {
+        if "\"$`\\".contains(c) {
+          result.push('\\');
+        }
+        result.push(c);
+      }
+    }
+    None => {
+      for (i, c) in text.chars().enumerate() {
+        if " \t\n'\"\\$`;&|<>()*?[]{}!".contains(c) || (i == 0 && c == '#') {
+          result.push('\\');
+        }
+        result.push(c);
+      }
+    }
+  }
+  result
+}
+
+// The quote a word was started with, if it is still open.
+pub fn open_quote(raw: &str) -> Option<char> {
+  let first = raw.chars().next().filter(|&c| c == '\'' || c == '"')?;
+  let (_, open) = lex(raw);
+  match open {
+    true => Some(first),
+    false => None,
+  }
+}
+
+// The text that replaces the typed word: the unique candidate with its
+// suffix, or the prefix all candidates share.
+pub fn replacement(request: &Request, candidates: &[Candidate]) -> Option<String> {
+  let before: String = request.line.chars().take(request.start).collect();
+  let braced = request.context == Context::Variable && before.ends_with("${");
+  let open = match request.context {
+    Context::Variable => None,
+    _ => open_quote(&request.raw),
+  };
+  let quoted = |text: &str| match request.context {
+    Context::Variable => text.to_string(),
+    _ => quote(text, open),
+  };
+  if candidates.len() == 1 {
+    let candidate = &candidates[0];
+    let mut text = quoted(&candidate.value);
+    if let Some(suffix) = candidate.suffix {
+      if let Some(open) = open {
+        text.push(open);
+      }
+      text.push(if braced { '}' } else { suffix });
+    }
+    return Some(text);
+  }
+  let prefix = common_prefix(candidates);
+  match prefix.chars().count() > request.word.chars().count() {
+    true => Some(quoted(&prefix)),
+    false => None,
+  }
+}
+
+pub fn common_prefix(candidates: &[Candidate]) -> String {
+  let mut prefix: Vec<char> = match candidates.first() {
+    Some(candidate) => candidate.value.chars().collect(),
+    None => return String::new(),
+  };
+  for candidate in &candidates[1..] {
+    let len = prefix
+      .iter()
+      .zip(candidate.value.chars())
+      .take_while(|(a, b)| *a == b)
+      .count();
+    prefix.truncate(len);
+  }
+  prefix.into_iter().collect()
+}
+
+// Entries of the directory named by `word` that start with its last
+// component. `executables` keeps only directories and runnable files.
+pub fn complete_files(word: &str, home: Option<&Path>, executables: bool) -> Vec<Candidate> {
+  let (dir, name) = match word.rfind('/') {
+    Some(idx) => (&word[..=idx], &word[idx + 1..]),
+    None => ("", word),
+  };
+  let lookup = match (dir.strip_prefix("~/"), home) {
+    (Some(rest), Some(home)) => home.join(rest),
+    _ if dir.is_empty() => Path::new(".").to_path_buf(),
+    _ => Path::new(dir).to_path_buf(),
+  };
+  let entries = match fs::read_dir(&lookup) {
+    Ok(entries) => entries,
+    Err(_) => return Vec::new(),
+  };
+  let mut candidates = Vec::new();
+  for entry in entries.flatten() {
+    let file_name = entry.file_name().to_string_lossy().to_string();
+    if !file_name.starts_with(name) || (file_name.starts_with('.') && !name.starts_with('.')) {
+      continue;
+    }
+    let path = entry.path();
+    let candidate = match path.is_dir() {
+      true => Candidate {
+        value: format!("{}{}/", dir, file_name),
+        display: format!("{}/", file_name),
+        suffix: None,
+        description: None,
+      },
+      false if executables && !crate::runtime::lookup::is_executable(&path) => continue,
+      false => Candidate {
+        value: format!("{}{}", dir, file_name),
+        display: file_name,
+        suffix: Some(' '),
+        description: None,
+      },
+    };
+    candidates.push(candidate);
+  }
+  candidates
+}
+
+#[cfg(test)]
+pub mod complete_test {
+  use super::*;
+  use crate::testing::TempDir;
+  #[test]
+  fn request_test() {
+    let request = request("ls -l my\\ fi", 12);
+    assert_eq!(request.context, Context::Argument);
+    assert_eq!(request.start, 6);
+    assert_eq!(request.word, "my fi");
+    assert_eq!(request.words, vec!["ls".to_string(), "-l".to_string(), "my fi".to_string()]);
+    assert_eq!(super::request("FOO=1 gi", 8).context, Context::Command);
+    assert_eq!(super::request("1x=foo gi", 9).context, Context::Argument);
+    assert_eq!(super::request("ls | gr", 7).context, Context::Command);
+    assert_eq!(super::request("{ ec", 4).context, Context::Command);
+    assert_eq!(super::request("cat < fi", 8).context, Context::Redirection);
+    assert_eq!(super::request("cat >", 5).context, Context::Redirection);
+    assert_eq!(super::request("cat > out ar", 12).context, Context::Argument);
+    let variable = super::request("echo \"${HO", 10);
+    assert_eq!(variable.context, Context::Variable);
+    assert_eq!((variable.start, variable.word.as_str()), (8, "HO"));
+    assert_eq!(super::request("echo '$HO", 9).context, Context::Argument);
+  }
+  #[test]
+  fn quote_test() {
+    assert_eq!(quote("a b&c", None), "a\\ b\\&c");
+    assert_eq!(quote("#x~", None), "\\#x~");
+    assert_eq!(quote("it's", Some('\'')), "'it'\\''s");
+    assert_eq!(quote("a\"b", Some('"')), "\"a\\\"b");
+    assert_eq!(unquote("'a b'\\ c\"\\$d\""), "a b c$d");
+    assert_eq!(open_quote("'abc"), Some('\''));
+    assert_eq!(open_quote("'abc'"), None);
+  }
+  #[test]
+  fn replacement_test() {
+    let request = super::request("cat my", 6);
+    let unique = vec![Candidate::new("my file")];
+    assert_eq!(replacement(&request, &unique), Some("my\\ file ".to_string()));
+    let several = vec![Candidate::new("my file"), Candidate::new("my files")];
+    assert_eq!(replacement(&request, &several), Some("my\\ file".to_string()));
+    let request = super::request("cat 'my", 7);
+    assert_eq!(replacement(&request, &unique), Some("'my file' ".to_string()));
+    let request = super::request("echo ${HO", 9);
+    assert_eq!(replacement(&request, &[Candidate::new("HOME")]), Some("HOME}".to_string()));
+    let request = super::request("cat my\\ file", 12);
+    assert_eq!(replacement(&request, &several), None);
+  }
+  #[test]
+  fn files_test() {
+    let dir = TempDir::new("complete");
+    fs::create_dir_all(dir.join("sub dir")).unwrap();
+    fs::write(dir.join("script.sh"), "").unwrap();
+    fs::write(dir.join(".hidden"), "").unwrap();
+    let prefix = format!("{}/s", dir.path().display());
+    let mut candidates = complete_files(&prefix, None, false);
+    candidates.sort_by(|a, b| a.value.cmp(&b.value));
+    let displays: Vec<&str> = candidates.iter().map(|c| c.display.as_str()).collect();
+    assert_eq!(displays, vec!["script.sh", "sub dir/"]);
+    assert_eq!(candidates[1].suffix, None);
+    assert!(complete_files(&prefix, None, true).iter().all(|c| c.suffix.is_none()));
+    assert_eq!(complete_files(&format!("{}/", dir.path().display()), None, false).len(), 2);
+  }
+}
@@ -1,10 +1,19 @@
 use super::action::Action;
+use super::complete::Completer;
 use super::cursor::{move_down, move_left, move_right, move_up};
+use super::search::Direction;
 use super::signal::exit_signal_safe;
+use super::reader;
 use super::terminal::Terminal;
+use super::vi::{Effect, Mode};
+use crate::parser::bang::expand_history;
+use crate::parser::list::is_complete;
+use crate::runtime::tempfile::TempFile;
 use nix::sys::signal::Signal;
 use std::cell::RefCell;
+use std::env;
 use std::io::{self, Write};
+use std::process;
 use std::rc::Rc;
 use std::string::ToString;
 use termion::event::{Event, Key};
@@ -18,37 +27,123 @@ impl Handler {
   ) -> Option<Result<Action, io::Error>> {
     match signal {
       Signal::SIGINT => {
-        terminal.history.clear_buffer();
+        terminal.search = None;
+        let menu = terminal.menu.take();
+        terminal.clear_match();
+        terminal.move_to_end();
+        if menu.is_some() {
+          terminal.clear_to_screen_end();
+        }
+        terminal.history.borrow_mut().clear_buffer();
         terminal.reset();
         terminal.write_linefeed();
         terminal.write_line();
-        return Some(Ok(Action::Cancel));
+        Some(Ok(Action::Cancel))
       }
       Signal::SIGTSTP | Signal::SIGQUIT => {
         terminal.reset();
-        terminal.history.clear_buffer();
-        return Some(Ok(Action::Exit));
+        terminal.history.borrow_mut().clear_buffer();
+        Some(Ok(Action::Exit))
+      }
+      Signal::SIGWINCH => {
+        terminal.resize();
+        None
       }
       Signal::SIGCONT => {
-        return None;
+        None
       }
       _ => None,
     }
   }
-  pub fn handle_event(terminal: &mut Terminal, event: Event) -> Option<Result<Action, io::Error>> {
+  pub fn handle_event(
+    terminal: &mut Terminal,
+    event: Event,
+    completer: &mut dyn Completer,
+  ) -> Option<Result<Action, io::Error>> {
     match event {
       Event::Key(key) => {
-        return Handler::handle_keypress(terminal, key);
+        Handler::handle_keypress(terminal, key, completer)
       }
       _ => None,
     }
   }
-  fn handle_keypress(terminal: &mut Terminal, key: Key) -> Option<Result<Action, io::Error>> {
+  // Inserts pasted text as it is, newlines included, so nothing runs
+  // before Enter. A trailing newline is dropped for the same reason.
+  pub fn handle_paste(terminal: &mut Terminal, text: &str) {
+    if terminal.search.is_some() {
+      terminal.finish_search(true);
+    }
+    if terminal.menu.is_some() {
+      terminal.close_menu(true, false);
+    }
+    // in vi normal mode the paste goes in as if typed after `i`
+    if let Some(vi) = terminal.vi.as_mut() {
+      if vi.mode == Mode::Normal {
+        vi.cancel();
+        let before = (terminal.line(), terminal.pos);
+        Handler::handle_vi(terminal, 'i', before);
+      }
+    }
+    let text = text.trim_end_matches('\n');
+    let before = (terminal.line(), terminal.pos);
+    terminal.push(text);
+    if let Some(vi) = terminal.vi.as_mut() {
+      if vi.mode == Mode::Insert {
+        vi.inserted.push_str(text);
+      }
+    }
+    if terminal.line() != before.0 {
+      terminal.undo.record(before, false);
+    }
+  }
+  fn handle_keypress(
+    terminal: &mut Terminal,
+    key: Key,
+    completer: &mut dyn Completer,
+  ) -> Option<Result<Action, io::Error>> {
     let mut buf = [0u8; 10];
+    let tabbed = std::mem::replace(&mut terminal.tabbed, false);
+    let ctrl_x = std::mem::replace(&mut terminal.ctrl_x, false);
+    if terminal.search.is_some() && Handler::handle_search(terminal, key) {
+      return None;
+    }
+    if terminal.menu.is_some() && Handler::handle_menu(terminal, key) {
+      return None;
+    }
+    let killed = std::mem::replace(&mut terminal.killed, false);
+    let yanked = terminal.yanked.take();
+    let before = (terminal.line(), terminal.pos);
+    if let Some(mode) = terminal.vi.as_ref().map(|vi| vi.mode) {
+      match (mode, key) {
+        (Mode::Normal, Key::Char(c)) if c != '\n' => return Handler::handle_vi(terminal, c, before),
+        (Mode::Normal, Key::Ctrl('r')) => {
+          terminal.redo();
+          Handler::clamp(terminal);
+          return None;
+        }
+        // Esc followed quickly by a command arrives as one Alt key
+        (_, Key::Alt(c)) => {
+          terminal.vi_escape();
+          return Handler::handle_vi(terminal, c, before);
+        }
+        (_, Key::Esc) => terminal.vi_escape(),
+        (Mode::Insert, Key::Char(c)) if c != '\n' && c != '\t' => {
+          if let Some(vi) = terminal.vi.as_mut() {
+            vi.inserted.push(c);
+          }
+        }
+        (Mode::Insert, Key::Backspace) => {
+          if let Some(vi) = terminal.vi.as_mut() {
+            vi.inserted.pop();
+          }
+        }
+        _ => {}
+      }
+    }
     match key {
       Key::Ctrl('d') => {
         terminal.reset();
-        terminal.history.clear_buffer();
+        terminal.history.borrow_mut().clear_buffer();
         return Some(Ok(Action::Exit));
       }
       Key::Delete => {
@@ -69,72 +164,277 @@ impl Handler {
       Key::Right | Key::Ctrl('f') => {
         terminal.move_right(1);
       }
-      Key::Up | Key::Ctrl('p') => {
-        if terminal.history.is_end() {
-          terminal.set_buffer();
+      Key::Up | Key::Ctrl('p') => Handler::history(terminal, true),
+      Key::Down | Key::Ctrl('n') => Handler::history(terminal, false),
+      // an unclosed quote, trailing `\` or open compound command goes on
+      // to the next row instead of running
+      Key::Char('\n') | Key::Ctrl('j') | Key::Ctrl('m') => match is_complete(&terminal.line()) {
+        true => return Handler::accept(terminal),
+        false => {
+          terminal.move_to_end();
+          terminal.push("\n");
         }
-        let cmd = match terminal.history.prev() {
-          Some(c) => c.clone(),
-          None => {
-            return None;
-          }
-        };
-        terminal.update_buffer(&cmd);
-        terminal.move_to(0);
-        terminal.clear_to_screen_end();
-        terminal.write_buffer();
-        terminal.move_to_end();
+      },
+      Key::Char('\t') => terminal.complete(completer, tabbed),
+      // Ctrl-_ arrives as Ctrl-7
+      Key::Ctrl('7') => {
+        terminal.undo();
+        return None;
       }
-      Key::Down | Key::Ctrl('n') => {
-        let cmd = match terminal.history.next() {
-          Some(c) => c.clone(),
-          None => {
-            return None;
-          }
-        };
-        if terminal.history.is_end() {
-          terminal.history.clear_buffer();
-        }
-        terminal.update_buffer(&cmd);
-        terminal.move_to(0);
-        terminal.clear_to_screen_end();
-        terminal.write_buffer();
-        terminal.move_to_end();
+      Key::Ctrl('u') if ctrl_x => {
+        terminal.undo();
+        return None;
       }
-      Key::Char('\n') | Key::Ctrl('j') | Key::Ctrl('m') => match terminal.get() {
-        Some(line) => {
-          terminal.history.push(line.clone());
-          terminal.history.clear_buffer();
-          terminal.reset();
-          terminal.write_linefeed();
-          terminal.write_line();
-          return Some(Ok(Action::from(line)));
-        }
-        None => {
-          terminal.history.clear_buffer();
-          terminal.reset();
-          terminal.write_linefeed();
-          terminal.write_line();
-          return Some(Ok(Action::Cancel));
-        }
-      },
+      Key::Ctrl('x') => terminal.ctrl_x = true,
       Key::Char(c) => {
         terminal.push(c.encode_utf8(&mut buf));
       }
       Key::Ctrl(c) => match c {
+        'r' => terminal.start_search(Direction::Backward),
+        's' => terminal.start_search(Direction::Forward),
         'l' => {
           terminal.clear_screen();
           terminal.write_buffer();
+          terminal.move_to(terminal.pos);
         }
+        'k' => terminal.kill_line_end(killed),
+        'u' => terminal.kill_line_start(killed),
+        'w' => terminal.kill_word_back(killed, true),
+        'y' => terminal.yank(),
+        't' => terminal.transpose_chars(),
         _ => {}
       },
-      Key::Esc | Key::Insert => {}
-      Key::Alt(_c) => {}
+      Key::Alt(c) => match c {
+        'b' => terminal.move_word_left(),
+        'f' => terminal.move_word_right(),
+        'd' => terminal.kill_word_forward(killed),
+        '\x7f' => terminal.kill_word_back(killed, false),
+        'y' => terminal.yank_pop(yanked),
+        't' => terminal.transpose_words(),
+        'u' | 'l' | 'c' => terminal.change_case(c),
+        _ => {}
+      },
+      Key::Esc | Key::Insert | Key::BackTab => {}
       Key::F(_f) => {}
-      _ => unreachable!(),
+      _ => {}
     };
+    Handler::record(terminal, key, before);
     None
   }
+  // Saves the line from before an edit for undo. Typed chars share one
+  // step until something else happens.
+  fn record(terminal: &mut Terminal, key: Key, before: (String, usize)) {
+    if terminal.line() != before.0 {
+      let typed = matches!(key, Key::Char(c) if c != '\n' && c != '\t');
+      terminal.undo.record(before, typed);
+    } else if terminal.pos != before.1 {
+      terminal.undo.split();
+    }
+  }
+  // Keeps the vi normal-mode cursor on a char of the line.
+  fn clamp(terminal: &mut Terminal) {
+    let len = terminal.len();
+    if terminal.pos >= len {
+      terminal.move_to(len.saturating_sub(1));
+    }
+  }
+  fn accept(terminal: &mut Terminal) -> Option<Result<Action, io::Error>> {
+    terminal.clear_match();
+    terminal.move_to_end();
+    match terminal.get() {
+      Some(line) => {
+        let commands: Vec<String> =
+          terminal.history.borrow().cmd_list.iter().map(|entry| entry.command.clone()).collect();
+        let expansion = expand_history(&commands, &line);
+        terminal.history.borrow_mut().clear_buffer();
+        terminal.reset();
+        terminal.write_linefeed();
+        terminal.write_line();
+        let expansion = match expansion {
+          Ok(expansion) => expansion,
+          Err(e) => {
+            terminal.write_str(&format!("rustsh: {}\r\n", e));
+            return Some(Ok(Action::Cancel));
+          }
+        };
+        if expansion.changed {
+          terminal.write_str(&format!("{}\r\n", expansion.line));
+        }
+        terminal.history.borrow_mut().push(expansion.line.clone());
+        if expansion.print_only {
+          return Some(Ok(Action::Cancel));
+        }
+        Some(Ok(Action::from(expansion.line)))
+      }
+      None => {
+        terminal.history.borrow_mut().clear_buffer();
+        terminal.reset();
+        terminal.write_linefeed();
+        terminal.write_line();
+        Some(Ok(Action::Cancel))
+      }
+    }
+  }
+  // Moves between the rows of a multi-line buffer, then steps through
+  // history, by prefix when `set -o prefixsearch` is on.
+  fn history(terminal: &mut Terminal, backward: bool) {
+    if terminal.move_row(backward) {
+      return;
+    }
+    if backward && terminal.history.borrow().is_end() {
+      terminal.set_buffer();
+    }
+    let prefix = terminal.search_prefix();
+    let cmd = match (&prefix, backward) {
+      (Some(prefix), true) => terminal.history.borrow_mut().prev_matching(prefix).cloned(),
+      (Some(prefix), false) => terminal.history.borrow_mut().next_matching(prefix).cloned(),
+      (None, true) => terminal.history.borrow_mut().prev().cloned(),
+      (None, false) => terminal.history.borrow_mut().next().cloned(),
+    };
+    let cmd = match cmd {
+      Some(c) => c,
+      None => return,
+    };
+    if !backward && terminal.history.borrow().is_end() {
+      terminal.history.borrow_mut().clear_buffer();
+    }
+    Handler::recall(terminal, &cmd, prefix);
+  }
+  // Runs a normal-mode key through the vi state machine.
+  fn handle_vi(terminal: &mut Terminal, c: char, before: (String, usize)) -> Option<Result<Action, io::Error>> {
+    let line = terminal.line();
+    let (mode, effect) = match terminal.vi.as_mut() {
+      Some(vi) => (vi.mode, vi.feed(c, &line, terminal.pos)),
+      None => return None,
+    };
+    match effect {
+      Effect::Pending => {}
+      Effect::Edit(line, pos) => terminal.set_line(&line, pos),
+      Effect::Move(pos) => terminal.move_to(pos),
+      Effect::History(direction) => {
+        Handler::history(terminal, direction < 0);
+        Handler::clamp(terminal);
+      }
+      Effect::Undo => {
+        terminal.undo();
+        Handler::clamp(terminal);
+        return None;
+      }
+      Effect::Editor => return Handler::edit(terminal),
+      Effect::Bell => terminal.write_str("\x07"),
+    }
+    let entered = terminal.vi.as_ref().is_some_and(|vi| vi.mode != mode);
+    if entered {
+      terminal.write_cursor_shape();
+    }
+    if terminal.line() != before.0 {
+      terminal.undo.record(before, false);
+      // the text a change goes on to insert undoes along with it
+      if entered {
+        terminal.undo.join_next();
+      }
+    } else if terminal.pos != before.1 {
+      terminal.undo.split();
+    }
+    None
+  }
+  // Opens the line in $VISUAL or $EDITOR and runs what the editor saves.
+  fn edit(terminal: &mut Terminal) -> Option<Result<Action, io::Error>> {
+    let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+    terminal.write_str("\x1b[0 q\r\n");
+    if let Err(e) = terminal.display() {
+      return Some(Err(e));
+    }
+    let edited = TempFile::create("edit", ".sh", &format!("{}\n", terminal.line())).and_then(|file| {
+      let status = reader::cooked(|| {
+        process::Command::new("sh")
+          .arg("-c")
+          .arg(format!("{} \"$1\"", editor))
+          .arg("sh")
+          .arg(file.path())
+          .status()
+      })??;
+      match status.success() {
+        true => file.read(),
+        false => Ok(String::new()),
+      }
+    });
+    let line = match edited {
+      Ok(text) => text.trim_end_matches('\n').to_string(),
+      Err(e) => {
+        terminal.write_str(&format!("rustsh: {}: {}\r\n", editor, e));
+        String::new()
+      }
+    };
+    terminal.update_buffer(&line);
+    terminal.pos = terminal.len();
+    terminal.write_prompt();
+    terminal.write_str(&line.replace('\n', "\r\n"));
+    Handler::accept(terminal)
+  }
+  // Shows a history entry, keeping the cursor after the prefix when Up
+  // and Down are searching by prefix.
+  fn recall(terminal: &mut Terminal, cmd: &str, prefix: Option<String>) {
+    terminal.update_buffer(cmd);
+    terminal.move_to(0);
+    terminal.clear_to_screen_end();
+    terminal.write_buffer();
+    match prefix {
+      Some(prefix) => terminal.move_to(terminal.index_of(prefix.chars().count())),
+      None => terminal.move_to_end(),
+    }
+  }
+  // Like `handle_search`: keys that do not drive the menu accept the
+  // selection and are then handled as usual.
+  fn handle_menu(terminal: &mut Terminal, key: Key) -> bool {
+    let width = terminal.window_size.ws_col as usize;
+    let menu = match terminal.menu.as_mut() {
+      Some(menu) => menu,
+      None => return false,
+    };
+    match key {
+      Key::Char('\t') | Key::Down | Key::Ctrl('n') => menu.step(1),
+      Key::BackTab | Key::Up | Key::Ctrl('p') => menu.step(-1),
+      Key::Right => menu.step_column(width, 1),
+      Key::Left => menu.step_column(width, -1),
+      Key::Char('\n') | Key::Ctrl('j') | Key::Ctrl('m') => {
+        terminal.close_menu(true, true);
+        return true;
+      }
+      Key::Esc | Key::Ctrl('g') => {
+        terminal.close_menu(false, false);
+        return true;
+      }
+      _ => {
+        terminal.close_menu(true, false);
+        return false;
+      }
+    }
+    terminal.render_menu();
+    true
+  }
+  // Returns false when the key ends the search and should then be handled
+  // as usual, so Enter runs the match and arrows start editing it.
+  fn handle_search(terminal: &mut Terminal, key: Key) -> bool {
+    match key {
+      Key::Ctrl('r') => terminal.update_search(None, Direction::Backward, true),
+      Key::Ctrl('s') => terminal.update_search(None, Direction::Forward, true),
+      Key::Ctrl('g') => terminal.finish_search(false),
+      Key::Backspace => {
+        let direction = terminal.search.as_ref().map_or(Direction::Backward, |search| search.direction);
+        terminal.update_search(Some(None), direction, false);
+      }
+      Key::Char(c) if c != '\n' && !c.is_control() => {
+        let direction = terminal.search.as_ref().map_or(Direction::Backward, |search| search.direction);
+        terminal.update_search(Some(Some(c)), direction, false);
+      }
+      _ => {
+        terminal.finish_search(true);
+        return false;
+      }
+    }
+    true
+  }
 }
 
 impl Handler {
@@ -145,3 +445,24 @@ impl Handler {
     lock.flush()
   }
 }
+
+#[cfg(test)]
+pub mod handler_test {
+  use super::*;
+  use crate::terminal::vi::Vi;
+  #[test]
+  fn vi_paste_test() {
+    let mut terminal = Terminal::new();
+    terminal.vi = Some(Vi::new());
+    terminal.set_line("ab", 2);
+    terminal.vi_escape();
+    assert_eq!(terminal.pos, 1);
+    Handler::handle_paste(&mut terminal, "X\nY\n");
+    assert_eq!(terminal.line(), "aX\nYb");
+    let vi = terminal.vi.as_ref().unwrap();
+    assert_eq!(vi.mode, Mode::Insert);
+    assert_eq!(vi.inserted, "X\nY");
+    terminal.undo();
+    assert_eq!(terminal.line(), "ab");
+  }
+}
@@ -1,31 +1,154 @@
 use super::buffer::Buffer;
+use nix::fcntl::{flock, FlockArg};
 use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entry {
+  pub command: String,
+  // seconds since the epoch
+  pub start: Option<u64>,
+  // milliseconds
+  pub duration: Option<u64>,
+  pub cwd: Option<String>,
+  pub status: Option<i32>,
+  pub session: Option<u32>,
+}
+impl Entry {
+  pub fn new(command: String) -> Entry {
+    let start = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|time| time.as_secs())
+      .ok();
+    Entry {
+      command,
+      start,
+      ..Entry::default()
+    }
+  }
+  fn has_metadata(&self) -> bool {
+    self.start.is_some()
+      || self.duration.is_some()
+      || self.cwd.is_some()
+      || self.status.is_some()
+      || self.session.is_some()
+  }
+}
+
+// What `History::push` keeps, configured from $HISTCONTROL and
+// $HISTIGNORE. Consecutive duplicates are always dropped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+  pub ignorespace: bool,
+  pub erasedups: bool,
+  pub ignore: Vec<String>,
+}
+impl Policy {
+  pub fn new(histcontrol: Option<&str>, histignore: Option<&str>) -> Policy {
+    let mut policy = Policy::default();
+    for option in histcontrol.unwrap_or("").split(':') {
+      match option {
+        "ignorespace" | "ignoreboth" => policy.ignorespace = true,
+        "erasedups" => policy.erasedups = true,
+        _ => {}
+      }
+    }
+    let mut pattern = String::new();
+    let mut chars = histignore.unwrap_or("").chars().peekable();
+    while let Some(c) = chars.next() {
+      match c {
+        '\\' if chars.peek() == Some(&':') => pattern.push(chars.next().unwrap()),
+        ':' => policy.ignore.push(std::mem::take(&mut pattern)),
+        _ => pattern.push(c),
+      }
+    }
+    policy.ignore.push(pattern);
+    policy.ignore.retain(|pattern| !pattern.is_empty());
+    policy
+  }
+  // `&` in $HISTIGNORE stands for the previous entry.
+  pub fn ignores(&self, command: &str, previous: Option<&str>) -> bool {
+    if self.ignorespace && command.starts_with(' ') {
+      return true;
+    }
+    self.ignore.iter().any(|pattern| {
+      let pattern = match previous {
+        Some(previous) => pattern.replace('&', previous),
+        None => pattern.clone(),
+      };
+      pattern_match(&pattern, command)
+    })
+  }
+}
+
+fn pattern_match(pattern: &str, text: &str) -> bool {
+  match (CString::new(pattern), CString::new(text)) {
+    (Ok(pattern), Ok(text)) => unsafe { libc::fnmatch(pattern.as_ptr(), text.as_ptr(), 0) == 0 },
+    _ => false,
+  }
+}
 
 #[derive(Debug)]
 pub struct History {
   buffer: Option<String>,
-  pub cmd_list: VecDeque<String>,
+  pub cmd_list: VecDeque<Entry>,
   pub pos: usize,
+  // entries before `saved` are already in the history file, which we have
+  // read up to byte `offset`
+  saved: usize,
+  offset: u64,
+  size: usize,
+  pub policy: Policy,
+  // Up/Down only visit entries starting with the text before the cursor
+  pub prefix_search: bool,
+}
+impl Default for History {
+  fn default() -> Self {
+    Self::new()
+  }
 }
-impl<'a> History {
+impl History {
   pub fn new() -> History {
     History {
       cmd_list: VecDeque::new(),
       buffer: None,
       pos: 0,
+      saved: 0,
+      offset: 0,
+      size: DEFAULT_SIZE,
+      policy: Policy::default(),
+      prefix_search: false,
     }
   }
+  pub fn set_size(&mut self, size: usize) {
+    self.size = size;
+    self.trim();
+  }
+  fn trim(&mut self) {
+    while self.cmd_list.len() > self.size {
+      self.cmd_list.pop_front();
+      self.saved = self.saved.saturating_sub(1);
+    }
+    self.pos = self.cmd_list.len();
+  }
   pub fn is_end(&self) -> bool {
-    return self.pos == self.cmd_list.len();
+    self.pos == self.cmd_list.len()
   }
   pub fn is_start(&self) -> bool {
-    return self.pos == 0;
+    self.pos == 0
   }
   pub fn is_last(&self) -> bool {
-    return self.cmd_list.len() > 0 && self.pos == self.cmd_list.len() - 1;
+    !self.cmd_list.is_empty() && self.pos == self.cmd_list.len() - 1
   }
   pub fn set_buffer(&mut self, buffer: Buffer) {
     self.buffer = Some(buffer.as_str());
@@ -43,16 +166,18 @@ impl<'a> History {
     self.pos = 0;
     self.cmd_list = VecDeque::new();
     self.buffer = None;
+    self.saved = 0;
   }
   pub fn prev(&mut self) -> Option<&String> {
     match self.pos {
       _ if self.pos > 0 => {
         self.pos -= 1;
-        self.cmd_list.get(self.pos)
+        self.cmd_list.get(self.pos).map(|entry| &entry.command)
       }
       _ => None,
     }
   }
+  #[allow(clippy::should_implement_trait)]
   pub fn next(&mut self) -> Option<&String> {
     match self.pos {
       _ if self.is_last() => {
@@ -61,35 +186,316 @@ impl<'a> History {
       }
       _ if self.pos + 1 < self.cmd_list.len() => {
         self.pos += 1;
-        self.cmd_list.get(self.pos)
+        self.cmd_list.get(self.pos).map(|entry| &entry.command)
       }
       _ => None,
     }
   }
+  // Like `prev`, but skips entries that do not start with `prefix` or
+  // repeat the line on display.
+  pub fn prev_matching(&mut self, prefix: &str) -> Option<&String> {
+    let current = self.cmd_list.get(self.pos).map(|entry| entry.command.clone());
+    let idx = (0..self.pos).rev().find(|&idx| {
+      let command = &self.cmd_list[idx].command;
+      command.starts_with(prefix) && Some(command) != current.as_ref()
+    })?;
+    self.pos = idx;
+    self.cmd_list.get(idx).map(|entry| &entry.command)
+  }
+  pub fn next_matching(&mut self, prefix: &str) -> Option<&String> {
+    if self.is_end() {
+      return None;
+    }
+    let current = self.cmd_list.get(self.pos).map(|entry| entry.command.clone());
+    let found = (self.pos + 1..self.cmd_list.len()).find(|&idx| {
+      let command = &self.cmd_list[idx].command;
+      command.starts_with(prefix) && Some(command) != current.as_ref()
+    });
+    match found {
+      Some(idx) => {
+        self.pos = idx;
+        self.cmd_list.get(idx).map(|entry| &entry.command)
+      }
+      None => {
+        self.pos = self.cmd_list.len();
+        self.get_buffer()
+      }
+    }
+  }
   pub fn push(&mut self, cmd: String) {
+    let previous = self.cmd_list.back().map(|entry| entry.command.as_str());
+    if self.policy.ignores(&cmd, previous) {
+      self.pos = self.cmd_list.len();
+      return;
+    }
+    if self.policy.erasedups {
+      for idx in (0..self.cmd_list.len()).rev() {
+        if self.cmd_list[idx].command == cmd {
+          self.remove(idx);
+        }
+      }
+    }
     match self.cmd_list.back() {
-      Some(s) => {
-        if *s != cmd {
-          self.cmd_list.push_back(cmd);
+      Some(entry) => {
+        if entry.command != cmd {
+          self.cmd_list.push_back(Entry::new(cmd));
         }
       }
       None => {
-        self.cmd_list.push_back(cmd);
+        self.cmd_list.push_back(Entry::new(cmd));
+      }
+    };
+    self.trim();
+  }
+  // The newest entry while it has not been written to the file yet, so
+  // the caller can fill in how the command went.
+  pub fn pending_mut(&mut self) -> Option<&mut Entry> {
+    match self.cmd_list.len() > self.saved {
+      true => self.cmd_list.back_mut(),
+      false => None,
+    }
+  }
+
+  pub fn load(&mut self, path: &Path) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    flock(file.as_raw_fd(), FlockArg::LockShared).map_err(to_io)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    self.offset = content.len() as u64;
+    self.insert_saved(parse_entries(&content));
+    Ok(())
+  }
+
+  // Adds entries that already exist in a file ahead of the unsaved ones.
+  pub fn insert_saved(&mut self, entries: Vec<Entry>) {
+    let unsaved: Vec<Entry> = self.cmd_list.drain(self.saved..).collect();
+    self.cmd_list.extend(entries);
+    self.saved = self.cmd_list.len();
+    self.cmd_list.extend(unsaved);
+    self.trim();
+  }
+
+  // Records that the history file at `path` now holds every entry.
+  pub fn mark_saved(&mut self, path: &Path) -> io::Result<()> {
+    self.saved = self.cmd_list.len();
+    self.offset = path.metadata()?.len();
+    Ok(())
+  }
+
+  pub fn remove(&mut self, index: usize) -> Option<Entry> {
+    let entry = self.cmd_list.remove(index)?;
+    if index < self.saved {
+      self.saved -= 1;
+    }
+    self.pos = self.cmd_list.len();
+    Some(entry)
+  }
+
+  pub fn unsaved(&self) -> Vec<Entry> {
+    self.cmd_list.range(self.saved..).cloned().collect()
+  }
+
+  // Picks up entries other sessions appended since we last touched the
+  // file. They are placed before our own unsaved entries.
+  pub fn merge(&mut self, path: &Path) -> io::Result<()> {
+    let mut file = match File::open(path) {
+      Ok(file) => file,
+      Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+      Err(e) => return Err(e),
+    };
+    flock(file.as_raw_fd(), FlockArg::LockShared).map_err(to_io)?;
+    self.read_new(&mut file)
+  }
+
+  fn read_new(&mut self, file: &mut File) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    if len < self.offset {
+      // the file was truncated or replaced; everything in it is known
+      self.offset = len;
+      return Ok(());
+    }
+    file.seek(SeekFrom::Start(self.offset))?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    self.offset = len;
+    for entry in parse_entries(&content) {
+      self.cmd_list.insert(self.saved, entry);
+      self.saved += 1;
+    }
+    self.trim();
+    Ok(())
+  }
+
+  pub fn append(&mut self, path: &Path, merge: bool) -> io::Result<()> {
+    if self.saved >= self.cmd_list.len() {
+      return Ok(());
+    }
+    let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+    flock(file.as_raw_fd(), FlockArg::LockExclusive).map_err(to_io)?;
+    if merge {
+      self.read_new(&mut file)?;
+    }
+    let mut content = String::new();
+    for entry in self.cmd_list.range(self.saved..) {
+      content.push_str(&format_entry(entry));
+    }
+    file.write_all(content.as_bytes())?;
+    file.flush()?;
+    self.saved = self.cmd_list.len();
+    self.offset = file.metadata()?.len();
+    Ok(())
+  }
+
+  // Keeps only the newest `limit` entries in the file.
+  pub fn truncate_file(&mut self, path: &Path, limit: usize) -> io::Result<()> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+      Ok(file) => file,
+      Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+      Err(e) => return Err(e),
+    };
+    flock(file.as_raw_fd(), FlockArg::LockExclusive).map_err(to_io)?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    let entries = parse_entries(&content);
+    if entries.len() <= limit {
+      return Ok(());
+    }
+    let content: String = entries[entries.len() - limit..]
+      .iter()
+      .map(format_entry)
+      .collect();
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(content.as_bytes())?;
+    self.offset = content.len() as u64;
+    Ok(())
+  }
+}
+
+pub fn read_entries(path: &Path) -> io::Result<Vec<Entry>> {
+  let mut file = File::open(path)?;
+  flock(file.as_raw_fd(), FlockArg::LockShared).map_err(to_io)?;
+  let mut content = String::new();
+  file.read_to_string(&mut content)?;
+  Ok(parse_entries(&content))
+}
+
+pub fn write_entries<'a, I: IntoIterator<Item = &'a Entry>>(path: &Path, entries: I, append: bool) -> io::Result<()> {
+  let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+  flock(file.as_raw_fd(), FlockArg::LockExclusive).map_err(to_io)?;
+  if !append {
+    file.set_len(0)?;
+  }
+  let content: String = entries.into_iter().map(format_entry).collect();
+  file.write_all(content.as_bytes())
+}
+
+fn to_io(error: nix::Error) -> io::Error {
+  io::Error::other(error.to_string())
+}
+
+// One command per line; lines of a multi-line command end in a backslash,
+// and backslashes a line itself ends in are doubled so they do not read
+// back as one. Metadata goes on a preceding `#:` comment line holding
+// tab-separated start, duration, status, session and cwd, so the file
+// still reads as a plain list of commands.
+fn format_entry(entry: &Entry) -> String {
+  let mut result = String::new();
+  if entry.has_metadata() {
+    let field = |value: Option<String>| value.unwrap_or_default();
+    result.push_str(&format!(
+      "#:{}\t{}\t{}\t{}\t{}\n",
+      field(entry.start.map(|v| v.to_string())),
+      field(entry.duration.map(|v| v.to_string())),
+      field(entry.status.map(|v| v.to_string())),
+      field(entry.session.map(|v| v.to_string())),
+      field(entry.cwd.clone()),
+    ));
+  }
+  if looks_like_metadata(&entry.command) {
+    result.push('\\');
+  }
+  let lines: Vec<String> = entry.command.split('\n').map(escape_line).collect();
+  result.push_str(&lines.join("\\\n"));
+  result.push('\n');
+  result
+}
+// A command such as `#: note` would read back as a metadata line, so it
+// is written with one more leading backslash, which parsing takes off.
+fn looks_like_metadata(line: &str) -> bool {
+  line.trim_start_matches('\\').starts_with("#:")
+}
+
+fn escape_line(line: &str) -> String {
+  let trailing = line.len() - line.trim_end_matches('\\').len();
+  format!("{}{}", line, "\\".repeat(trailing))
+}
+// Undoes `escape_line`; an odd backslash left over marks a line that
+// continues on the next.
+fn unescape_line(line: &str) -> (&str, bool) {
+  let trailing = line.len() - line.trim_end_matches('\\').len();
+  (&line[..line.len() - trailing + trailing / 2], trailing % 2 == 1)
+}
+
+fn parse_metadata(line: &str) -> Entry {
+  let mut fields = line.splitn(5, '\t');
+  let mut next = || fields.next().filter(|field| !field.is_empty());
+  Entry {
+    command: String::new(),
+    start: next().and_then(|v| v.parse().ok()),
+    duration: next().and_then(|v| v.parse().ok()),
+    status: next().and_then(|v| v.parse().ok()),
+    session: next().and_then(|v| v.parse().ok()),
+    cwd: next().map(String::from),
+  }
+}
+
+fn parse_entries(content: &str) -> Vec<Entry> {
+  let mut entries = Vec::new();
+  let mut metadata: Option<Entry> = None;
+  let mut pending: Option<String> = None;
+  for line in content.lines() {
+    let line = match (&pending, line.strip_prefix("#:")) {
+      (None, Some(fields)) => {
+        metadata = Some(parse_metadata(fields));
+        continue;
+      }
+      (None, None) if line.starts_with('\\') && looks_like_metadata(line) => &line[1..],
+      _ => line,
+    };
+    let (text, continued) = unescape_line(line);
+    let mut command = match pending.take() {
+      Some(mut command) => {
+        command.push('\n');
+        command
       }
+      None => String::new(),
     };
-    self.pos = self.cmd_list.len()
+    command.push_str(text);
+    if continued {
+      pending = Some(command);
+    } else if !command.is_empty() {
+      let entry = metadata.take().unwrap_or_default();
+      entries.push(Entry { command, ..entry });
+    }
   }
+  if let Some(command) = pending {
+    let entry = metadata.take().unwrap_or_default();
+    entries.push(Entry { command, ..entry });
+  }
+  entries
 }
 
 #[cfg(test)]
 pub mod history_test {
-  use super::History;
+  use super::*;
+  use crate::testing::TempDir;
   #[test]
   fn push_test() {
     let mut history = History::new();
     let string: String = "awdadad awd81274387  a ii!@!#".into();
     history.push(string.clone());
-    assert_eq!(history.cmd_list.back(), Some(&string));
+    assert_eq!(history.cmd_list.back().map(|entry| &entry.command), Some(&string));
   }
   #[test]
   fn duplicate_push_test() {
@@ -100,6 +506,132 @@ pub mod history_test {
     assert_eq!(history.cmd_list.len(), 1);
   }
   #[test]
+  fn policy_test() {
+    let mut history = History::new();
+    history.policy = Policy::new(Some("ignoreboth:erasedups"), Some("ls:cd *:&"));
+    for command in &["make", " secret", "ls", "cd /tmp", "git status", "make", "make", "lsof"] {
+      history.push(command.to_string());
+    }
+    let commands: Vec<&str> = history.cmd_list.iter().map(|entry| entry.command.as_str()).collect();
+    assert_eq!(commands, vec!["git status", "make", "lsof"]);
+    assert_eq!(Policy::new(None, Some("a\\:b")).ignore, vec!["a:b".to_string()]);
+  }
+  #[test]
+  fn prefix_test() {
+    let mut history = History::new();
+    for command in &["git log", "make", "git status", "git status", "ls"] {
+      history.push(command.to_string());
+    }
+    history.set_buffer(Buffer::from("git"));
+    assert_eq!(history.prev_matching("git").cloned(), Some("git status".to_string()));
+    assert_eq!(history.prev_matching("git").cloned(), Some("git log".to_string()));
+    assert_eq!(history.prev_matching("git"), None);
+    assert_eq!(history.next_matching("git").cloned(), Some("git status".to_string()));
+    assert_eq!(history.next_matching("git").cloned(), Some("git".to_string()));
+    assert!(history.is_end());
+  }
+  #[test]
+  fn size_test() {
+    let mut history = History::new();
+    history.set_size(2);
+    history.push("A".to_string());
+    history.push("B".to_string());
+    history.push("C".to_string());
+    let commands: Vec<&str> = history.cmd_list.iter().map(|entry| entry.command.as_str()).collect();
+    assert_eq!(commands, vec!["B", "C"]);
+  }
+  #[test]
+  fn file_test() {
+    let dir = TempDir::new("history");
+    let path = dir.join("history");
+    let mut first = History::new();
+    let mut second = History::new();
+    first.push("echo one".to_string());
+    first.push("f() {\necho two\n}".to_string());
+    first.append(&path, false).unwrap();
+    second.load(&path).unwrap();
+    assert_eq!(second.cmd_list, first.cmd_list);
+    first.push("echo three".to_string());
+    first.append(&path, false).unwrap();
+    second.push("echo four".to_string());
+    second.merge(&path).unwrap();
+    assert_eq!(second.cmd_list.len(), 4);
+    assert_eq!(second.cmd_list[2].command, "echo three");
+    second.append(&path, true).unwrap();
+    second.truncate_file(&path, 2).unwrap();
+    let mut third = History::new();
+    third.load(&path).unwrap();
+    let commands: Vec<&str> = third.cmd_list.iter().map(|entry| entry.command.as_str()).collect();
+    assert_eq!(commands, vec!["echo three", "echo four"]);
+  }
+  #[test]
+  fn metadata_test() {
+    let entry = Entry {
+      command: "make\ntest".to_string(),
+      start: Some(1_700_000_000),
+      duration: Some(1500),
+      cwd: Some("/src/my repo".to_string()),
+      status: Some(2),
+      session: Some(42),
+    };
+    let content = format!("echo plain\n{}", format_entry(&entry));
+    assert_eq!(content, "echo plain\n#:1700000000\t1500\t2\t42\t/src/my repo\nmake\\\ntest\n");
+    let entries = parse_entries(&content);
+    assert_eq!(entries[0], Entry { command: "echo plain".to_string(), ..Entry::default() });
+    assert_eq!(entries[1], entry);
+  }
+  #[test]
+  fn metadata_like_command_test() {
+    let commands = ["#: note", "#:foo", "\\#:bar", "echo #:baz"];
+    let mut content = String::new();
+    for command in commands.iter() {
+      let entry = Entry {
+        command: command.to_string(),
+        status: Some(0),
+        ..Entry::default()
+      };
+      content.push_str(&format_entry(&entry));
+    }
+    assert!(content.contains("\n\\#:foo\n") && content.contains("\n\\\\#:bar\n"));
+    let entries = parse_entries(&content);
+    let parsed: Vec<&str> = entries.iter().map(|entry| entry.command.as_str()).collect();
+    assert_eq!(parsed, commands);
+    assert!(entries.iter().all(|entry| entry.status == Some(0)));
+  }
+  #[test]
+  fn trailing_backslash_test() {
+    let commands = ["echo \\", "printf '%s\\n' a\\", "a\\\\\nb\\", "echo after"];
+    let mut content = String::new();
+    for command in commands.iter() {
+      let entry = Entry {
+        command: command.to_string(),
+        status: Some(0),
+        ..Entry::default()
+      };
+      content.push_str(&format_entry(&entry));
+    }
+    let entries = parse_entries(&content);
+    let parsed: Vec<&str> = entries.iter().map(|entry| entry.command.as_str()).collect();
+    assert_eq!(parsed, commands);
+    assert!(entries.iter().all(|entry| entry.status == Some(0)));
+    assert_eq!(parse_entries("echo a\\\nb\n")[0].command, "echo a\nb");
+  }
+  #[test]
+  fn merge_metadata_like_test() {
+    let dir = TempDir::new("history-merge");
+    let path = dir.join("history");
+    let mut first = History::new();
+    let mut second = History::new();
+    first.push("#:foo".to_string());
+    first.push("echo after".to_string());
+    first.append(&path, true).unwrap();
+    second.push("echo mine".to_string());
+    second.merge(&path).unwrap();
+    let commands: Vec<&str> = second.cmd_list.iter().map(|entry| entry.command.as_str()).collect();
+    assert_eq!(commands, vec!["#:foo", "echo after", "echo mine"]);
+    assert_eq!(second.cmd_list[1], first.cmd_list[1]);
+  }
+  #[test]
   fn history_test() {
     let mut history = History::new();
     history.push("A".to_string());
@@ -0,0 +1,64 @@
+use std::collections::VecDeque;
+
+const SIZE: usize = 32;
+
+// Text removed by the kill commands, newest first. Consecutive kills grow
+// the newest entry instead of adding new ones.
+#[derive(Debug, Default)]
+pub struct KillRing {
+  entries: VecDeque<String>,
+  index: usize,
+}
+
+impl KillRing {
+  pub fn new() -> KillRing {
+    KillRing::default()
+  }
+
+  // `append` joins the previous kill; `backward` kills go in front of it.
+  pub fn kill(&mut self, text: &str, append: bool, backward: bool) {
+    if text.is_empty() {
+      return;
+    }
+    match (append, self.entries.front_mut()) {
+      (true, Some(last)) if backward => last.insert_str(0, text),
+      (true, Some(last)) => last.push_str(text),
+      _ => {
+        self.entries.push_front(text.to_string());
+        self.entries.truncate(SIZE);
+      }
+    }
+    self.index = 0;
+  }
+
+  pub fn yank(&mut self) -> Option<&String> {
+    self.index = 0;
+    self.entries.front()
+  }
+
+  // The next older entry, wrapping around; used by yank-pop.
+  pub fn rotate(&mut self) -> Option<&String> {
+    if self.entries.is_empty() {
+      return None;
+    }
+    self.index = (self.index + 1) % self.entries.len();
+    self.entries.get(self.index)
+  }
+}
+
+#[cfg(test)]
+pub mod kill_test {
+  use super::*;
+  #[test]
+  fn kill_test() {
+    let mut ring = KillRing::new();
+    ring.kill("world", false, false);
+    ring.kill("!", true, false);
+    ring.kill("hello ", true, true);
+    assert_eq!(ring.yank().cloned(), Some("hello world!".to_string()));
+    ring.kill("other", false, false);
+    assert_eq!(ring.yank().cloned(), Some("other".to_string()));
+    assert_eq!(ring.rotate().cloned(), Some("hello world!".to_string()));
+    assert_eq!(ring.rotate().cloned(), Some("other".to_string()));
+  }
+}
@@ -0,0 +1,191 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+// Where the text of the line lands on screen. Rows count from the row
+// the prompt ends on and columns from the left edge; text starts after
+// the prompt, wraps at `width` and moves to a new row after each newline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+  pub start: usize,
+  pub width: usize,
+}
+
+// Columns the last line of the prompt takes, leaving out escape
+// sequences such as colors.
+pub fn prompt_width(prompt: &str) -> usize {
+  let last = prompt.rsplit('\n').next().unwrap_or("");
+  let mut width = 0;
+  let mut chars = last.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\x1b' => {
+        if chars.peek() == Some(&'[') {
+          chars.next();
+          for c in chars.by_ref() {
+            if ('@'..='~').contains(&c) {
+              break;
+            }
+          }
+        } else {
+          chars.next();
+        }
+      }
+      '\r' => width = 0,
+      c if c.is_control() => {}
+      c => width += c.width().unwrap_or(0),
+    }
+  }
+  width
+}
+
+// Columns a grapheme takes: two for East Asian wide and emoji clusters,
+// none for a newline, otherwise one. Combining marks add nothing to
+// the char they follow.
+pub fn width(grapheme: &str) -> usize {
+  let first = match grapheme.chars().next() {
+    Some('\n') | None => return 0,
+    Some(c) => c,
+  };
+  // a variation selector asks for the emoji presentation
+  if grapheme.contains('\u{fe0f}') {
+    return 2;
+  }
+  first.width().unwrap_or(0).clamp(1, 2)
+}
+
+// Columns `text` takes when it fits on one row.
+pub fn text_width(text: &str) -> usize {
+  text.graphemes(true).map(width).sum()
+}
+
+// The longest start of `text` that fits in `columns`.
+pub fn clip(text: &str, columns: usize) -> &str {
+  let mut used = 0;
+  for (idx, grapheme) in text.grapheme_indices(true) {
+    used += width(grapheme);
+    if used > columns {
+      return &text[..idx];
+    }
+  }
+  text
+}
+
+impl Layout {
+  pub fn new(prompt: &str, width: usize) -> Layout {
+    let width = width.max(1);
+    Layout {
+      start: prompt_width(prompt) % width,
+      width,
+    }
+  }
+
+  // Row and column after the first `n` graphemes. A row filled up to the
+  // last column leaves the cursor at the start of the next one.
+  pub fn position<S: AsRef<str>>(&self, graphemes: &[S], n: usize) -> (usize, usize) {
+    let (row, col) = self.walk(graphemes, n);
+    match col >= self.width {
+      true => (row + 1, 0),
+      false => (row, col),
+    }
+  }
+
+  // Like `position`, but a full row keeps the column past its end the
+  // way terminals hold off wrapping until the next char is written.
+  fn walk<S: AsRef<str>>(&self, graphemes: &[S], n: usize) -> (usize, usize) {
+    let (mut row, mut col) = (0, self.start);
+    for grapheme in graphemes.iter().take(n) {
+      let grapheme = grapheme.as_ref();
+      if grapheme == "\n" {
+        row += 1;
+        col = 0;
+        continue;
+      }
+      let width = width(grapheme);
+      if col + width > self.width {
+        row += 1;
+        col = 0;
+      }
+      col += width;
+    }
+    (row, col)
+  }
+
+  // The index whose position is on `row` and closest to `col` without
+  // passing it.
+  pub fn index_at<S: AsRef<str>>(&self, graphemes: &[S], row: usize, col: usize) -> usize {
+    let mut found = None;
+    for n in 0..=graphemes.len() {
+      let (r, c) = self.position(graphemes, n);
+      if r > row {
+        break;
+      }
+      if r == row && (c <= col || found.is_none()) {
+        found = Some(n);
+      }
+    }
+    found.unwrap_or(graphemes.len())
+  }
+}
+
+#[cfg(test)]
+pub mod layout_test {
+  use super::*;
+  fn graphemes(text: &str) -> Vec<&str> {
+    text.graphemes(true).collect()
+  }
+  #[test]
+  fn prompt_width_test() {
+    assert_eq!(prompt_width("$ "), 2);
+    assert_eq!(prompt_width("\x1b[32muser\x1b[0m $ "), 7);
+    assert_eq!(prompt_width("first line\n> "), 2);
+    assert_eq!(prompt_width("日本 $ "), 7);
+  }
+  #[test]
+  fn width_test() {
+    assert_eq!(width("a"), 1);
+    assert_eq!(width("e\u{301}"), 1);
+    assert_eq!(width("日"), 2);
+    assert_eq!(width("👍🏽"), 2);
+    assert_eq!(width("❤\u{fe0f}"), 2);
+    assert_eq!(width("\n"), 0);
+  }
+  #[test]
+  fn text_width_test() {
+    assert_eq!(text_width("ab日本e\u{301}"), 7);
+    assert_eq!(clip("ab日本", 3), "ab");
+    assert_eq!(clip("ab日本", 4), "ab日");
+    assert_eq!(clip("ab", 5), "ab");
+  }
+  #[test]
+  fn wide_wrap_test() {
+    let layout = Layout::new("$ ", 6);
+    let text = graphemes("ab日本");
+    assert_eq!(layout.position(&text, 3), (1, 0));
+    assert_eq!(layout.position(&text, 4), (1, 2));
+  }
+  #[test]
+  fn position_test() {
+    let layout = Layout::new("$ ", 10);
+    let text = graphemes("echo 12345678\nls");
+    assert_eq!(layout.position(&text, 0), (0, 2));
+    assert_eq!(layout.position(&text, 7), (0, 9));
+    assert_eq!(layout.position(&text, 8), (1, 0));
+    assert_eq!(layout.position(&text, 9), (1, 1));
+    assert_eq!(layout.position(&text, 13), (1, 5));
+    assert_eq!(layout.position(&text, 14), (2, 0));
+    assert_eq!(layout.position(&text, 16), (2, 2));
+    let full = graphemes("12345678\nx");
+    assert_eq!(layout.position(&full, 8), (1, 0));
+    assert_eq!(layout.position(&full, 9), (1, 0));
+    assert_eq!(layout.position(&full, 10), (1, 1));
+  }
+  #[test]
+  fn index_at_test() {
+    let layout = Layout::new("$ ", 10);
+    let text = graphemes("echo 12345678\nls");
+    assert_eq!(layout.index_at(&text, 0, 5), 3);
+    assert_eq!(layout.index_at(&text, 1, 3), 11);
+    assert_eq!(layout.index_at(&text, 2, 9), 16);
+    assert_eq!(layout.index_at(&text, 0, 0), 0);
+  }
+}
@@ -0,0 +1,171 @@
+use super::complete::{open_quote, quote, Candidate, Context, Request};
+use super::layout::{clip, text_width};
+
+// Completion candidates shown in a grid below the prompt. Items fill
+// each column top to bottom.
+#[derive(Debug, Clone)]
+pub struct Menu {
+  pub request: Request,
+  pub candidates: Vec<Candidate>,
+  pub selected: usize,
+  // first grid row on screen
+  pub scroll: usize,
+}
+
+impl Menu {
+  pub fn new(request: Request, candidates: Vec<Candidate>) -> Menu {
+    Menu {
+      request,
+      candidates,
+      selected: 0,
+      scroll: 0,
+    }
+  }
+
+  pub fn cells(&self) -> Vec<String> {
+    let longest = self.candidates.iter().map(|c| text_width(&c.display)).max().unwrap_or(0);
+    self
+      .candidates
+      .iter()
+      .map(|candidate| match &candidate.description {
+        Some(description) => {
+          let pad = longest - text_width(&candidate.display);
+          format!("{}{}  -- {}", candidate.display, " ".repeat(pad), description)
+        }
+        None => candidate.display.clone(),
+      })
+      .collect()
+  }
+
+  // Rows and columns of the grid and the width of each column. A column
+  // never gets wider than the terminal; cells that do not fit are cut.
+  pub fn layout(&self, width: usize) -> (usize, usize, usize) {
+    let longest = self.cells().iter().map(|cell| text_width(cell)).max().unwrap_or(0);
+    let column = (longest + 2).min(width.max(1));
+    let columns = (width / column).max(1).min(self.candidates.len().max(1));
+    let rows = self.candidates.len().div_ceil(columns);
+    (rows, columns, column)
+  }
+
+  // Moves the selection by `delta` items, wrapping around.
+  pub fn step(&mut self, delta: isize) {
+    let len = self.candidates.len() as isize;
+    if len > 0 {
+      self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+    }
+  }
+
+  // Moves across columns, staying on the same row where possible.
+  pub fn step_column(&mut self, width: usize, delta: isize) {
+    let (rows, _, _) = self.layout(width);
+    let target = self.selected as isize + delta * rows as isize;
+    if target >= 0 && (target as usize) < self.candidates.len() {
+      self.selected = target as usize;
+    }
+  }
+
+  // Keeps the selected row inside a window of `height` rows.
+  pub fn visible(&mut self, width: usize, height: usize) -> (usize, usize) {
+    let (rows, _, _) = self.layout(width);
+    let height = height.max(1).min(rows);
+    let row = self.selected % rows;
+    if row < self.scroll {
+      self.scroll = row;
+    } else if row >= self.scroll + height {
+      self.scroll = row + 1 - height;
+    }
+    (self.scroll, self.scroll + height)
+  }
+
+  // Grid lines for rows `start..end`, the selected cell in reverse video.
+  pub fn lines(&self, width: usize, start: usize, end: usize) -> Vec<String> {
+    let (rows, columns, column) = self.layout(width);
+    let cells = self.cells();
+    (start..end)
+      .map(|row| {
+        let mut line = String::new();
+        for col in 0..columns {
+          let idx = col * rows + row;
+          let cell = match cells.get(idx) {
+            Some(cell) => clip(cell, column),
+            None => break,
+          };
+          let pad = " ".repeat(column - text_width(cell));
+          match idx == self.selected {
+            true => line.push_str(&format!("\x1b[7m{}\x1b[0m{}", cell, pad)),
+            false => line.push_str(&format!("{}{}", cell, pad)),
+          }
+        }
+        line.trim_end().to_string()
+      })
+      .collect()
+  }
+
+  // The word as it reads with the selection in place; `finish` adds the
+  // closing quote and suffix.
+  pub fn insertion(&self, finish: bool) -> String {
+    let candidate = &self.candidates[self.selected];
+    let open = match self.request.context {
+      Context::Variable => return candidate.value.clone(),
+      _ => open_quote(&self.request.raw),
+    };
+    let mut text = quote(&candidate.value, open);
+    if let (true, Some(suffix)) = (finish, candidate.suffix) {
+      if let Some(open) = open {
+        text.push(open);
+      }
+      text.push(suffix);
+    }
+    text
+  }
+}
+
+#[cfg(test)]
+pub mod menu_test {
+  use super::*;
+  use crate::terminal::complete::request;
+  fn menu(names: &[&str]) -> Menu {
+    let candidates = names.iter().map(|name| Candidate::new(name)).collect();
+    Menu::new(request("ls ", 3), candidates)
+  }
+  #[test]
+  fn navigation_test() {
+    let mut menu = menu(&["a", "b", "c", "d", "e"]);
+    assert_eq!(menu.layout(6), (3, 2, 3));
+    menu.step(-1);
+    assert_eq!(menu.selected, 4);
+    menu.step(1);
+    assert_eq!(menu.selected, 0);
+    menu.step_column(6, 1);
+    assert_eq!(menu.selected, 3);
+    menu.step_column(6, 1);
+    assert_eq!(menu.selected, 3);
+  }
+  #[test]
+  fn scroll_test() {
+    let mut menu = menu(&["a", "b", "c", "d", "e"]);
+    assert_eq!(menu.visible(1, 2), (0, 2));
+    menu.selected = 3;
+    assert_eq!(menu.visible(1, 2), (2, 4));
+    menu.selected = 0;
+    assert_eq!(menu.visible(1, 2), (0, 2));
+  }
+  #[test]
+  fn lines_test() {
+    let mut menu = menu(&["a b", "c"]);
+    menu.candidates[1].description = Some("the c".to_string());
+    assert_eq!(menu.cells(), vec!["a b".to_string(), "c    -- the c".to_string()]);
+    assert_eq!(menu.lines(80, 0, 1), vec!["\x1b[7ma b\x1b[0m            c    -- the c".to_string()]);
+    assert_eq!(menu.insertion(false), "a\\ b");
+    assert_eq!(menu.insertion(true), "a\\ b ");
+  }
+  #[test]
+  fn width_test() {
+    let wide = menu(&["日本", "e\u{301}x", "abc"]);
+    assert_eq!(wide.layout(80), (1, 3, 6));
+    assert_eq!(wide.lines(80, 0, 1), vec!["\x1b[7m日本\x1b[0m  e\u{301}x    abc".to_string()]);
+    let long = menu(&["a-long-file-name", "b"]);
+    assert_eq!(long.layout(6), (2, 1, 6));
+    assert_eq!(long.lines(6, 0, 2), vec!["\x1b[7ma-long\x1b[0m".to_string(), "b".to_string()]);
+  }
+}
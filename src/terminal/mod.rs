@@ -1,9 +1,17 @@
 pub mod action;
 mod buffer;
+pub mod complete;
 mod cursor;
 mod handler;
-mod history;
+pub mod history;
+mod kill;
+mod layout;
+mod menu;
 pub mod reader;
+mod search;
 mod signal;
+#[allow(clippy::module_inception)]
 mod terminal;
+mod undo;
+mod vi;
 mod window;
@@ -1,39 +1,107 @@
 use super::action::Action;
+use super::complete::Completer;
 use super::handler::Handler;
+use super::history::History;
 use super::signal::{exit_signal_safe, prepare, take};
 use super::terminal::Terminal;
+use super::vi::Vi;
 use nix::sys::select::{select, FdSet};
 use nix::sys::signal::Signal;
-use std::io::{self, Read, Write};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Write};
 use std::os::unix::io::{AsRawFd, RawFd};
-use termion::async_stdin;
+use std::rc::Rc;
 use termion::event::{self, Event, Key};
 use termios;
 
+// Terminals wrap pasted text in these when bracketed paste is on.
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+enum Input {
+  Event(Event),
+  Paste(String),
+}
+
 #[derive(Debug)]
 pub struct Reader {
   saved_terminal: termios::Termios,
-  leftover: Option<u8>,
+  // bytes read from the terminal but not yet parsed into keys
+  pending: VecDeque<u8>,
   terminal: Terminal,
 }
-impl<'a> Drop for Reader {
+impl Drop for Reader {
   fn drop(&mut self) {
-    use termios::*;
-    let fd = io::stdout().as_raw_fd();
-    tcsetattr(fd, TCSAFLUSH, &mut self.saved_terminal).unwrap();
+    restore_terminal(&self.saved_terminal);
   }
 }
-impl<'a> Reader {
+impl Default for Reader {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+impl Reader {
   pub fn new() -> Reader {
     let saved_terminal = setup_terminal().unwrap();
     Reader {
-      leftover: None,
+      pending: VecDeque::new(),
       terminal: Terminal::new(),
-      saved_terminal: saved_terminal,
+      saved_terminal,
+    }
+  }
+
+  pub fn set_history(&mut self, history: Rc<RefCell<History>>) {
+    self.terminal.history = history;
+  }
+
+  pub fn set_prompt(&mut self, prompt: &str) {
+    self.terminal.prompt = prompt.to_string();
+  }
+
+  // Characters besides alphanumerics that Alt-B/Alt-F and friends
+  // treat as part of a word, like zsh's $WORDCHARS.
+  pub fn set_word_chars(&mut self, chars: &str) {
+    self.terminal.word_chars = chars.to_string();
+  }
+
+  // Switches between the emacs-style editor and modal vi editing.
+  pub fn set_vi_mode(&mut self, enabled: bool) {
+    match (enabled, self.terminal.vi.is_some()) {
+      (true, false) => self.terminal.vi = Some(Vi::new()),
+      (false, true) => self.terminal.vi = None,
+      _ => {}
     }
   }
 
-  pub fn read_line(&mut self) -> Result<Action, io::Error> {
+  // Colors commands, strings, variables and the like while typing.
+  pub fn set_highlight(&mut self, enabled: bool) {
+    self.terminal.highlight = enabled;
+  }
+
+  // Columns and rows of the terminal as of now.
+  pub fn window_size(&mut self) -> (usize, usize) {
+    self.terminal.update_size();
+    let size = &self.terminal.window_size;
+    (size.ws_col as usize, size.ws_row as usize)
+  }
+
+  pub fn read_line(&mut self, completer: &mut dyn Completer) -> Result<Action, io::Error> {
+    setup_terminal()?;
+    let result = self.read_raw_line(completer);
+    // commands get the terminal without bracketed paste
+    self.terminal.write_str("\x1b[?2004l");
+    self.terminal.display()?;
+    restore_terminal(&self.saved_terminal);
+    result
+  }
+
+  fn read_raw_line(&mut self, completer: &mut dyn Completer) -> Result<Action, io::Error> {
+    // drop signals delivered while a foreground command was running; the
+    // command had the screen, so a resize only needs the new size
+    take();
+    self.terminal.update_size();
+    self.terminal.write_str("\x1b[?2004h");
     self.terminal.write_prompt();
     self.terminal.display().unwrap();
 
@@ -57,14 +125,21 @@ impl<'a> Reader {
           Some(Err(e)) => {
             return Err(e);
           }
-          None => {}
+          None => {
+            self.terminal.display()?;
+          }
         }
       }
-      if wait_input() {
+      if !self.pending.is_empty() || wait_input() {
         match self.read_char() {
-          Some(Ok(res)) => {
+          Some(Ok(Input::Paste(text))) => {
+            Handler::handle_paste(&mut self.terminal, &text);
+            self.terminal.highlight(completer);
+            self.terminal.display()?;
+          }
+          Some(Ok(Input::Event(event))) => {
             self.terminal.display().unwrap();
-            match Handler::handle_event(&mut self.terminal, res.0) {
+            match Handler::handle_event(&mut self.terminal, event, completer) {
               Some(Ok(Action::Line(line))) => {
                 self.terminal.display().unwrap();
                 return Ok(Action::Line(line));
@@ -80,6 +155,7 @@ impl<'a> Reader {
                 return Err(e);
               }
               None => {
+                self.terminal.highlight(completer);
                 self.terminal.display().unwrap();
               }
             };
@@ -95,52 +171,141 @@ impl<'a> Reader {
       }
     }
   }
-  fn read_char(&mut self) -> Option<Result<(Event, Vec<u8>), io::Error>> {
-    let mut source = io::stdin();
-    if let Some(c) = self.leftover {
-      // we have a leftover byte, use it
-      self.leftover = None;
-      return Some(parse_event(c, &mut source.bytes()));
+  fn read_char(&mut self) -> Option<Result<Input, io::Error>> {
+    if self.pending.is_empty() {
+      match fill(&mut self.pending, None) {
+        Ok(0) => return None,
+        Ok(_) => {}
+        Err(e) => return Some(Err(e)),
+      }
     }
-    let mut buf = [0u8; 2];
-    let res = match source.read(&mut buf) {
-      Ok(0) => {
-        return None;
+    let c = self.pending.pop_front()?;
+    // a lone ESC is the Esc key rather than the start of a sequence
+    if c == b'\x1b' && self.pending.is_empty() {
+      return Some(Ok(Input::Event(Event::Key(Key::Esc))));
+    }
+    let result = parse_event(c, &mut Bytes {
+      pending: &mut self.pending,
+    });
+    match result {
+      Ok((Event::Unsupported(bytes), _)) if bytes == PASTE_START => Some(self.read_paste().map(Input::Paste)),
+      Ok((event, _)) => Some(Ok(Input::Event(event))),
+      Err(e) => Some(Err(e)),
+    }
+  }
+  // Collects pasted bytes up to the closing marker. Terminals send line
+  // breaks in a paste as carriage returns.
+  fn read_paste(&mut self) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    while !bytes.ends_with(PASTE_END) {
+      match self.pending.pop_front() {
+        Some(byte) => bytes.push(byte),
+        None => {
+          if fill(&mut self.pending, None)? == 0 {
+            break;
+          }
+        }
       }
-      Ok(1) => match buf[0] {
-        b'\x1B' => Ok((Event::Key(Key::Esc), vec![b'\x1B'])),
-        c => parse_event(c, &mut source.bytes()),
-      },
-      Ok(2) => {
-        let option_iter = &mut Some(buf[1]).into_iter();
-        let result = {
-          let mut iter = option_iter.map(|c| Ok(c)).chain(source.bytes());
-          parse_event(buf[0], &mut iter)
-        };
-        self.leftover = option_iter.next();
-        result
+    }
+    if bytes.ends_with(PASTE_END) {
+      bytes.truncate(bytes.len() - PASTE_END.len());
+    }
+    Ok(String::from_utf8_lossy(&bytes).replace("\r\n", "\n").replace('\r', "\n"))
+  }
+}
+// Feeds termion's parser from the pending bytes, waiting briefly for the
+// rest of a sequence that arrived split across reads.
+struct Bytes<'a> {
+  pending: &'a mut VecDeque<u8>,
+}
+impl Iterator for Bytes<'_> {
+  type Item = io::Result<u8>;
+  fn next(&mut self) -> Option<io::Result<u8>> {
+    if self.pending.is_empty() {
+      match fill(self.pending, Some(50)) {
+        Ok(0) => return None,
+        Ok(_) => {}
+        Err(e) => return Some(Err(e)),
       }
-      Ok(_) => unreachable!(),
-      Err(e) => Err(e),
-    };
-    Some(res)
+    }
+    self.pending.pop_front().map(Ok)
+  }
+}
+// Reads what the terminal has ready, waiting up to `timeout` ms for it
+// or as long as it takes with None. Returns the number of bytes read,
+// 0 on timeout or end of input. Unlike a buffered stdin this leaves
+// nothing hidden from select.
+fn fill(pending: &mut VecDeque<u8>, timeout: Option<i32>) -> io::Result<usize> {
+  let fd = libc::STDIN_FILENO;
+  let mut poll = libc::pollfd {
+    fd,
+    events: libc::POLLIN,
+    revents: 0,
+  };
+  loop {
+    match unsafe { libc::poll(&mut poll, 1, timeout.unwrap_or(-1)) } {
+      0 => return Ok(0),
+      -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+      -1 => return Err(io::Error::last_os_error()),
+      _ => break,
+    }
+  }
+  let mut buf = [0u8; 1024];
+  loop {
+    match nix::unistd::read(fd, &mut buf) {
+      Ok(n) => {
+        pending.extend(&buf[..n]);
+        return Ok(n);
+      }
+      Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => continue,
+      Err(e) => return Err(io::Error::other(e)),
+    }
   }
 }
 fn setup_terminal() -> io::Result<termios::Termios> {
   use termios::*;
   let fd = io::stdout().as_raw_fd();
-  let mut termios = Termios::from_fd(fd).unwrap();
-  let saved_terminal = termios.clone();
+  let mut termios = Termios::from_fd(fd)?;
+  let saved_terminal = termios;
   termios.c_cflag |= CREAD | CLOCAL;
   termios.c_lflag &= !(ICANON | ECHO);
   termios.c_oflag &= !OPOST;
-  termios.c_iflag &= !(INLCR | ICRNL);
+  termios.c_iflag &= !(INLCR | ICRNL | IXON);
 
   termios.c_cc[VMIN] = 0;
   termios.c_cc[VTIME] = 0;
-  termios::tcsetattr(fd, TCSANOW, &mut termios).unwrap();
+  termios::tcsetattr(fd, TCSANOW, &termios)?;
   Ok(saved_terminal)
 }
+// Runs `f` with the terminal back in canonical mode, for programs such
+// as an editor started while a line is being read.
+pub fn cooked<T>(f: impl FnOnce() -> T) -> io::Result<T> {
+  use termios::*;
+  let fd = io::stdout().as_raw_fd();
+  let raw = Termios::from_fd(fd)?;
+  let mut termios = raw;
+  termios.c_lflag |= ICANON | ECHO;
+  termios.c_oflag |= OPOST;
+  termios.c_iflag |= ICRNL;
+  tcsetattr(fd, TCSANOW, &termios)?;
+  let result = f();
+  tcsetattr(fd, TCSANOW, &raw)?;
+  Ok(result)
+}
+// Raw mode disables output post-processing, so a bare "\n" would not
+// return the cursor to the first column.
+pub fn newline(fd: RawFd) -> &'static str {
+  use termios::*;
+  match Termios::from_fd(fd) {
+    Ok(termios) if termios.c_oflag & OPOST == 0 => "\r\n",
+    _ => "\n",
+  }
+}
+fn restore_terminal(saved_terminal: &termios::Termios) {
+  use termios::*;
+  let fd = io::stdout().as_raw_fd();
+  let _ = tcsetattr(fd, TCSADRAIN, saved_terminal);
+}
 fn wait_input() -> bool {
   let stdin_fileno = io::stdout().as_raw_fd();
   let mut r_fds = FdSet::new();
@@ -148,17 +313,15 @@ fn wait_input() -> bool {
 
   let mut e_fds = FdSet::new();
 
-  loop {
-    match select(
-      stdin_fileno + 1,
-      Some(&mut r_fds),
-      None,
-      Some(&mut e_fds),
-      None.as_mut(),
-    ) {
-      Ok(n) => return n == 1,
-      Err(_e) => return false,
-    }
+  match select(
+    stdin_fileno + 1,
+    Some(&mut r_fds),
+    None,
+    Some(&mut e_fds),
+    None.as_mut(),
+  ) {
+    Ok(n) => n == 1,
+    Err(_e) => false,
   }
 }
 fn parse_event<I>(item: u8, iter: &mut I) -> Result<(Event, Vec<u8>), io::Error>
@@ -0,0 +1,136 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+  Backward,
+  Forward,
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+  pub query: String,
+  pub direction: Direction,
+  pub failed: bool,
+  // history index of the match on display; None while still showing the
+  // line the search started from
+  pub index: Option<usize>,
+  // char offset of the query inside the match
+  pub offset: usize,
+  pub original: String,
+  pub original_pos: usize,
+}
+
+impl Search {
+  pub fn new(direction: Direction, original: String, original_pos: usize) -> Search {
+    Search {
+      query: String::new(),
+      direction,
+      failed: false,
+      index: None,
+      offset: original_pos,
+      original,
+      original_pos,
+    }
+  }
+
+  pub fn label(&self) -> String {
+    let name = match self.direction {
+      Direction::Backward => "reverse-i-search",
+      Direction::Forward => "i-search",
+    };
+    match self.failed {
+      true => format!("(failed {})'{}': ", name, self.query),
+      false => format!("({})'{}': ", name, self.query),
+    }
+  }
+
+  // Looks for the query starting at the current match (inclusive) or, when
+  // `skip` is set, at the next entry in the search direction.
+  pub fn update(&mut self, commands: &[String], skip: bool) {
+    if self.query.is_empty() {
+      self.failed = false;
+      return;
+    }
+    let current = self.index.map(|idx| commands[idx].as_str());
+    let start = match (self.index, self.direction, skip) {
+      (None, Direction::Backward, _) => commands.len().checked_sub(1),
+      (None, Direction::Forward, _) => None,
+      (Some(idx), _, false) => Some(idx),
+      (Some(idx), Direction::Backward, true) => idx.checked_sub(1),
+      (Some(idx), Direction::Forward, true) => Some(idx + 1),
+    };
+    let found = start.and_then(|start| {
+      find(commands, &self.query, start, self.direction, match skip {
+        true => current,
+        false => None,
+      })
+    });
+    match found {
+      Some((idx, offset)) => {
+        self.index = Some(idx);
+        self.offset = offset;
+        self.failed = false;
+      }
+      None => self.failed = true,
+    }
+  }
+}
+
+// Returns the history index and char offset of the first command holding
+// `query`, walking from `start` in `direction`. Commands equal to `skip`
+// are passed over so repeated searches do not stop on duplicates.
+pub fn find(
+  commands: &[String],
+  query: &str,
+  start: usize,
+  direction: Direction,
+  skip: Option<&str>,
+) -> Option<(usize, usize)> {
+  let matches = |idx: usize| {
+    let command = &commands[idx];
+    if skip == Some(command.as_str()) {
+      return None;
+    }
+    let byte = match direction {
+      Direction::Backward => command.rfind(query),
+      Direction::Forward => command.find(query),
+    }?;
+    Some((idx, command[..byte].chars().count()))
+  };
+  match direction {
+    Direction::Backward => (0..=start.min(commands.len().checked_sub(1)?)).rev().find_map(matches),
+    Direction::Forward => (start..commands.len()).find_map(matches),
+  }
+}
+
+#[cfg(test)]
+pub mod search_test {
+  use super::*;
+  fn commands() -> Vec<String> {
+    vec!["make test".to_string(), "git status".to_string(), "make build".to_string(), "ls".to_string()]
+  }
+  #[test]
+  fn find_test() {
+    let commands = commands();
+    assert_eq!(find(&commands, "make", 3, Direction::Backward, None), Some((2, 0)));
+    assert_eq!(find(&commands, "make", 1, Direction::Backward, None), Some((0, 0)));
+    assert_eq!(find(&commands, "stat", 0, Direction::Forward, None), Some((1, 4)));
+    assert_eq!(find(&commands, "nope", 3, Direction::Backward, None), None);
+  }
+  #[test]
+  fn update_test() {
+    let commands = commands();
+    let mut search = Search::new(Direction::Backward, String::new(), 0);
+    search.query.push_str("mak");
+    search.update(&commands, false);
+    assert_eq!(search.index, Some(2));
+    search.update(&commands, true);
+    assert_eq!(search.index, Some(0));
+    search.update(&commands, true);
+    assert!(search.failed);
+    assert_eq!(search.index, Some(0));
+    assert_eq!(search.label(), "(failed reverse-i-search)'mak': ");
+    search.direction = Direction::Forward;
+    search.update(&commands, true);
+    assert_eq!(search.index, Some(2));
+    assert!(!search.failed);
+  }
+}
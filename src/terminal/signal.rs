@@ -27,10 +27,7 @@ pub fn take() -> Option<Signal> {
   if n == !0 {
     None
   } else {
-    match Signal::try_from(n as libc::c_int).ok() {
-      Some(signal) => Some(signal),
-      _ => None,
-    }
+    Signal::try_from(n as libc::c_int).ok()
   }
 }
 pub fn prepare() -> io::Result<()> {
@@ -44,6 +41,13 @@ pub fn prepare() -> io::Result<()> {
   let _ = unsafe { sigaction(Signal::SIGTSTP, &sig_action).unwrap() };
   let _ = unsafe { sigaction(Signal::SIGCONT, &sig_action).unwrap() };
   let _ = unsafe { sigaction(Signal::SIGQUIT, &sig_action).unwrap() };
+  // a resize must not cut short a wait on the job in the foreground
+  let resize_action = signal::SigAction::new(
+    signal::SigHandler::Handler(handle_signal),
+    signal::SaFlags::SA_RESTART,
+    signal::SigSet::empty(),
+  );
+  let _ = unsafe { sigaction(Signal::SIGWINCH, &resize_action).unwrap() };
 
   Ok(())
 }
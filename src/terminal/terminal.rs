@@ -1,9 +1,19 @@
 use super::buffer::Buffer;
+use super::complete::{self, unquote, Completer};
 use super::cursor;
 use super::history::History;
+use super::kill::KillRing;
+use super::layout::{clip, Layout};
+use super::menu::Menu;
+use super::search::{Direction, Search};
+use super::undo::Undo;
+use super::vi::{Mode, Vi};
 use super::window::{get_winsize, Winsize};
+use crate::parser::span::{self, Kind};
 use libc::STDOUT_FILENO;
 use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
 use std::env;
 use std::io::{self, Write};
 use std::rc::Rc;
@@ -11,112 +21,139 @@ use std::rc::Rc;
 #[derive(Debug)]
 pub struct Terminal {
   pub pos: usize,
+  // screen row of the cursor, counted from the row the prompt ends on
+  row: usize,
   stdout_buffer: String,
   buffer: Rc<RefCell<Buffer>>,
-  pub history: History,
+  pub history: Rc<RefCell<History>>,
   pub prompt: String,
   pub window_size: Winsize,
+  pub search: Option<Search>,
+  // the last key was a Tab that left the word ambiguous
+  pub tabbed: bool,
+  pub menu: Option<Menu>,
+  pub kill_ring: KillRing,
+  // the last key killed text, so the next kill joins it
+  pub killed: bool,
+  // where the last yank put its text, for yank-pop
+  pub yanked: Option<(usize, usize)>,
+  // characters besides alphanumerics that word motions treat as word
+  pub word_chars: String,
+  pub undo: Undo,
+  // Ctrl-X was pressed and the next key completes the sequence
+  pub ctrl_x: bool,
+  // modal state when `set -o vi` is on
+  pub vi: Option<Vi>,
+  // color the line as it is typed
+  pub highlight: bool,
+  // whether each command word seen on this line runs something
+  commands: HashMap<String, Option<bool>>,
+  // char offsets of the bracket or quote pair at the cursor
+  matched: Option<(usize, usize)>,
 }
 impl Terminal {
   pub fn new() -> Terminal {
-    let terminal = Terminal {
+    Terminal {
       pos: 0,
-      history: History::new(),
+      row: 0,
+      history: Rc::new(RefCell::new(History::new())),
       buffer: Rc::new(RefCell::new(Buffer::new())),
       stdout_buffer: String::new(),
       // prompt: format!("{} $ ",env::current_dir().unwrap().to_str().unwrap()).into(),
       prompt: "$ ".into(),
-      window_size: get_winsize(STDOUT_FILENO).unwrap(),
-    };
-    return terminal;
+      window_size: get_winsize(STDOUT_FILENO).ok().filter(|size| size.ws_col > 0).unwrap_or_default(),
+      search: None,
+      tabbed: false,
+      menu: None,
+      kill_ring: KillRing::new(),
+      killed: false,
+      yanked: None,
+      word_chars: String::new(),
+      undo: Undo::new(),
+      ctrl_x: false,
+      vi: None,
+      highlight: false,
+      commands: HashMap::new(),
+      matched: None,
+    }
   }
   pub fn set_buffer(&mut self) {
-    self.history.set_buffer(self.buffer.borrow().clone());
+    self.history.borrow_mut().set_buffer(self.buffer.borrow().clone());
   }
   pub fn update_buffer(&mut self, string: &str) {
     self.buffer = Rc::new(RefCell::new(Buffer::from(string)));
   }
   pub fn reset(&mut self) {
     self.pos = 0;
+    self.row = 0;
     self.buffer.borrow_mut().clear();
+    self.undo.clear();
+    self.commands.clear();
+    self.matched = None;
+    if let Some(vi) = self.vi.as_mut() {
+      vi.reset();
+      // hand the terminal's default cursor back to the command
+      self.stdout_buffer.push_str("\x1b[0 q");
+    }
+  }
+  pub fn line(&self) -> String {
+    self.buffer.borrow().as_str()
   }
   pub fn get(&mut self) -> Option<String> {
     let line = self.buffer.borrow().as_str();
-    if line.len() == 0 {
+    if line.is_empty() {
       return None;
     }
-    return Some(line);
+    Some(line)
   }
   pub fn push(&mut self, string: &str) {
-    if self.pos == self.buffer.borrow().len() {
-      self.buffer.borrow_mut().insert_str(self.pos, string);
-      self.stdout_buffer.push_str(string);
-      self.pos += string.len();
-    } else {
-      self.buffer.borrow_mut().insert_str(self.pos, string);
+    let pos = self.pos;
+    let at_end = pos == self.buffer.borrow().len();
+    let end = self.buffer.borrow_mut().insert_str(pos, string);
+    if !at_end {
       self.clear_to_screen_end();
-      self
-        .stdout_buffer
-        .push_str(&self.buffer.borrow_mut().as_str().get(self.pos..).unwrap());
-      self.pos += string.len();
-      self.move_to(self.pos);
     }
+    // a combining mark redraws the char it joins
+    let start = pos.min(end.saturating_sub(1));
+    if start < pos {
+      self.move_to(start);
+    }
+    self.write_from(start);
+    self.move_to(end);
   }
   pub fn delete_left(&mut self, n: usize) {
     let old_pos = self.pos;
-    if self.pos >= n {
-      self.pos -= n;
-    } else {
-      self.pos = 0;
-    }
-    self.buffer.borrow_mut().remove(self.pos, old_pos);
-    self.move_to(self.pos);
+    let pos = self.pos.saturating_sub(n);
+    self.buffer.borrow_mut().remove(pos, old_pos);
+    self.move_to(pos);
     self.clear_to_screen_end();
-    self
-      .stdout_buffer
-      .push_str(&self.buffer.borrow().as_str().get(self.pos..).unwrap());
-    self.move_to(self.pos);
+    self.write_from(pos);
+    self.move_to(pos);
   }
   pub fn delete_right(&mut self, n: usize) {
-    let old_pos = self.pos;
-    if self.pos + n <= self.buffer.borrow().len() {
-      self.pos += n;
-    } else {
-      self.pos = self.buffer.borrow().len();
-    }
-    self.buffer.borrow_mut().remove(old_pos, self.pos);
+    let pos = self.pos;
+    let len = self.buffer.borrow().len();
+    self.buffer.borrow_mut().remove(pos, (pos + n).min(len));
     self.clear_to_screen_end();
-    self
-      .stdout_buffer
-      .push_str(&self.buffer.borrow().as_str().get(old_pos..).unwrap());
-    self.move_to(old_pos);
+    self.write_from(pos);
+    self.move_to(pos);
   }
 
   pub fn clear_screen(&mut self) {
     self
       .stdout_buffer
       .push_str(&format!("\x1b[2J\x1b[1;1H{}", self.prompt));
+    self.row = 0;
   }
   pub fn clear_to_screen_end(&mut self) {
     self.stdout_buffer.push_str(&cursor::clear_to_screen_end());
   }
   pub fn move_left(&mut self, n: usize) {
-    if self.pos == 0 {
-      return;
-    }
-    self.pos -= n;
-    self.stdout_buffer.push_str(&cursor::move_left(n));
+    self.move_to(self.pos.saturating_sub(n));
   }
   pub fn move_right(&mut self, n: usize) {
-    let buf = self.buffer.borrow();
-    if self.pos == buf.len() {
-      return;
-    }
-    self.pos += n;
-    if self.pos > buf.len() {
-      self.pos = buf.len();
-    }
-    self.stdout_buffer.push_str(&cursor::move_right(n));
+    let len = self.buffer.borrow().len();
+    self.move_to((self.pos + n).min(len));
   }
   pub fn move_to_first(&mut self) {
     self.move_to(0);
@@ -125,11 +162,518 @@ impl Terminal {
     let n = self.buffer.borrow().len();
     self.move_to(n);
   }
+  // Reads the window size again, keeping the last good one when the
+  // terminal cannot tell.
+  pub fn update_size(&mut self) {
+    if let Ok(size) = get_winsize(STDOUT_FILENO) {
+      if size.ws_col > 0 {
+        self.window_size = size;
+      }
+    }
+  }
+  // Lays the prompt and line out again after the window changed size.
+  pub fn resize(&mut self) {
+    self.update_size();
+    self.move_to_cell(0, 0);
+    self.clear_to_screen_end();
+    if self.search.is_some() {
+      return self.render_search();
+    }
+    self.write_prompt_line();
+    self.write_buffer();
+    self.move_to(self.pos);
+    if self.menu.is_some() {
+      self.render_menu();
+    }
+  }
+  pub fn layout(&self) -> Layout {
+    Layout::new(&self.prompt, self.window_size.ws_col as usize)
+  }
+  fn graphemes(&self) -> Vec<String> {
+    self.buffer.borrow().graphemes().to_vec()
+  }
+  pub fn len(&self) -> usize {
+    self.buffer.borrow().len()
+  }
+  // Char offset of grapheme `idx`, and back, for the parts of the editor
+  // that count chars.
+  pub fn char_offset(&self, idx: usize) -> usize {
+    self.buffer.borrow().char_offset(idx)
+  }
+  pub fn index_of(&self, offset: usize) -> usize {
+    self.buffer.borrow().index_of(offset)
+  }
   pub fn move_to(&mut self, n: usize) {
+    let (row, col) = self.layout().position(&self.graphemes(), n);
     self.pos = n;
-    self
-      .stdout_buffer
-      .push_str(&cursor::move_to(self.prompt.len() + n + 1));
+    self.move_to_cell(row, col);
+  }
+  fn move_to_cell(&mut self, row: usize, col: usize) {
+    if row < self.row {
+      self.stdout_buffer.push_str(&cursor::move_up(self.row - row));
+    } else if row > self.row {
+      self.stdout_buffer.push_str(&cursor::move_down(row - self.row));
+    }
+    self.row = row;
+    self.stdout_buffer.push_str(&cursor::move_to(col + 1));
+  }
+  // Moves to the same column on the row above or below, returning false
+  // on the first or last row so Up and Down can fall back to history.
+  pub fn move_row(&mut self, up: bool) -> bool {
+    let layout = self.layout();
+    let chars = self.graphemes();
+    let (row, col) = layout.position(&chars, self.pos);
+    let (last, _) = layout.position(&chars, chars.len());
+    let target = match up {
+      true if row > 0 => row - 1,
+      false if row < last => row + 1,
+      _ => return false,
+    };
+    let pos = layout.index_at(&chars, target, col);
+    self.move_to(pos);
+    true
+  }
+  // Writes the line from `start`, where the cursor is, to its end. With
+  // highlighting an edit can recolor what comes before it, so the whole
+  // line is written.
+  fn write_from(&mut self, start: usize) {
+    let graphemes = self.graphemes();
+    let mut start = start;
+    if self.highlight && start > 0 {
+      self.move_to_cell(0, self.layout().start);
+      start = 0;
+    }
+    let styles = self.styles(&graphemes);
+    let mut current = "";
+    for (grapheme, style) in graphemes.iter().zip(&styles).skip(start) {
+      if style != current {
+        if !current.is_empty() {
+          self.stdout_buffer.push_str("\x1b[0m");
+        }
+        self.stdout_buffer.push_str(style);
+        current = style;
+      }
+      self.stdout_buffer.push_str(&grapheme.replace('\n', "\r\n"));
+    }
+    if !current.is_empty() {
+      self.stdout_buffer.push_str("\x1b[0m");
+    }
+    let (row, col) = self.layout().position(&graphemes, graphemes.len());
+    // step off a full row so the cursor really is where `position` says
+    if start < graphemes.len() && col == 0 && graphemes.last().map(String::as_str) != Some("\n") {
+      self.stdout_buffer.push_str("\r\n");
+    }
+    self.row = row;
+  }
+  // The escape sequence coloring each grapheme, empty for plain text.
+  fn styles(&self, graphemes: &[String]) -> Vec<String> {
+    if !self.highlight {
+      return vec![String::new(); graphemes.len()];
+    }
+    let line = graphemes.concat();
+    let chars: Vec<char> = line.chars().collect();
+    let mut styles = vec![String::new(); chars.len()];
+    for span in span::spans(&line) {
+      let color = match span.kind {
+        Kind::Command => {
+          let name = unquote(&chars[span.start..span.end].iter().collect::<String>());
+          match self.commands.get(&name) {
+            Some(Some(true)) => "\x1b[32m",
+            Some(Some(false)) => "\x1b[31m",
+            _ => "",
+          }
+        }
+        Kind::Argument => "",
+        Kind::String => "\x1b[33m",
+        Kind::Variable => "\x1b[36m",
+        Kind::Operator => "\x1b[35m",
+        Kind::Redirection => "\x1b[34m",
+        Kind::Comment => "\x1b[90m",
+      };
+      for style in &mut styles[span.start..span.end] {
+        *style = color.to_string();
+      }
+    }
+    if let Some((open, close)) = self.matched {
+      for n in [open, close] {
+        if let Some(style) = styles.get_mut(n) {
+          style.push_str("\x1b[1;4m");
+        }
+      }
+    }
+    let mut offset = 0;
+    graphemes
+      .iter()
+      .map(|grapheme| {
+        let style = styles[offset].clone();
+        offset += grapheme.chars().count();
+        style
+      })
+      .collect()
+  }
+  // Looks up command words not seen on this line yet and follows the
+  // cursor to the bracket or quote pair it is on, drawing the line again
+  // when the colors change.
+  pub fn highlight(&mut self, completer: &mut dyn Completer) {
+    if !self.highlight || self.search.is_some() || self.menu.is_some() {
+      return;
+    }
+    let line = self.line();
+    let chars: Vec<char> = line.chars().collect();
+    let mut changed = false;
+    for span in span::spans(&line).into_iter().filter(|span| span.kind == Kind::Command) {
+      let name = unquote(&chars[span.start..span.end].iter().collect::<String>());
+      if let Entry::Vacant(entry) = self.commands.entry(name) {
+        let known = completer.is_command(entry.key());
+        changed |= known.is_some();
+        entry.insert(known);
+      }
+    }
+    let matched = span::matching(&line, self.char_offset(self.pos));
+    if matched != self.matched {
+      self.matched = matched;
+      changed = true;
+    }
+    if changed {
+      let pos = self.pos;
+      self.redraw(pos);
+    }
+  }
+  // Takes the bracket highlight off a line that stays on screen.
+  pub fn clear_match(&mut self) {
+    if self.matched.take().is_some() {
+      let pos = self.pos;
+      self.redraw(pos);
+    }
+  }
+  // The text before the cursor when Up/Down should match on it.
+  pub fn search_prefix(&self) -> Option<String> {
+    if !self.history.borrow().prefix_search || self.pos == 0 {
+      return None;
+    }
+    Some(self.buffer.borrow().slice(0, self.pos))
+  }
+  pub fn start_search(&mut self, direction: Direction) {
+    let line = self.buffer.borrow().as_str();
+    self.search = Some(Search::new(direction, line, self.char_offset(self.pos)));
+    self.render_search();
+  }
+  // Edits the query (None drops its last char) or, with `skip`, moves on
+  // to the next match in `direction`.
+  pub fn update_search(&mut self, edit: Option<Option<char>>, direction: Direction, skip: bool) {
+    let commands: Vec<String> =
+      self.history.borrow().cmd_list.iter().map(|entry| entry.command.clone()).collect();
+    let search = match self.search.as_mut() {
+      Some(search) => search,
+      None => return,
+    };
+    search.direction = direction;
+    match edit {
+      Some(Some(c)) => search.query.push(c),
+      Some(None) => {
+        search.query.pop();
+        search.index = None;
+      }
+      None => {}
+    }
+    search.update(&commands, skip);
+    self.render_search();
+  }
+  pub fn render_search(&mut self) {
+    let search = match &self.search {
+      Some(search) => search,
+      None => return,
+    };
+    let line = match search.index {
+      Some(idx) => self.history.borrow().cmd_list[idx].command.clone(),
+      None => search.original.clone(),
+    };
+    let label = search.label();
+    let mut output = format!("\r{}{}", cursor::clear_to_screen_end(), label);
+    let text: Vec<String> = Buffer::from(format!("{}{}", label, line)).graphemes().to_vec();
+    let start = search.offset.min(line.chars().count());
+    let end = match search.index {
+      Some(_) => start + search.query.chars().count(),
+      None => start,
+    };
+    for (i, c) in line.chars().enumerate() {
+      if i == start && end > start {
+        output.push_str("\x1b[7m");
+      }
+      output.push(c);
+      if i + 1 == end && end > start {
+        output.push_str("\x1b[0m");
+      }
+    }
+    // the label replaces the prompt, so lay the text out from column 0
+    let layout = Layout::new("", self.window_size.ws_col as usize);
+    let target = layout.position(&text, Buffer::from(label.as_str()).len() + Buffer::from(line.as_str()).index_of(start));
+    self.move_to_cell(0, 0);
+    self.stdout_buffer.push_str(&output.replace('\n', "\r\n"));
+    self.row = layout.position(&text, text.len()).0;
+    self.move_to_cell(target.0, target.1);
+  }
+  // Leaves search mode, keeping the match in the buffer or restoring the
+  // line the search started from.
+  pub fn finish_search(&mut self, accept: bool) {
+    let search = match self.search.take() {
+      Some(search) => search,
+      None => return,
+    };
+    let (line, pos) = match (accept, search.index) {
+      (true, Some(idx)) => {
+        // Continue history navigation from the accepted entry.
+        let mut history = self.history.borrow_mut();
+        if history.is_end() {
+          history.set_buffer(Buffer::from(search.original.as_str()));
+        }
+        history.pos = idx;
+        (history.cmd_list[idx].command.clone(), search.offset)
+      }
+      _ => (search.original.clone(), search.original_pos),
+    };
+    if line != search.original {
+      let original_pos = Buffer::from(search.original.as_str()).index_of(search.original_pos);
+      self.undo.record((search.original, original_pos), false);
+    }
+    self.update_buffer(&line);
+    self.move_to_cell(0, 0);
+    self.clear_to_screen_end();
+    self.write_prompt_line();
+    self.write_buffer();
+    self.move_to(self.index_of(pos));
+  }
+  // Completes the word before the cursor. A second Tab on an ambiguous
+  // word lists the candidates instead.
+  pub fn complete(&mut self, completer: &mut dyn Completer, listing: bool) {
+    let line = self.buffer.borrow().as_str();
+    let request = complete::request(&line, self.char_offset(self.pos));
+    let mut candidates = completer.complete(&request);
+    candidates.sort_by(|a, b| a.value.cmp(&b.value));
+    candidates.dedup_by(|a, b| a.value == b.value);
+    if candidates.is_empty() {
+      self.write_str("\x07");
+      return;
+    }
+    self.tabbed = candidates.len() > 1;
+    match complete::replacement(&request, &candidates) {
+      Some(text) => self.replace(self.index_of(request.start), self.index_of(request.pos), &text),
+      None if listing => {
+        self.menu = Some(Menu::new(request, candidates));
+        self.render_menu();
+      }
+      None => self.write_str("\x07"),
+    }
+  }
+  // Draws the line with the selected candidate in place and the visible
+  // part of the grid below it, then returns the cursor to the line.
+  pub fn render_menu(&mut self) {
+    let width = self.window_size.ws_col as usize;
+    let (line, pos) = match self.menu.as_ref() {
+      Some(menu) => splice(&menu.request, &menu.insertion(false)),
+      None => return,
+    };
+    self.update_buffer(&line);
+    // the grid gets the rows below the prompt and line, less one for the
+    // scroll indicator
+    let graphemes = self.graphemes();
+    let (last, _) = self.layout().position(&graphemes, graphemes.len());
+    let height = (self.window_size.ws_row as usize).saturating_sub(last + 2);
+    let menu = match self.menu.as_mut() {
+      Some(menu) => menu,
+      None => return,
+    };
+    let (start, end) = menu.visible(width, height);
+    let (rows, _, _) = menu.layout(width);
+    let mut lines = menu.lines(width, start, end);
+    if rows > end - start {
+      let status = format!("rows {} to {} of {}", start + 1, end, rows);
+      lines.push(clip(&status, width).to_string());
+    }
+    self.move_to(0);
+    self.clear_to_screen_end();
+    self.write_buffer();
+    for line in &lines {
+      self.write_str("\r\n");
+      self.write_str(line);
+    }
+    if !lines.is_empty() {
+      self.write_str(&cursor::move_up(lines.len()));
+    }
+    self.move_to(self.index_of(pos));
+  }
+  // Leaves the menu, keeping the selection or restoring the line it was
+  // opened on. `finish` completes the word with its suffix.
+  pub fn close_menu(&mut self, accept: bool, finish: bool) {
+    let menu = match self.menu.take() {
+      Some(menu) => menu,
+      None => return,
+    };
+    let (line, pos) = match accept {
+      true => splice(&menu.request, &menu.insertion(finish)),
+      false => (menu.request.line.clone(), menu.request.pos),
+    };
+    if line != menu.request.line {
+      let original_pos = Buffer::from(menu.request.line.as_str()).index_of(menu.request.pos);
+      self.undo.record((menu.request.line.clone(), original_pos), false);
+    }
+    self.update_buffer(&line);
+    self.move_to(0);
+    self.clear_to_screen_end();
+    self.write_buffer();
+    self.move_to(self.index_of(pos));
+  }
+  // Replaces the graphemes between `start` and `end` and redraws the line.
+  pub fn replace(&mut self, start: usize, end: usize, text: &str) {
+    let pos = self.buffer.borrow_mut().replace(start, end, text);
+    self.redraw(pos);
+  }
+  pub fn redraw(&mut self, pos: usize) {
+    self.move_to(0);
+    self.clear_to_screen_end();
+    self.write_buffer();
+    self.move_to(pos);
+  }
+  pub fn set_line(&mut self, line: &str, pos: usize) {
+    self.update_buffer(line);
+    self.redraw(pos);
+  }
+  fn is_word(&self) -> impl Fn(char) -> bool {
+    let word_chars = self.word_chars.clone();
+    move |c: char| c.is_alphanumeric() || word_chars.contains(c)
+  }
+  fn word_start(&self) -> usize {
+    self.buffer.borrow().word_start(self.pos, self.is_word())
+  }
+  fn word_end(&self) -> usize {
+    self.buffer.borrow().word_end(self.pos, self.is_word())
+  }
+  // Removes `start..end` into the kill ring, joining the previous kill
+  // when `append` is set.
+  pub fn kill(&mut self, start: usize, end: usize, append: bool) {
+    if start == end {
+      return;
+    }
+    let text = self.buffer.borrow().slice(start, end);
+    self.kill_ring.kill(&text, append, end <= self.pos);
+    self.killed = true;
+    self.replace(start, end, "");
+  }
+  pub fn kill_line_end(&mut self, append: bool) {
+    let len = self.buffer.borrow().len();
+    self.kill(self.pos, len, append);
+  }
+  pub fn kill_line_start(&mut self, append: bool) {
+    self.kill(0, self.pos, append);
+  }
+  // Ctrl-W stops at whitespace rather than at word characters.
+  pub fn kill_word_back(&mut self, append: bool, whitespace: bool) {
+    let start = match whitespace {
+      true => self.buffer.borrow().word_start(self.pos, |c: char| !c.is_whitespace()),
+      false => self.word_start(),
+    };
+    self.kill(start, self.pos, append);
+  }
+  pub fn kill_word_forward(&mut self, append: bool) {
+    let end = self.word_end();
+    self.kill(self.pos, end, append);
+  }
+  pub fn yank(&mut self) {
+    let text = match self.kill_ring.yank() {
+      Some(text) => text.clone(),
+      None => return,
+    };
+    let pos = self.pos;
+    self.replace(pos, pos, &text);
+    self.yanked = Some((pos, self.pos));
+  }
+  // Swaps the text of the last yank for the next older kill.
+  pub fn yank_pop(&mut self, yanked: Option<(usize, usize)>) {
+    let (start, end) = match yanked {
+      Some(range) => range,
+      None => return self.write_str("\x07"),
+    };
+    let text = match self.kill_ring.rotate() {
+      Some(text) => text.clone(),
+      None => return,
+    };
+    self.replace(start, end, &text);
+    self.yanked = Some((start, self.pos));
+  }
+  pub fn move_word_left(&mut self) {
+    let pos = self.word_start();
+    self.move_to(pos);
+  }
+  pub fn move_word_right(&mut self) {
+    let pos = self.word_end();
+    self.move_to(pos);
+  }
+  // Swaps the chars around the cursor, or the last two at the end of
+  // the line, and moves past them.
+  pub fn transpose_chars(&mut self) {
+    let len = self.buffer.borrow().len();
+    if len < 2 || self.pos == 0 {
+      return self.write_str("\x07");
+    }
+    let pos = self.pos.min(len - 1) + 1;
+    let swapped = format!("{}{}", self.buffer.borrow().slice(pos - 1, pos), self.buffer.borrow().slice(pos - 2, pos - 1));
+    self.replace(pos - 2, pos, &swapped);
+  }
+  // Swaps the word before the cursor with the one at or after it.
+  pub fn transpose_words(&mut self) {
+    let buffer = self.buffer.borrow().clone();
+    let is_word = self.is_word();
+    let end2 = buffer.word_end(self.pos, &is_word);
+    let start2 = buffer.word_start(end2, &is_word);
+    let start1 = buffer.word_start(start2, &is_word);
+    let end1 = buffer.word_end(start1, &is_word);
+    if start1 == start2 || end1 > start2 {
+      return self.write_str("\x07");
+    }
+    let text = format!(
+      "{}{}{}",
+      buffer.slice(start2, end2),
+      buffer.slice(end1, start2),
+      buffer.slice(start1, end1)
+    );
+    self.replace(start1, end2, &text);
+  }
+  // Upper-cases, lower-cases or capitalizes to the end of the word.
+  pub fn change_case(&mut self, case: char) {
+    let end = self.word_end();
+    let text = self.buffer.borrow().slice(self.pos, end);
+    let changed = match case {
+      'u' => text.to_uppercase(),
+      'l' => text.to_lowercase(),
+      _ => {
+        let is_word = self.is_word();
+        let mut capitalized = String::new();
+        let mut first = true;
+        for c in text.chars() {
+          match (first, is_word(c)) {
+            (true, true) => {
+              capitalized.extend(c.to_uppercase());
+              first = false;
+            }
+            _ => capitalized.extend(c.to_lowercase()),
+          }
+        }
+        capitalized
+      }
+    };
+    self.replace(self.pos, end, &changed);
+  }
+  pub fn undo(&mut self) {
+    match self.undo.undo((self.line(), self.pos)) {
+      Some((line, pos)) => self.set_line(&line, pos),
+      None => self.write_str("\x07"),
+    }
+  }
+  pub fn redo(&mut self) {
+    match self.undo.redo((self.line(), self.pos)) {
+      Some((line, pos)) => self.set_line(&line, pos),
+      None => self.write_str("\x07"),
+    }
   }
   pub fn display(&mut self) -> io::Result<()> {
     self.write(&self.stdout_buffer)?;
@@ -140,17 +684,44 @@ impl Terminal {
     self.stdout_buffer.push_str(s);
   }
   pub fn write_line(&mut self) {
-    self.stdout_buffer.push_str("\n");
+    self.stdout_buffer.push('\n');
   }
   pub fn write_linefeed(&mut self) {
-    self.stdout_buffer.push_str("\r");
+    self.stdout_buffer.push('\r');
   }
   pub fn write_buffer(&mut self) {
-    let string = self.buffer.borrow().as_str();
-    self.write_str(&string);
+    self.write_from(0);
   }
   pub fn write_prompt(&mut self) {
-    self.write_str(&format!("{}", self.prompt.clone()));
+    self.write_str(&self.prompt.clone());
+    self.write_cursor_shape();
+  }
+  // Earlier lines of a multi-line prompt are still on screen when the
+  // line is drawn again.
+  fn write_prompt_line(&mut self) {
+    let prompt = self.prompt.rsplit('\n').next().unwrap_or("").to_string();
+    self.write_str(&prompt);
+  }
+  // A bar cursor in vi insert mode and a block in normal mode.
+  pub fn write_cursor_shape(&mut self) {
+    match self.vi.as_ref().map(|vi| vi.mode) {
+      Some(Mode::Insert) => self.write_str("\x1b[6 q"),
+      Some(Mode::Normal) => self.write_str("\x1b[2 q"),
+      None => {}
+    }
+  }
+  // Leaves vi insert mode, stepping back onto the last char typed. In
+  // normal mode it only drops a half-typed command.
+  pub fn vi_escape(&mut self) {
+    if let Some(vi) = self.vi.as_mut() {
+      if vi.mode == Mode::Normal {
+        vi.cancel();
+        return;
+      }
+      let pos = vi.escape(self.pos);
+      self.move_to(pos);
+      self.write_cursor_shape();
+    }
   }
   fn write(&self, s: &str) -> io::Result<()> {
     let stdout = io::stdout();
@@ -160,7 +731,19 @@ impl Terminal {
   }
 }
 
+// The request's line with its word replaced by `text`, and the cursor
+// position after it.
+fn splice(request: &complete::Request, text: &str) -> (String, usize) {
+  let mut line: String = request.line.chars().take(request.start).collect();
+  line.push_str(text);
+  let pos = line.chars().count();
+  line.extend(request.line.chars().skip(request.pos));
+  (line, pos)
+}
+
 #[cfg(test)]
+// the older tests pass their literals through .into()
+#[allow(clippy::useless_conversion)]
 pub mod terminal_test {
   use super::Terminal;
   #[test]
@@ -186,7 +769,7 @@ pub mod terminal_test {
     terminal.set_buffer();
     assert_eq!(
       Some(&terminal.buffer.borrow().as_str()),
-      terminal.history.get_buffer()
+      terminal.history.borrow_mut().get_buffer().cloned().as_ref()
     );
   }
   #[test]
@@ -218,10 +801,101 @@ pub mod terminal_test {
     assert_eq!(terminal.get(), Some(string));
   }
   #[test]
+  fn kill_yank_test() {
+    let mut terminal = Terminal::new();
+    terminal.push("echo hello world");
+    terminal.kill_word_back(false, true);
+    terminal.kill_word_back(true, true);
+    assert_eq!(terminal.line(), "echo ");
+    terminal.yank();
+    assert_eq!(terminal.line(), "echo hello world");
+    terminal.move_to_first();
+    terminal.kill_word_forward(false);
+    assert_eq!(terminal.line(), " hello world");
+    terminal.move_to_end();
+    terminal.yank();
+    terminal.yank_pop(terminal.yanked);
+    assert_eq!(terminal.line(), " hello worldhello world");
+  }
+  #[test]
+  fn transpose_test() {
+    let mut terminal = Terminal::new();
+    terminal.push("sl");
+    terminal.transpose_chars();
+    assert_eq!(terminal.line(), "ls");
+    terminal.set_line("cp src dst", 10);
+    terminal.transpose_words();
+    assert_eq!(terminal.line(), "cp dst src");
+    terminal.move_to_first();
+    terminal.transpose_words();
+    assert_eq!(terminal.line(), "cp dst src");
+  }
+  #[test]
+  fn word_test() {
+    let mut terminal = Terminal::new();
+    terminal.set_line("git commit-all HEAD", 0);
+    terminal.move_word_right();
+    assert_eq!(terminal.pos, 3);
+    terminal.change_case('c');
+    assert_eq!(terminal.line(), "git Commit-all HEAD");
+    terminal.change_case('u');
+    assert_eq!(terminal.line(), "git Commit-ALL HEAD");
+    terminal.word_chars = "-".to_string();
+    terminal.move_word_left();
+    assert_eq!(terminal.pos, 4);
+    terminal.change_case('l');
+    assert_eq!(terminal.line(), "git commit-all HEAD");
+  }
+  #[test]
   fn push_test() {
     let mut terminal = Terminal::new();
     let string: String = "dawdawd0981740 54 !!@!@$ ui h2".into();
     terminal.push(&string);
     assert_eq!(terminal.buffer.borrow().as_str(), string);
   }
+  #[test]
+  fn highlight_test() {
+    use super::super::complete::{Candidate, Completer, Request};
+    struct Known;
+    impl Completer for Known {
+      fn complete(&mut self, _request: &Request) -> Vec<Candidate> {
+        Vec::new()
+      }
+      fn is_command(&mut self, name: &str) -> Option<bool> {
+        Some(name == "ls")
+      }
+    }
+    let mut terminal = Terminal::new();
+    terminal.highlight = true;
+    terminal.set_line("ls (x) | nope", 3);
+    terminal.highlight(&mut Known);
+    let styles = terminal.styles(&terminal.graphemes());
+    assert_eq!(styles[0], "\x1b[32m");
+    assert_eq!(styles[3], "\x1b[35m\x1b[1;4m");
+    assert_eq!(styles[4], "\x1b[31m");
+    assert_eq!(styles[5], "\x1b[35m\x1b[1;4m");
+    assert_eq!(styles[9], "\x1b[31m");
+    terminal.clear_match();
+    assert_eq!(terminal.styles(&terminal.graphemes())[3], "\x1b[35m");
+  }
+  #[test]
+  fn menu_height_test() {
+    use super::super::complete::{request, Candidate};
+    use super::super::menu::Menu;
+    let mut terminal = Terminal::new();
+    terminal.window_size.ws_col = 10;
+    terminal.window_size.ws_row = 6;
+    let line = "echo 12345678 ";
+    terminal.set_line(line, 14);
+    let candidates = (0..10).map(|n| Candidate::new(&format!("a{}", n))).collect();
+    let mut menu = Menu::new(request(line, 14), candidates);
+    menu.selected = 4;
+    terminal.menu = Some(menu);
+    terminal.stdout_buffer.clear();
+    terminal.render_menu();
+    // two rows of line leave three for the grid and one for the indicator,
+    // which is cut to the width
+    assert!(terminal.stdout_buffer.ends_with("\r\nrows 3 to \x1b[4A\x1b[9G"));
+    assert_eq!(terminal.stdout_buffer.matches("\r\n").count(), 4);
+  }
 }
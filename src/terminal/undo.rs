@@ -0,0 +1,94 @@
+// Snapshots of the line taken before each edit, so undo and redo can
+// step back and forth through them. A run of typed chars shares one
+// snapshot and comes back as a single step.
+#[derive(Debug, Clone, Default)]
+pub struct Undo {
+  undo: Vec<(String, usize)>,
+  redo: Vec<(String, usize)>,
+  // the last edit typed a char, so the next typed char joins it
+  typing: bool,
+}
+
+impl Undo {
+  pub fn new() -> Undo {
+    Undo::default()
+  }
+
+  // Records the line as it was before an edit. `typed` edits join the
+  // run of typed chars before them.
+  pub fn record(&mut self, before: (String, usize), typed: bool) {
+    if !(typed && self.typing) {
+      self.undo.push(before);
+    }
+    self.typing = typed;
+    self.redo.clear();
+  }
+
+  // Makes the next typed chars part of the last step, like the text a vi
+  // change command goes on to insert.
+  pub fn join_next(&mut self) {
+    self.typing = true;
+  }
+
+  // Ends the current run of typed chars, e.g. when the cursor moves.
+  pub fn split(&mut self) {
+    self.typing = false;
+  }
+
+  pub fn undo(&mut self, current: (String, usize)) -> Option<(String, usize)> {
+    let previous = self.undo.pop()?;
+    self.redo.push(current);
+    self.typing = false;
+    Some(previous)
+  }
+
+  pub fn redo(&mut self, current: (String, usize)) -> Option<(String, usize)> {
+    let next = self.redo.pop()?;
+    self.undo.push(current);
+    self.typing = false;
+    Some(next)
+  }
+
+  pub fn clear(&mut self) {
+    self.undo.clear();
+    self.redo.clear();
+    self.typing = false;
+  }
+}
+
+#[cfg(test)]
+pub mod undo_test {
+  use super::*;
+  fn state(line: &str) -> (String, usize) {
+    (line.to_string(), line.len())
+  }
+  #[test]
+  fn group_test() {
+    let mut undo = Undo::new();
+    undo.record(state(""), true);
+    undo.record(state("l"), true);
+    undo.record(state("ls"), false);
+    undo.record(state("ls -"), true);
+    undo.split();
+    undo.record(state("ls -l"), true);
+    assert_eq!(undo.undo(state("ls -la")), Some(state("ls -l")));
+    assert_eq!(undo.undo(state("ls -l")), Some(state("ls -")));
+    assert_eq!(undo.undo(state("ls -")), Some(state("ls")));
+    assert_eq!(undo.undo(state("ls")), Some(state("")));
+    assert_eq!(undo.undo(state("")), None);
+  }
+  #[test]
+  fn redo_test() {
+    let mut undo = Undo::new();
+    undo.record(state(""), true);
+    undo.record(state("a"), false);
+    assert_eq!(undo.undo(state("ab ")), Some(state("a")));
+    assert_eq!(undo.undo(state("a")), Some(state("")));
+    assert_eq!(undo.redo(state("")), Some(state("a")));
+    assert_eq!(undo.redo(state("a")), Some(state("ab ")));
+    assert_eq!(undo.redo(state("ab ")), None);
+    undo.undo(state("ab "));
+    undo.record(state("a"), true);
+    assert_eq!(undo.redo(state("ax")), None);
+  }
+}
@@ -0,0 +1,606 @@
+use super::buffer::{base, Buffer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mode {
+  #[default]
+  Insert,
+  Normal,
+}
+
+// What a normal-mode key asks the line editor to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Effect {
+  // more keys are needed
+  Pending,
+  Edit(String, usize),
+  Move(usize),
+  // -1 for the previous history entry, 1 for the next
+  History(isize),
+  Undo,
+  Editor,
+  Bell,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cmd {
+  count: usize,
+  op: Option<char>,
+  key: char,
+  arg: Option<char>,
+}
+
+enum Parse {
+  Incomplete,
+  Complete(Cmd),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Vi {
+  pub mode: Mode,
+  pending: String,
+  last_find: Option<(char, char)>,
+  register: String,
+  // keys of the last change and the text its insert session typed
+  last_change: Option<(String, String)>,
+  // the change that opened the current insert session
+  change: Option<String>,
+  pub inserted: String,
+}
+
+// Kind of text a grapheme belongs to for word motions: blank, word or
+// punctuation. Big words only tell blanks from the rest.
+fn class(grapheme: &str, big: bool) -> u8 {
+  let c = base(grapheme);
+  match c {
+    _ if c.is_whitespace() => 0,
+    _ if big || c.is_alphanumeric() || c == '_' => 1,
+    _ => 2,
+  }
+}
+
+fn graphemes(text: &str) -> Vec<String> {
+  Buffer::from(text).graphemes().to_vec()
+}
+
+// Whether a grapheme is exactly the char typed after `f`, `t` and such.
+fn is(grapheme: &str, c: char) -> bool {
+  grapheme.chars().eq(std::iter::once(c))
+}
+
+fn next_word_start(chars: &[String], pos: usize, big: bool) -> usize {
+  let mut i = pos;
+  if i >= chars.len() {
+    return chars.len();
+  }
+  let start = class(&chars[i], big);
+  if start != 0 {
+    while i < chars.len() && class(&chars[i], big) == start {
+      i += 1;
+    }
+  }
+  while i < chars.len() && class(&chars[i], big) == 0 {
+    i += 1;
+  }
+  i
+}
+
+fn prev_word_start(chars: &[String], pos: usize, big: bool) -> usize {
+  let mut i = pos.min(chars.len());
+  while i > 0 && class(&chars[i - 1], big) == 0 {
+    i -= 1;
+  }
+  if i == 0 {
+    return 0;
+  }
+  let start = class(&chars[i - 1], big);
+  while i > 0 && class(&chars[i - 1], big) == start {
+    i -= 1;
+  }
+  i
+}
+
+fn word_end(chars: &[String], pos: usize, big: bool) -> usize {
+  let mut i = pos + 1;
+  while i < chars.len() && class(&chars[i], big) == 0 {
+    i += 1;
+  }
+  if i >= chars.len() {
+    return chars.len().saturating_sub(1);
+  }
+  let start = class(&chars[i], big);
+  while i + 1 < chars.len() && class(&chars[i + 1], big) == start {
+    i += 1;
+  }
+  i
+}
+
+// Range of an `i`/`a` text object around `pos`, end exclusive.
+fn text_object(chars: &[String], pos: usize, around: bool, object: char) -> Option<(usize, usize)> {
+  if pos >= chars.len() {
+    return None;
+  }
+  match object {
+    'w' | 'W' => {
+      let big = object == 'W';
+      let kind = class(&chars[pos], big);
+      let mut start = pos;
+      while start > 0 && class(&chars[start - 1], big) == kind {
+        start -= 1;
+      }
+      let mut end = pos;
+      while end < chars.len() && class(&chars[end], big) == kind {
+        end += 1;
+      }
+      if around {
+        let trailing = end;
+        while end < chars.len() && class(&chars[end], big) == 0 {
+          end += 1;
+        }
+        if end == trailing {
+          while start > 0 && class(&chars[start - 1], big) == 0 {
+            start -= 1;
+          }
+        }
+      }
+      Some((start, end))
+    }
+    '"' | '\'' | '`' => {
+      let open = (0..=pos).rev().find(|&i| is(&chars[i], object))?;
+      let close = match open == pos {
+        true => (pos + 1..chars.len()).find(|&i| is(&chars[i], object))?,
+        false => (pos..chars.len()).find(|&i| is(&chars[i], object))?,
+      };
+      match around {
+        true => Some((open, close + 1)),
+        false => Some((open + 1, close)),
+      }
+    }
+    _ => {
+      let (open, close) = match object {
+        '(' | ')' | 'b' => ('(', ')'),
+        '{' | '}' | 'B' => ('{', '}'),
+        '[' | ']' => ('[', ']'),
+        '<' | '>' => ('<', '>'),
+        _ => return None,
+      };
+      let mut depth = 0;
+      let mut start = None;
+      for i in (0..=pos).rev() {
+        match base(&chars[i]) {
+          c if c == close && i != pos => depth += 1,
+          c if c == open && depth == 0 => {
+            start = Some(i);
+            break;
+          }
+          c if c == open => depth -= 1,
+          _ => {}
+        }
+      }
+      let start = start?;
+      depth = 0;
+      let mut end = None;
+      for (i, grapheme) in chars.iter().enumerate().skip(start + 1) {
+        let c = base(grapheme);
+        match c {
+          _ if c == open => depth += 1,
+          _ if c == close && depth == 0 => {
+            end = Some(i);
+            break;
+          }
+          _ if c == close => depth -= 1,
+          _ => {}
+        }
+      }
+      let end = end?;
+      match around {
+        true => Some((start, end + 1)),
+        false => Some((start + 1, end)),
+      }
+    }
+  }
+}
+
+fn parse(keys: &str) -> Option<Parse> {
+  let mut chars = keys.chars().peekable();
+  let count = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+    let mut digits = String::new();
+    while let Some(&c) = chars.peek() {
+      if !c.is_ascii_digit() || (c == '0' && digits.is_empty()) {
+        break;
+      }
+      digits.push(c);
+      chars.next();
+    }
+    digits.parse::<usize>().ok()
+  };
+  let first = count(&mut chars);
+  let mut key = match chars.next() {
+    Some(key) => key,
+    None => return Some(Parse::Incomplete),
+  };
+  let mut op = None;
+  let mut second = None;
+  if "dcy".contains(key) {
+    op = Some(key);
+    second = count(&mut chars);
+    key = match chars.next() {
+      Some(key) => key,
+      None => return Some(Parse::Incomplete),
+    };
+  }
+  let needs_arg = "fFtTr".contains(key) || (op.is_some() && (key == 'i' || key == 'a'));
+  let arg = match needs_arg {
+    true => match chars.next() {
+      Some(arg) => Some(arg),
+      None => return Some(Parse::Incomplete),
+    },
+    false => None,
+  };
+  if chars.next().is_some() {
+    return None;
+  }
+  Some(Parse::Complete(Cmd {
+    count: first.unwrap_or(1) * second.unwrap_or(1),
+    op,
+    key,
+    arg,
+  }))
+}
+
+impl Vi {
+  pub fn new() -> Vi {
+    Vi::default()
+  }
+
+  // Leaves insert mode, finishing the change `.` will repeat. Returns
+  // where the cursor goes.
+  pub fn escape(&mut self, pos: usize) -> usize {
+    self.mode = Mode::Normal;
+    self.pending.clear();
+    if let Some(change) = self.change.take() {
+      self.last_change = Some((change, std::mem::take(&mut self.inserted)));
+    }
+    self.inserted.clear();
+    pos.saturating_sub(1)
+  }
+
+  // Drops a half-typed normal-mode command.
+  pub fn cancel(&mut self) {
+    self.pending.clear();
+  }
+
+  // Starts a fresh line in insert mode.
+  pub fn reset(&mut self) {
+    self.mode = Mode::Insert;
+    self.pending.clear();
+    self.change = None;
+    self.inserted.clear();
+  }
+
+  pub fn feed(&mut self, key: char, line: &str, pos: usize) -> Effect {
+    self.pending.push(key);
+    let keys = self.pending.clone();
+    let cmd = match parse(&keys) {
+      Some(Parse::Incomplete) => return Effect::Pending,
+      Some(Parse::Complete(cmd)) => cmd,
+      None => {
+        self.pending.clear();
+        return Effect::Bell;
+      }
+    };
+    self.pending.clear();
+    if cmd.key == '.' && cmd.op.is_none() {
+      return self.repeat(line, pos);
+    }
+    let effect = self.execute(&cmd, line, pos);
+    let changes = cmd.op.is_some_and(|op| op != 'y') || "xXsSCDr~pPiaIA".contains(cmd.key);
+    if changes && effect != Effect::Bell {
+      match self.mode {
+        Mode::Insert => self.change = Some(keys),
+        Mode::Normal => self.last_change = Some((keys, String::new())),
+      }
+    }
+    effect
+  }
+
+  fn repeat(&mut self, line: &str, pos: usize) -> Effect {
+    let (keys, inserted) = match self.last_change.clone() {
+      Some(change) => change,
+      None => return Effect::Bell,
+    };
+    let cmd = match parse(&keys) {
+      Some(Parse::Complete(cmd)) => cmd,
+      _ => return Effect::Bell,
+    };
+    match self.execute(&cmd, line, pos) {
+      Effect::Edit(line, pos) if self.mode == Mode::Insert => {
+        let mut chars = graphemes(&line);
+        let inserted = graphemes(&inserted);
+        let len = inserted.len();
+        chars.splice(pos..pos, inserted);
+        self.mode = Mode::Normal;
+        self.change = None;
+        Effect::Edit(chars.concat(), (pos + len).saturating_sub(1))
+      }
+      effect => effect,
+    }
+  }
+
+  // Where a motion lands and whether an operator includes that char.
+  fn target(&mut self, chars: &[String], pos: usize, cmd: &Cmd) -> Option<(usize, bool)> {
+    let len = chars.len();
+    let mut target = pos;
+    let inclusive = match cmd.key {
+      'h' => {
+        target = pos.saturating_sub(cmd.count);
+        false
+      }
+      'l' | ' ' => {
+        target = (pos + cmd.count).min(len);
+        false
+      }
+      '0' => {
+        target = 0;
+        false
+      }
+      '^' => {
+        target = chars.iter().position(|g| !base(g).is_whitespace()).unwrap_or(len);
+        false
+      }
+      '$' => {
+        target = len;
+        false
+      }
+      'w' | 'W' => {
+        for _ in 0..cmd.count {
+          target = next_word_start(chars, target, cmd.key == 'W');
+        }
+        false
+      }
+      'b' | 'B' => {
+        for _ in 0..cmd.count {
+          target = prev_word_start(chars, target, cmd.key == 'B');
+        }
+        false
+      }
+      'e' | 'E' => {
+        for _ in 0..cmd.count {
+          target = word_end(chars, target, cmd.key == 'E');
+        }
+        true
+      }
+      'f' | 'F' | 't' | 'T' | ';' | ',' => {
+        let (kind, c) = match (cmd.key, cmd.arg) {
+          (';', _) => self.last_find?,
+          (',', _) => {
+            let (kind, c) = self.last_find?;
+            let reversed = match kind {
+              'f' => 'F',
+              'F' => 'f',
+              't' => 'T',
+              _ => 't',
+            };
+            (reversed, c)
+          }
+          (kind, Some(c)) => {
+            self.last_find = Some((kind, c));
+            (kind, c)
+          }
+          _ => return None,
+        };
+        // a repeated `t` steps past the char it stopped before
+        let skip = (cmd.key == ';' || cmd.key == ',') as usize;
+        for _ in 0..cmd.count {
+          target = match kind {
+            'f' => (target + 1..len).find(|&i| is(&chars[i], c))?,
+            't' => (target + 1 + skip..len).find(|&i| is(&chars[i], c))? - 1,
+            'F' => (0..target).rev().find(|&i| is(&chars[i], c))?,
+            _ => (0..target.saturating_sub(skip)).rev().find(|&i| is(&chars[i], c))? + 1,
+          };
+        }
+        kind == 'f' || kind == 't'
+      }
+      _ => return None,
+    };
+    Some((target, inclusive))
+  }
+
+  fn execute(&mut self, cmd: &Cmd, line: &str, pos: usize) -> Effect {
+    let chars = graphemes(line);
+    let len = chars.len();
+    let last = len.saturating_sub(1);
+    let edit = |chars: Vec<String>, pos: usize| Effect::Edit(chars.concat(), pos);
+    if let Some(op) = cmd.op {
+      let range = match cmd.key {
+        key if key == op => Some((0, len)),
+        'i' | 'a' => cmd.arg.and_then(|object| text_object(&chars, pos, cmd.key == 'a', object)),
+        // `cw` on a word changes only to its end, like `ce`
+        'w' | 'W' if op == 'c' && pos < len && !base(&chars[pos]).is_whitespace() => {
+          let big = cmd.key == 'W';
+          let at_end = pos + 1 >= len || class(&chars[pos + 1], big) != class(&chars[pos], big);
+          let mut end = match at_end {
+            true => pos,
+            false => word_end(&chars, pos, big),
+          };
+          for _ in 1..cmd.count {
+            end = word_end(&chars, end, big);
+          }
+          Some((pos, end + 1))
+        }
+        _ => self.target(&chars, pos, cmd).map(|(target, inclusive)| match target < pos {
+          true => (target, pos),
+          false => (pos, (target + inclusive as usize).min(len)),
+        }),
+      };
+      let (start, end) = match range {
+        Some(range) => range,
+        None => return Effect::Bell,
+      };
+      self.register = chars[start..end].concat();
+      let mut rest = chars.clone();
+      match op {
+        'y' => return Effect::Move(start.min(last)),
+        'd' => {
+          rest.drain(start..end);
+          let pos = start.min(rest.len().saturating_sub(1));
+          return edit(rest, pos);
+        }
+        _ => {
+          rest.drain(start..end);
+          self.mode = Mode::Insert;
+          return edit(rest, start);
+        }
+      }
+    }
+    let count = cmd.count;
+    let mut chars = chars;
+    match cmd.key {
+      'i' => self.insert(chars, pos),
+      'a' => self.insert(chars, (pos + 1).min(len)),
+      'I' => {
+        let start = chars.iter().position(|g| !base(g).is_whitespace()).unwrap_or(len);
+        self.insert(chars, start)
+      }
+      'A' => self.insert(chars, len),
+      'x' | 'X' if len == 0 => Effect::Bell,
+      'x' => {
+        let end = (pos + count).min(len);
+        self.register = chars.drain(pos..end).collect();
+        let pos = pos.min(chars.len().saturating_sub(1));
+        edit(chars, pos)
+      }
+      'X' => {
+        let start = pos.saturating_sub(count);
+        self.register = chars.drain(start..pos).collect();
+        edit(chars, start)
+      }
+      's' => self.execute(&Cmd { op: Some('c'), key: 'l', ..cmd.clone() }, line, pos),
+      'S' => self.execute(&Cmd { op: Some('c'), key: 'c', ..cmd.clone() }, line, pos),
+      'C' => self.execute(&Cmd { op: Some('c'), key: '$', ..cmd.clone() }, line, pos),
+      'D' => self.execute(&Cmd { op: Some('d'), key: '$', ..cmd.clone() }, line, pos),
+      'Y' => self.execute(&Cmd { op: Some('y'), key: 'y', ..cmd.clone() }, line, pos),
+      'r' => match cmd.arg {
+        Some(c) if pos + count <= len => {
+          for grapheme in &mut chars[pos..pos + count] {
+            *grapheme = c.to_string();
+          }
+          edit(chars, pos + count - 1)
+        }
+        _ => Effect::Bell,
+      },
+      '~' if len > 0 => {
+        let end = (pos + count).min(len);
+        for grapheme in &mut chars[pos..end] {
+          *grapheme = grapheme
+            .chars()
+            .map(|c| match c.is_uppercase() {
+              true => c.to_lowercase().next().unwrap_or(c),
+              false => c.to_uppercase().next().unwrap_or(c),
+            })
+            .collect();
+        }
+        edit(chars, end.min(len - 1))
+      }
+      'p' | 'P' if self.register.is_empty() => Effect::Bell,
+      'p' | 'P' => {
+        let at = match cmd.key {
+          'p' => (pos + 1).min(len),
+          _ => pos,
+        };
+        let text = graphemes(&self.register.repeat(count));
+        let added = text.len();
+        chars.splice(at..at, text);
+        edit(chars, at + added - 1)
+      }
+      'u' => Effect::Undo,
+      'v' => Effect::Editor,
+      'k' | '-' => Effect::History(-1),
+      'j' | '+' => Effect::History(1),
+      _ => match self.target(&chars, pos, cmd) {
+        Some((target, _)) => Effect::Move(target.min(last)),
+        None => Effect::Bell,
+      },
+    }
+  }
+
+  fn insert(&mut self, chars: Vec<String>, pos: usize) -> Effect {
+    self.mode = Mode::Insert;
+    Effect::Edit(chars.concat(), pos)
+  }
+}
+
+#[cfg(test)]
+pub mod vi_test {
+  use super::*;
+  // Feeds normal-mode keys and returns the resulting line and cursor.
+  fn run(line: &str, pos: usize, keys: &str) -> (String, usize) {
+    let mut vi = Vi::new();
+    vi.mode = Mode::Normal;
+    let mut line = line.to_string();
+    let mut pos = pos;
+    for key in keys.chars() {
+      if vi.mode == Mode::Insert {
+        match key {
+          '\x1b' => pos = vi.escape(pos),
+          _ => {
+            let mut chars = graphemes(&line);
+            chars.insert(pos, key.to_string());
+            line = chars.concat();
+            vi.inserted.push(key);
+            pos += 1;
+          }
+        }
+        continue;
+      }
+      match vi.feed(key, &line, pos) {
+        Effect::Edit(new, new_pos) => {
+          line = new;
+          pos = new_pos;
+        }
+        Effect::Move(new_pos) => pos = new_pos,
+        _ => {}
+      }
+    }
+    (line, pos)
+  }
+  #[test]
+  fn motion_test() {
+    assert_eq!(run("echo foo.bar baz", 0, "w").1, 5);
+    assert_eq!(run("echo foo.bar baz", 0, "2w").1, 8);
+    assert_eq!(run("echo foo.bar baz", 0, "2W").1, 13);
+    assert_eq!(run("echo foo.bar baz", 15, "b").1, 13);
+    assert_eq!(run("echo foo.bar baz", 0, "e").1, 3);
+    assert_eq!(run("echo foo.bar baz", 5, "$").1, 15);
+    assert_eq!(run("echo foo.bar baz", 5, "0").1, 0);
+    assert_eq!(run("  ls", 3, "^").1, 2);
+    assert_eq!(run("a,b,c,d", 0, "f,;").1, 3);
+    assert_eq!(run("a,b,c,d", 0, "t,").1, 0);
+    assert_eq!(run("a,b,c,d", 0, "t,;").1, 2);
+    assert_eq!(run("a,b,c,d", 6, "F,,").1, 5);
+  }
+  #[test]
+  fn operator_test() {
+    assert_eq!(run("echo foo bar", 5, "dw"), ("echo bar".to_string(), 5));
+    assert_eq!(run("echo foo bar", 5, "d2w"), ("echo ".to_string(), 4));
+    assert_eq!(run("echo foo bar", 5, "cwbaz\x1b"), ("echo baz bar".to_string(), 7));
+    assert_eq!(run("echo foo bar", 5, "D"), ("echo ".to_string(), 4));
+    assert_eq!(run("echo foo bar", 5, "dd"), ("".to_string(), 0));
+    assert_eq!(run("echo foo bar", 9, "db"), ("echo bar".to_string(), 5));
+    assert_eq!(run("echo foo bar", 5, "yep"), ("echo ffoooo bar".to_string(), 8));
+    assert_eq!(run("echo 'a b' c", 7, "di'"), ("echo '' c".to_string(), 6));
+    assert_eq!(run("f (a (b) c)", 4, "ca(x\x1b"), ("f x".to_string(), 2));
+    assert_eq!(run("echo foo bar", 6, "daw"), ("echo bar".to_string(), 5));
+  }
+  #[test]
+  fn edit_test() {
+    assert_eq!(run("ls", 0, "Atmp\x1b"), ("lstmp".to_string(), 4));
+    assert_eq!(run("abc", 0, "3x"), ("".to_string(), 0));
+    assert_eq!(run("abc", 0, "xp"), ("bac".to_string(), 1));
+    assert_eq!(run("abc", 0, "2rz"), ("zzc".to_string(), 1));
+    assert_eq!(run("abc", 0, "~~"), ("ABc".to_string(), 2));
+    assert_eq!(run("a b c", 0, "dw."), ("c".to_string(), 0));
+    assert_eq!(run("foo foo", 0, "cwbar\x1bw."), ("bar bar".to_string(), 6));
+    assert_eq!(run("ca\u{301}fe", 1, "x"), ("cfe".to_string(), 1));
+    assert_eq!(run("日本語 x", 0, "wx"), ("日本語 ".to_string(), 3));
+  }
+}
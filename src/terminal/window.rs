@@ -22,3 +22,14 @@ pub struct Winsize {
   pub ws_xpixel: c_ushort,
   pub ws_ypixel: c_ushort,
 }
+
+impl Default for Winsize {
+  fn default() -> Self {
+    Winsize {
+      ws_row: 24,
+      ws_col: 80,
+      ws_xpixel: 0,
+      ws_ypixel: 0,
+    }
+  }
+}
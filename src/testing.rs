@@ -0,0 +1,45 @@
+use crate::runtime::shell::{Reports, Shell};
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+
+// A scratch directory for one test, removed when it goes out of scope so
+// a failing assertion does not leave it behind. `name` keeps tests that
+// run in parallel apart.
+pub struct TempDir {
+  path: PathBuf,
+}
+
+impl TempDir {
+  pub fn new(name: &str) -> TempDir {
+    let path = env::temp_dir().join(format!("rustsh-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    TempDir { path }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+    self.path.join(path)
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.path);
+  }
+}
+
+// Keeps what `shell` reports from here on instead of printing it, since
+// the test harness holds on to stderr.
+pub fn collect_reports(shell: &mut Shell) -> Rc<RefCell<Vec<String>>> {
+  let messages = Rc::new(RefCell::new(Vec::new()));
+  shell.reports = Reports::Collect(messages.clone());
+  messages
+}
@@ -1,5 +1,7 @@
 use super::input::Input;
 use super::output::Output;
+use super::tokens::Redirection;
+use std::io;
 
 #[derive(Debug,Clone)]
 pub struct Command {
@@ -7,6 +9,8 @@ pub struct Command {
   pub args: Vec<String>,
   pub input: Option<Input>,
   pub output: Option<Output>,
+  pub error: Option<Output>,
+  pub redirects: Vec<(Redirection, String)>,
 }
 impl Command {
   pub fn new(
@@ -20,12 +24,14 @@ impl Command {
       args,
       input,
       output,
+      error: None,
+      redirects: Vec::new(),
     }
   }
 }
 impl PartialEq for Command {
   fn eq(&self, other: &Self) -> bool {
-    self.command == other.command && self.args == other.args
+    self.command == other.command && self.args == other.args && self.redirects == other.redirects
   }
 }
 impl Command {
@@ -35,4 +41,16 @@ impl Command {
   pub fn inp(&mut self, input: Input) {
     self.input = Some(input);
   }
+  pub fn take_output(&mut self) -> Output {
+    match self.output.take() {
+      Some(output) => output,
+      None => Output::from(io::stdout()),
+    }
+  }
+  pub fn take_input(&mut self) -> Input {
+    match self.input.take() {
+      Some(input) => input,
+      None => Input::from(io::stdin()),
+    }
+  }
 }
@@ -1,5 +1,7 @@
 use std::io;
 use std::fs;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::process::Stdio;
 #[derive(Debug)]
 pub enum Input {
   File(fs::File),
@@ -34,3 +36,21 @@ impl io::Read for Input {
     }
   }
 }
+
+impl AsRawFd for Input {
+  fn as_raw_fd(&self) -> RawFd {
+    match *self {
+      Input::File(ref f) => f.as_raw_fd(),
+      Input::Stdin(ref stdin) => stdin.as_raw_fd(),
+    }
+  }
+}
+
+impl From<Input> for Stdio {
+  fn from(input: Input) -> Self {
+    match input {
+      Input::File(f) => Stdio::from(f),
+      Input::Stdin(_) => Stdio::inherit(),
+    }
+  }
+}
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod tokens;
 pub mod command;
 pub mod input;
@@ -1,10 +1,13 @@
 use std::fs;
 use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::process::Stdio;
 
 #[derive(Debug)]
 pub enum Output {
   File(fs::File),
   Stdout(io::Stdout),
+  Stderr(io::Stderr),
 }
 
 impl From<io::Stdout> for Output {
@@ -24,6 +27,7 @@ impl Clone for Output {
       match *self {
           Output::File(ref file) => Output::File(file.try_clone().unwrap()),
           Output::Stdout(_) => Output::Stdout(io::stdout()),
+          Output::Stderr(_) => Output::Stderr(io::stderr()),
       }
   }
 }
@@ -34,18 +38,41 @@ impl io::Write for Output {
     match *self {
       Output::File(ref mut f) => f.write(buf),
       Output::Stdout(ref mut stdout) => stdout.write(buf),
+      Output::Stderr(ref mut stderr) => stderr.write(buf),
     }
   }
   fn write_all(&mut self,buf: &[u8]) -> io::Result<()>{
     match *self{
       Output::File(ref mut f)=>f.write_all(buf),
-      Output::Stdout(ref mut stdout)=>stdout.write_all(buf)
+      Output::Stdout(ref mut stdout)=>stdout.write_all(buf),
+      Output::Stderr(ref mut stderr)=>stderr.write_all(buf)
     }
   }
   fn flush(&mut self)->io::Result<()>{
     match *self{
       Output::File(ref mut f)=>f.flush(),
-      Output::Stdout(ref mut stdout)=>stdout.flush()
+      Output::Stdout(ref mut stdout)=>stdout.flush(),
+      Output::Stderr(ref mut stderr)=>stderr.flush()
+    }
+  }
+}
+
+impl AsRawFd for Output {
+  fn as_raw_fd(&self) -> RawFd {
+    match *self {
+      Output::File(ref f) => f.as_raw_fd(),
+      Output::Stdout(ref stdout) => stdout.as_raw_fd(),
+      Output::Stderr(ref stderr) => stderr.as_raw_fd(),
+    }
+  }
+}
+
+impl From<Output> for Stdio {
+  fn from(output: Output) -> Self {
+    match output {
+      Output::File(f) => Stdio::from(f),
+      Output::Stdout(_) => Stdio::inherit(),
+      Output::Stderr(stderr) => Stdio::from(stderr),
     }
   }
 }
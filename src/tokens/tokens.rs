@@ -3,6 +3,11 @@ use super::command::Command;
 pub enum Redirection {
   Lt,
   Gt,
+  Append,
+  ErrGt,
+  ErrAppend,
+  ErrToOut,
+  OutToErr,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -28,6 +33,29 @@ impl Clone for Redirection {
       Redirection::Gt => Redirection::Gt,
 
       Redirection::Lt => Redirection::Lt,
+      Redirection::Append => Redirection::Append,
+      Redirection::ErrGt => Redirection::ErrGt,
+      Redirection::ErrAppend => Redirection::ErrAppend,
+      Redirection::ErrToOut => Redirection::ErrToOut,
+      Redirection::OutToErr => Redirection::OutToErr,
     }
   }
 }
+
+impl Redirection {
+  // Longest operators first so `>>` is not read as `>` followed by `>file`.
+  pub fn operators() -> [(&'static str, Redirection); 7] {
+    [
+      ("2>&1", Redirection::ErrToOut),
+      (">&2", Redirection::OutToErr),
+      ("2>>", Redirection::ErrAppend),
+      ("2>", Redirection::ErrGt),
+      (">>", Redirection::Append),
+      (">", Redirection::Gt),
+      ("<", Redirection::Lt),
+    ]
+  }
+  pub fn takes_target(&self) -> bool {
+    !matches!(*self, Redirection::ErrToOut | Redirection::OutToErr)
+  }
+}